@@ -1,6 +1,7 @@
 #![allow(clippy::multiple_crate_versions)]
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::io::{self, IsTerminal, Write};
 use std::path::PathBuf;
 use std::process::Command;
@@ -15,6 +16,22 @@ pub enum ApiKey {
     Cmd { cmd: String },
 }
 
+/// Optional columns of the goal table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalColumn {
+    /// Time left until derailment
+    Countdown,
+    /// Amount pledged
+    Pledge,
+    /// Value of the last datapoint
+    LastValue,
+    /// Rate of the bright red line with its units
+    Rate,
+    /// Days since the last entered datapoint
+    DaysSinceEntry,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DisplayConfig {
     #[serde(default = "default_true")]
@@ -23,6 +40,9 @@ pub struct DisplayConfig {
     pub show_last_value: bool,
     #[serde(default = "default_datapoints_limit")]
     pub datapoints_limit: usize,
+    /// Explicit column set; overrides `show_pledge`/`show_last_value` when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<GoalColumn>>,
 }
 
 impl Default for DisplayConfig {
@@ -31,24 +51,213 @@ impl Default for DisplayConfig {
             show_pledge: true,
             show_last_value: false,
             datapoints_limit: default_datapoints_limit(),
+            columns: None,
+        }
+    }
+}
+
+impl DisplayConfig {
+    /// Returns the configured goal table columns, falling back to the
+    /// countdown plus the `show_pledge`/`show_last_value` toggles.
+    #[must_use]
+    pub fn goal_columns(&self) -> Vec<GoalColumn> {
+        if let Some(columns) = &self.columns {
+            return columns.clone();
+        }
+        let mut columns = vec![GoalColumn::Countdown];
+        if self.show_pledge {
+            columns.push(GoalColumn::Pledge);
+        }
+        if self.show_last_value {
+            columns.push(GoalColumn::LastValue);
+        }
+        columns
+    }
+}
+
+/// Order of the goal table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortMode {
+    /// Goals done today last, then by safety buffer
+    #[default]
+    Safebuf,
+    /// By derailment time
+    Urgency,
+    /// Highest pledge first
+    Pledge,
+    /// Alphabetical by slug
+    Slug,
+    /// Longest without an entry first
+    LastEntry,
+}
+
+impl SortMode {
+    pub const VALUES: [Self; 5] = [
+        Self::Safebuf,
+        Self::Urgency,
+        Self::Pledge,
+        Self::Slug,
+        Self::LastEntry,
+    ];
+
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Safebuf => "safebuf",
+            Self::Urgency => "urgency",
+            Self::Pledge => "pledge",
+            Self::Slug => "slug",
+            Self::LastEntry => "last entry",
+        }
+    }
+}
+
+/// Grouping of the goal table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupMode {
+    #[default]
+    None,
+    /// By color zone, most urgent first
+    Zone,
+    /// By first goal tag
+    Tag,
+}
+
+impl GroupMode {
+    pub const VALUES: [Self; 3] = [Self::None, Self::Zone, Self::Tag];
+
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Zone => "zone",
+            Self::Tag => "tag",
+        }
+    }
+}
+
+/// One or several keys bound to a beetui action, e.g. `"j"` or `["j", "Down"]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyBinding {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyBinding {
+    #[must_use]
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            Self::One(key) => vec![key.as_str()],
+            Self::Many(keys) => keys.iter().map(String::as_str).collect(),
         }
     }
 }
 
+/// Base palette of the TUI.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemePreset {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// Color overrides for beetui. Values are color names (`red`, `lightblue`),
+/// `#rrggbb` or 256-color indexes; unset colors come from the preset.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThemeConfig {
+    #[serde(default)]
+    pub preset: ThemePreset,
+    /// Goals due today
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub red: Option<String>,
+    /// Goals due tomorrow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub yellow: Option<String>,
+    /// Goals due in two days
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blue: Option<String>,
+    /// Goals due in three to six days
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub green: Option<String>,
+    /// Goals with a week or more of buffer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub white: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alert: Option<String>,
+    /// Progress spinner, previews and other accents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent: Option<String>,
+    /// Background flash of a goal that just got a datapoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TuiConfig {
     #[serde(default = "default_true")]
     pub refresh_on_start: bool,
+    /// Seconds between background refreshes; 0 disables auto-refresh
+    #[serde(default = "default_auto_refresh_secs")]
+    pub auto_refresh_secs: u64,
+    #[serde(default)]
+    pub sort: SortMode,
+    #[serde(default)]
+    pub group: GroupMode,
+    /// Action name to key(s), overriding the default bindings
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, KeyBinding>,
+    #[serde(default)]
+    pub theme: ThemeConfig,
 }
 
 impl Default for TuiConfig {
     fn default() -> Self {
         Self {
             refresh_on_start: true,
+            auto_refresh_secs: default_auto_refresh_secs(),
+            sort: SortMode::default(),
+            group: GroupMode::default(),
+            keys: BTreeMap::new(),
+            theme: ThemeConfig::default(),
         }
     }
 }
 
+/// A local data source that `beeline autodata` reports to a goal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum AutodataSource {
+    /// Commits per day in a git repository, optionally filtered by author
+    Git {
+        path: String,
+        #[serde(default)]
+        author: Option<String>,
+    },
+    /// Total word count of the markdown files in a directory
+    Wordcount { path: String },
+    /// Number of messages in a Maildir folder
+    Maildir { path: String },
+    /// Completed items per day in a todo.txt file
+    Todotxt { path: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutodataConfig {
+    pub goal: String,
+    #[serde(flatten)]
+    pub source: AutodataSource,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BeeConfig {
     pub api_key: ApiKey,
@@ -57,6 +266,8 @@ pub struct BeeConfig {
     pub display: DisplayConfig,
     #[serde(default)]
     pub tui: TuiConfig,
+    #[serde(default)]
+    pub autodata: Vec<AutodataConfig>,
 }
 
 impl Default for BeeConfig {
@@ -66,6 +277,7 @@ impl Default for BeeConfig {
             default_user: None,
             display: DisplayConfig::default(),
             tui: TuiConfig::default(),
+            autodata: Vec::new(),
         }
     }
 }
@@ -78,6 +290,10 @@ const fn default_datapoints_limit() -> usize {
     20
 }
 
+const fn default_auto_refresh_secs() -> u64 {
+    300
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum BeeConfigError {
     #[error("config error: {0}")]
@@ -266,7 +482,10 @@ pub enum TimestampParseError {
 
 #[cfg(test)]
 mod tests {
-    use super::{format_timestamp, parse_timestamp, ApiKey, BeeConfigError, TimestampParseError};
+    use super::{
+        format_timestamp, parse_timestamp, ApiKey, BeeConfig, BeeConfigError, DisplayConfig,
+        GoalColumn, GroupMode, SortMode, ThemePreset, TimestampParseError,
+    };
     use time::macros::datetime;
 
     #[test]
@@ -301,6 +520,64 @@ mod tests {
         assert!(matches!(err, BeeConfigError::CommandEmpty { .. }));
     }
 
+    #[test]
+    fn goal_columns_fall_back_to_toggles() {
+        let display = DisplayConfig {
+            show_last_value: true,
+            ..DisplayConfig::default()
+        };
+        assert_eq!(
+            display.goal_columns(),
+            vec![
+                GoalColumn::Countdown,
+                GoalColumn::Pledge,
+                GoalColumn::LastValue
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_table_layout_config() {
+        let config: BeeConfig = toml::from_str(
+            r#"
+            api_key = "key"
+            [tui]
+            sort = "last_entry"
+            group = "tag"
+            [display]
+            columns = ["rate", "days_since_entry"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.tui.sort, SortMode::LastEntry);
+        assert_eq!(config.tui.group, GroupMode::Tag);
+        assert_eq!(
+            config.display.goal_columns(),
+            vec![GoalColumn::Rate, GoalColumn::DaysSinceEntry]
+        );
+    }
+
+    #[test]
+    fn parses_key_bindings_and_theme() {
+        let config: BeeConfig = toml::from_str(
+            r#"
+            api_key = "key"
+            [tui.keys]
+            quit = "Q"
+            down = ["Ctrl-n", "Down"]
+            [tui.theme]
+            preset = "light"
+            accent = "cyan"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.tui.keys["quit"].keys(), vec!["Q"]);
+        assert_eq!(config.tui.keys["down"].keys(), vec!["Ctrl-n", "Down"]);
+        assert_eq!(config.tui.theme.preset, ThemePreset::Light);
+        assert_eq!(config.tui.theme.accent.as_deref(), Some("cyan"));
+        assert!(config.tui.theme.red.is_none());
+    }
+
     #[test]
     fn format_timestamp_produces_expected_format() {
         let ts = datetime!(2024-06-15 14:30:45 UTC);
@@ -0,0 +1,266 @@
+use crate::import;
+use anyhow::{Context, Result};
+use beeconfig::{AutodataConfig, AutodataSource};
+use beeminder::day::{daystamp, DayClock};
+use beeminder::types::CreateDatapoint;
+use beeminder::BeeminderClient;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command as ProcessCommand;
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime};
+
+/// Datapoints per goal are small, so one `create_all` request suffices.
+const CHUNK_SIZE: usize = 100;
+
+/// Collects datapoints for every configured source (or just `goal`) and
+/// pushes them with one `requestid` per goal and day, so re-runs update the
+/// day's datapoint instead of adding another.
+pub async fn run(
+    client: &BeeminderClient,
+    sources: &[AutodataConfig],
+    goal: Option<&str>,
+    days: u32,
+    dry_run: bool,
+) -> Result<()> {
+    let selected: Vec<&AutodataConfig> = sources
+        .iter()
+        .filter(|source| goal.is_none_or(|goal| source.goal == goal))
+        .collect();
+    if selected.is_empty() {
+        return Err(anyhow::anyhow!(match goal {
+            Some(goal) => format!("No autodata source configured for goal '{goal}'"),
+            None =>
+                "No autodata sources configured; add [[autodata]] entries to the beeminder config"
+                    .to_string(),
+        }));
+    }
+
+    let today = DayClock::local().day_of(OffsetDateTime::now_utc(), 0);
+    for config in selected {
+        let datapoints = collect(&config.source, today, days)
+            .with_context(|| format!("Failed to collect autodata for {}", config.goal))?;
+        println!("{} ({}):", config.goal, describe(&config.source));
+        for dp in &datapoints {
+            let day = dp.daystamp.as_deref().unwrap_or("");
+            println!("  {day}  {}", dp.value);
+        }
+        if dry_run || datapoints.is_empty() {
+            continue;
+        }
+        let (created, errors) =
+            import::upload(client, &config.goal, &datapoints, CHUNK_SIZE).await?;
+        println!("  Sent {created} datapoints.");
+        if !errors.is_empty() {
+            eprintln!(
+                "{}",
+                serde_json::to_string_pretty(&errors)
+                    .unwrap_or_else(|_| "Failed to format errors".to_string())
+            );
+        }
+    }
+    Ok(())
+}
+
+fn describe(source: &AutodataSource) -> String {
+    match source {
+        AutodataSource::Git { path, .. } => format!("git commits in {path}"),
+        AutodataSource::Wordcount { path } => format!("words in {path}"),
+        AutodataSource::Maildir { path } => format!("messages in {path}"),
+        AutodataSource::Todotxt { path } => format!("completed todos in {path}"),
+    }
+}
+
+/// Reads a source into daily datapoints for the last `days` days.
+///
+/// Per-day sources (git, todo.txt) report every day in the window, including
+/// zero days; snapshot sources (word count, Maildir) report today's total.
+pub fn collect(source: &AutodataSource, today: Date, days: u32) -> Result<Vec<CreateDatapoint>> {
+    let since = today - Duration::days(i64::from(days.max(1)) - 1);
+    let datapoints = match source {
+        AutodataSource::Git { path, author } => {
+            let dates = git_commit_dates(&expand_home(path), author.as_deref(), since)?;
+            daily_counts(dates, since, today)
+        }
+        AutodataSource::Todotxt { path } => {
+            let path = expand_home(path);
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            daily_counts(todotxt_completion_dates(&content), since, today)
+        }
+        AutodataSource::Wordcount { path } => {
+            let words = markdown_word_count(&expand_home(path))?;
+            vec![daily_datapoint(today, words)]
+        }
+        AutodataSource::Maildir { path } => {
+            let messages = maildir_message_count(&expand_home(path))?;
+            vec![daily_datapoint(today, messages)]
+        }
+    };
+    Ok(datapoints)
+}
+
+fn daily_datapoint(day: Date, value: usize) -> CreateDatapoint {
+    let daystamp = daystamp(day);
+    #[allow(clippy::cast_precision_loss)]
+    let value = value as f64;
+    CreateDatapoint::new(value)
+        .with_daystamp(&daystamp)
+        .with_requestid(&format!("beeline-autodata-{daystamp}"))
+}
+
+fn daily_counts(
+    dates: impl IntoIterator<Item = Date>,
+    since: Date,
+    today: Date,
+) -> Vec<CreateDatapoint> {
+    let mut counts: BTreeMap<Date, usize> = BTreeMap::new();
+    let mut day = Some(since);
+    while let Some(current) = day.filter(|current| *current <= today) {
+        counts.insert(current, 0);
+        day = current.next_day();
+    }
+    for date in dates {
+        if let Some(count) = counts.get_mut(&date) {
+            *count += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(day, count)| daily_datapoint(day, count))
+        .collect()
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => std::env::var_os("HOME").map_or_else(
+            || PathBuf::from(path),
+            |home| PathBuf::from(home).join(rest),
+        ),
+        None => PathBuf::from(path),
+    }
+}
+
+fn git_commit_dates(repo: &Path, author: Option<&str>, since: Date) -> Result<Vec<Date>> {
+    let mut cmd = ProcessCommand::new("git");
+    cmd.arg("-C")
+        .arg(repo)
+        .arg("log")
+        .arg("--all")
+        .arg("--no-merges")
+        .arg(format!("--since={since} 00:00"))
+        .arg("--date=short-local")
+        .arg("--format=%ad");
+    if let Some(author) = author {
+        cmd.arg(format!("--author={author}"));
+    }
+    let output = cmd.output().context("Failed to run git")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "git log failed in {}: {}",
+            repo.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| {
+            Date::parse(line.trim(), format_description!("[year]-[month]-[day]"))
+                .with_context(|| format!("Unexpected git date '{line}'"))
+        })
+        .collect()
+}
+
+/// Completion dates of todo.txt items ("x 2026-07-01 ...").
+fn todotxt_completion_dates(content: &str) -> Vec<Date> {
+    content
+        .lines()
+        .filter_map(|line| line.strip_prefix("x "))
+        .filter_map(|rest| rest.split_whitespace().next())
+        .filter_map(|date| Date::parse(date, format_description!("[year]-[month]-[day]")).ok())
+        .collect()
+}
+
+fn markdown_word_count(dir: &Path) -> Result<usize> {
+    let mut total = 0;
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            total += markdown_word_count(&path)?;
+        } else if path.extension().is_some_and(|ext| {
+            ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown")
+        }) {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            total += content.split_whitespace().count();
+        }
+    }
+    Ok(total)
+}
+
+fn maildir_message_count(dir: &Path) -> Result<usize> {
+    let mut total = 0;
+    for sub in ["cur", "new"] {
+        let path = dir.join(sub);
+        let entries = fs::read_dir(&path)
+            .with_context(|| format!("Not a Maildir folder: missing {}", path.display()))?;
+        total += entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_file())
+            .count();
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::date;
+
+    #[test]
+    fn counts_completed_todos_per_day_with_zero_days() {
+        let content = "x 2026-07-01 2026-06-30 write report\n\
+                       x 2026-07-03 call mom\n\
+                       x 2026-07-03 pay bills +home\n\
+                       (A) open task due:2026-07-03\n";
+        let datapoints = daily_counts(
+            todotxt_completion_dates(content),
+            date!(2026 - 07 - 02),
+            date!(2026 - 07 - 03),
+        );
+        assert_eq!(datapoints.len(), 2);
+        assert_eq!(datapoints[0].daystamp.as_deref(), Some("20260702"));
+        assert!((datapoints[0].value - 0.0).abs() < f64::EPSILON);
+        assert!((datapoints[1].value - 2.0).abs() < f64::EPSILON);
+        assert_eq!(
+            datapoints[1].requestid.as_deref(),
+            Some("beeline-autodata-20260703")
+        );
+    }
+
+    #[test]
+    fn counts_markdown_words_and_maildir_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("notes")).unwrap();
+        fs::write(dir.path().join("notes/a.md"), "one two three").unwrap();
+        fs::write(dir.path().join("notes/b.txt"), "ignored words").unwrap();
+        fs::write(dir.path().join("top.md"), "four five").unwrap();
+        assert_eq!(markdown_word_count(dir.path()).unwrap(), 5);
+
+        let maildir = dir.path().join("INBOX");
+        for sub in ["cur", "new", "tmp"] {
+            fs::create_dir_all(maildir.join(sub)).unwrap();
+        }
+        fs::write(maildir.join("cur/1"), "").unwrap();
+        fs::write(maildir.join("new/2"), "").unwrap();
+        fs::write(maildir.join("tmp/3"), "").unwrap();
+        assert_eq!(maildir_message_count(&maildir).unwrap(), 2);
+    }
+}
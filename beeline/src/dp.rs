@@ -0,0 +1,346 @@
+use crate::import::confirm;
+use crate::output::{self, OutputFormat};
+use crate::parse_unix_timestamp;
+use crate::transform::{self, Transform};
+use anyhow::{anyhow, Result};
+use beeminder::changeset::{Change, Changeset};
+use beeminder::day::{DayClock, GoalDay};
+use beeminder::duplicates::{self, DuplicateReason};
+use beeminder::entry::{self, EntryGoal};
+use beeminder::types::{Datapoint, GoalFull, UpdateDatapoint};
+use beeminder::BeeminderClient;
+use clap::Subcommand;
+use regex::Regex;
+use time::{Date, OffsetDateTime};
+
+/// Non-interactive datapoint commands.
+#[derive(Subcommand)]
+pub enum DpCommand {
+    /// List a goal's datapoints, newest first
+    List {
+        /// The name of the goal
+        goal: String,
+        /// Only datapoints on or after this day (YYYY-MM-DD, yesterday, -7, mon, ...)
+        #[arg(long, allow_hyphen_values = true)]
+        since: Option<String>,
+        /// Only datapoints on or before this day
+        #[arg(long, allow_hyphen_values = true)]
+        until: Option<String>,
+        /// Maximum number of datapoints to show
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Show one datapoint
+    Show {
+        /// The name of the goal
+        goal: String,
+        /// Datapoint ID
+        id: String,
+    },
+    /// Update the value, comment or timestamp of a datapoint
+    Update {
+        /// The name of the goal
+        goal: String,
+        /// Datapoint ID
+        id: String,
+        /// New value: a number, arithmetic (10+15) or a duration (1:30, 90m)
+        #[arg(long, allow_hyphen_values = true)]
+        value: Option<String>,
+        /// New comment
+        #[arg(long)]
+        comment: Option<String>,
+        /// New unix timestamp
+        #[arg(long)]
+        timestamp: Option<i64>,
+    },
+    /// Delete datapoints
+    Delete {
+        /// The name of the goal
+        goal: String,
+        /// Datapoint IDs
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Scale, offset, shift, rewrite or delete datapoints in bulk
+    #[command(
+        long_about = "Scale, offset, shift, rewrite or delete datapoints in bulk.\n\nSelect datapoints with --since/--until and --match, pick the edits, review the diff and confirm. Changes are sent in concurrent batches; datapoints changed elsewhere in the meantime are skipped, and failures are reported at the end without stopping the run.\n\nExamples:\n  beeline dp transform running --since 2026-03-01 --until 2026-03-31 --scale 0.001\n  beeline dp transform sleep --since -30 --shift -1h\n  beeline dp transform reading --replace 'pg\\b' --with pages\n  beeline dp transform pushups --match '^test' --delete"
+    )]
+    Transform {
+        /// The name of the goal
+        goal: String,
+        /// Only datapoints on or after this day (YYYY-MM-DD, yesterday, -7, mon, ...)
+        #[arg(long, allow_hyphen_values = true)]
+        since: Option<String>,
+        /// Only datapoints on or before this day
+        #[arg(long, allow_hyphen_values = true)]
+        until: Option<String>,
+        /// Only datapoints whose comment matches this regex
+        #[arg(long = "match", value_name = "REGEX")]
+        matching: Option<String>,
+        /// Multiply values by this factor
+        #[arg(long, allow_hyphen_values = true)]
+        scale: Option<f64>,
+        /// Add this to values, after scaling
+        #[arg(long, allow_hyphen_values = true)]
+        offset: Option<f64>,
+        /// Move timestamps, e.g. -2h, 90m or 1:30
+        #[arg(long, allow_hyphen_values = true)]
+        shift: Option<String>,
+        /// Regex to replace in comments
+        #[arg(long, value_name = "REGEX", requires = "with")]
+        replace: Option<String>,
+        /// Replacement text; $1 refers to capture groups
+        #[arg(long, requires = "replace")]
+        with: Option<String>,
+        /// Delete the selected datapoints
+        #[arg(long, conflicts_with_all = ["scale", "offset", "shift", "replace"])]
+        delete: bool,
+        /// Requests sent concurrently
+        #[arg(long, default_value_t = 10)]
+        batch_size: usize,
+        /// Only show the diff, don't change anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Find duplicate datapoints and delete the extras
+    #[command(
+        long_about = "Find duplicate datapoints and delete the extras.\n\nPoints sharing a requestid are duplicates at any distance; points with the same value, daystamp and comment are duplicates when entered within --window of each other, unless their requestids differ (a resend without a requestid still matches). The earliest point of each group is kept.\n\nExamples:\n  beeline dp dedupe pushups --dry-run\n  beeline dp dedupe running --window 1h"
+    )]
+    Dedupe {
+        /// The name of the goal
+        goal: String,
+        /// Maximum time between two entries of a group, e.g. 10m or 1h
+        #[arg(long, default_value = "10m")]
+        window: String,
+        /// Requests sent concurrently
+        #[arg(long, default_value_t = 10)]
+        batch_size: usize,
+        /// Only show the duplicates, don't delete anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+pub async fn run(client: &BeeminderClient, command: DpCommand, output: OutputFormat) -> Result<()> {
+    match command {
+        DpCommand::List {
+            goal,
+            since,
+            until,
+            limit,
+        } => {
+            let datapoints = list(client, &goal, since.as_deref(), until.as_deref(), limit).await?;
+            if !output.is_table() {
+                return output::print_records(output, &datapoints);
+            }
+            if datapoints.is_empty() {
+                println!("No datapoints.");
+            }
+            for dp in &datapoints {
+                print_datapoint(dp);
+            }
+        }
+        DpCommand::Show { goal, id } => {
+            let datapoints = client.get_datapoints(&goal, None, None, None, None).await?;
+            let dp = datapoints
+                .iter()
+                .find(|dp| dp.id == id)
+                .ok_or_else(|| anyhow!("No datapoint {id} in {goal}"))?;
+            if output.is_table() {
+                print_datapoint(dp);
+            } else {
+                output::print_record(output, dp)?;
+            }
+        }
+        DpCommand::Update {
+            goal,
+            id,
+            value,
+            comment,
+            timestamp,
+        } => {
+            if value.is_none() && comment.is_none() && timestamp.is_none() {
+                return Err(anyhow!(
+                    "Provide at least one of: --value, --comment, --timestamp"
+                ));
+            }
+            let mut update = UpdateDatapoint::new(id);
+            if let Some(value) = value {
+                let goal = client.get_goal_full(&goal, false).await?;
+                update.value = Some(entry::parse_value(&value, &EntryGoal::from(&goal))?);
+            }
+            update.comment = comment;
+            update.timestamp = parse_unix_timestamp(timestamp)?;
+            let updated = client.update_datapoint(&goal, &update).await?;
+            if output.is_table() {
+                print_datapoint(&updated);
+            } else {
+                output::print_record(output, &updated)?;
+            }
+        }
+        DpCommand::Delete { goal, ids } => {
+            let mut deleted = Vec::new();
+            let mut failed = 0;
+            for id in ids {
+                match client.delete_datapoint(&goal, &id).await {
+                    Ok(dp) => {
+                        if output.is_table() {
+                            println!("Deleted {id}");
+                        }
+                        deleted.push(dp);
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to delete {id}: {}", err.format_for_display());
+                        failed += 1;
+                    }
+                }
+            }
+            output::print_records(output, &deleted)?;
+            if failed > 0 {
+                return Err(anyhow!("{failed} datapoint(s) could not be deleted"));
+            }
+        }
+        DpCommand::Transform {
+            goal,
+            since,
+            until,
+            matching,
+            scale,
+            offset,
+            shift,
+            replace,
+            with,
+            delete,
+            batch_size,
+            dry_run,
+            yes,
+        } => {
+            let transform = Transform {
+                scale,
+                offset,
+                shift: shift
+                    .as_deref()
+                    .map(transform::parse_duration)
+                    .transpose()?,
+                replace: replace
+                    .as_deref()
+                    .map(|re| Ok::<_, regex::Error>((Regex::new(re)?, with.unwrap_or_default())))
+                    .transpose()?,
+                matching: matching.as_deref().map(Regex::new).transpose()?,
+                delete,
+            };
+            if transform.is_empty() {
+                return Err(anyhow!(
+                    "Provide at least one of: --scale, --offset, --shift, --replace, --delete"
+                ));
+            }
+            let datapoints = list(client, &goal, since.as_deref(), until.as_deref(), None).await?;
+            let changeset = transform.plan(&datapoints);
+            transform::run(client, &goal, &changeset, batch_size, dry_run, yes).await?;
+        }
+        DpCommand::Dedupe {
+            goal,
+            window,
+            batch_size,
+            dry_run,
+            yes,
+        } => {
+            let window = transform::parse_duration(&window)?;
+            let datapoints = client.get_datapoints(&goal, None, None, None, None).await?;
+            let groups = duplicates::find_duplicates(&datapoints, window);
+            if groups.is_empty() {
+                println!("No duplicates in {goal}.");
+                return Ok(());
+            }
+            let changeset = Changeset {
+                changes: groups
+                    .iter()
+                    .flat_map(|group| group.extras.iter())
+                    .map(|dp| Change::Delete((*dp).clone()))
+                    .collect(),
+                unknown_ids: Vec::new(),
+            };
+            let count = changeset.changes.len();
+            for group in &groups {
+                let reason = match group.reason {
+                    DuplicateReason::RequestId => "same requestid",
+                    DuplicateReason::SameEntry => "same entry",
+                };
+                println!("{} ({reason}):", group.keep.daystamp);
+                println!("  keep    {}", transform::describe(group.keep));
+                for dp in &group.extras {
+                    println!("  delete  {}", transform::describe(dp));
+                }
+            }
+            println!("{count} duplicates in {} groups.", groups.len());
+            if dry_run {
+                return Ok(());
+            }
+            if !yes && !confirm(&format!("Delete {count} duplicates from {goal}?"))? {
+                println!("Aborted.");
+                return Ok(());
+            }
+            transform::apply(client, &goal, &changeset, batch_size).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn list(
+    client: &BeeminderClient,
+    goal: &str,
+    since: Option<&str>,
+    until: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Vec<Datapoint>> {
+    if since.is_none() && until.is_none() {
+        let count = limit.map(u64::try_from).transpose()?;
+        return Ok(client.get_datapoints(goal, None, count, None, None).await?);
+    }
+
+    let (goal_full, user, mut datapoints) = tokio::try_join!(
+        client.get_goal_full(goal, false),
+        client.get_user(),
+        client.get_datapoints(goal, None, None, None, None),
+    )?;
+    let today = goal_today(&goal_full, &user.timezone);
+    let since = since.map(|value| entry_day(value, &today)).transpose()?;
+    let until = until.map(|value| entry_day(value, &today)).transpose()?;
+    datapoints.retain(|dp| {
+        let Some(day) = beeminder::day::parse_daystamp(&dp.daystamp) else {
+            return false;
+        };
+        since.is_none_or(|since| day >= since) && until.is_none_or(|until| day <= until)
+    });
+    if let Some(limit) = limit {
+        datapoints.truncate(limit);
+    }
+    Ok(datapoints)
+}
+
+fn goal_today(goal: &GoalFull, timezone: &str) -> GoalDay {
+    DayClock::for_user(timezone).goal_day(goal.deadline.unwrap_or(0), OffsetDateTime::now_utc())
+}
+
+/// Reads a `--since`/`--until` bound as a Beeminder day.
+pub fn entry_day(value: &str, today: &GoalDay) -> Result<Date> {
+    Ok(entry::parse_date(value, today)?.day(&today.clock()))
+}
+
+fn print_datapoint(dp: &Datapoint) {
+    let comment = dp.comment.as_deref().unwrap_or("");
+    if comment.is_empty() {
+        println!("{}  {:>10}  {}", dp.daystamp, dp.value, dp.id);
+    } else {
+        println!(
+            "{}  {:>10}  {}  \"{comment}\"",
+            dp.daystamp, dp.value, dp.id
+        );
+    }
+}
@@ -1,26 +1,17 @@
-use crate::EditableDatapoint;
-use anyhow::{Context, Result};
+use crate::dp;
+use crate::import::confirm;
+use anyhow::{anyhow, Context, Result};
 use beeconfig::TIMESTAMP_FORMAT;
-use beeminder::types::{CreateDatapoint, Datapoint, UpdateDatapoint};
-use beeminder::BeeminderClient;
-use std::collections::{HashMap, HashSet};
-use std::fs::File;
-use std::io::{BufRead, Write};
+use beeminder::changeset::{Change, Changeset, Conflict, EditedDatapoint, Resolution};
+use beeminder::entry::{self, EntryGoal};
+use beeminder::types::Datapoint;
+use beeminder::{BeeminderClient, Error as BeeminderError};
+use std::fs;
+use std::io::{self, Write};
 use std::process::Command as ProcessCommand;
 use tempfile::NamedTempFile;
 use time::{PrimitiveDateTime, UtcOffset};
 
-impl From<&Datapoint> for EditableDatapoint {
-    fn from(dp: &Datapoint) -> Self {
-        Self {
-            id: Some(dp.id.clone()),
-            timestamp: Some(dp.timestamp),
-            value: Some(dp.value),
-            comment: dp.comment.clone(),
-        }
-    }
-}
-
 pub fn write_datapoints_tsv(writer: &mut impl Write, datapoints: &Vec<Datapoint>) -> Result<()> {
     writeln!(writer, "TIMESTAMP\tVALUE\tCOMMENT\tID")?;
     let offset = UtcOffset::current_local_offset()?;
@@ -38,103 +29,341 @@ pub fn write_datapoints_tsv(writer: &mut impl Write, datapoints: &Vec<Datapoint>
     Ok(())
 }
 
-pub fn read_datapoints_tsv(reader: impl BufRead) -> Result<Vec<EditableDatapoint>> {
-    let mut lines = reader.lines();
+/// Marks the annotations added above malformed lines when the file is
+/// reopened; they are dropped again on the next round.
+const ERROR_PREFIX: &str = "# error: ";
 
-    // Skip header
-    lines.next();
+/// Datapoints shown when no range is given.
+const DEFAULT_COUNT: usize = 20;
 
+/// A malformed line of the edited file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LineError {
+    /// 1-based line number
+    pub line: usize,
+    pub message: String,
+}
+
+/// Parses the edited file. Blank lines, `#` comments and the header are
+/// skipped; every malformed line is reported, not just the first. Values
+/// are read with the entry grammar of `goal`.
+pub fn parse_datapoints_tsv(
+    text: &str,
+    offset: UtcOffset,
+    goal: &EntryGoal,
+) -> std::result::Result<Vec<EditedDatapoint>, Vec<LineError>> {
     let mut datapoints = Vec::new();
-    let offset = UtcOffset::current_local_offset()?;
+    let mut errors = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') || line.starts_with("TIMESTAMP\t") {
+            continue;
+        }
+        match parse_line(line, offset, goal) {
+            Ok(dp) => datapoints.push(dp),
+            Err(message) => errors.push(LineError {
+                line: idx + 1,
+                message,
+            }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(datapoints)
+    } else {
+        Err(errors)
+    }
+}
 
-    for line in lines {
-        let line = line?;
-        let mut fields = line.split('\t');
-
-        let date_str = fields
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing date"))?;
-        let value_str = fields
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing value"))?;
-        let comment = fields.next().unwrap_or("").to_string();
-        let id = fields.next().map(String::from).filter(|s| !s.is_empty());
-
-        let date = PrimitiveDateTime::parse(date_str, TIMESTAMP_FORMAT)?;
-        let timestamp = date.assume_offset(offset).to_offset(UtcOffset::UTC);
-        let value = value_str.parse()?;
-
-        datapoints.push(EditableDatapoint {
-            id,
-            timestamp: Some(timestamp),
-            value: Some(value),
-            comment: Some(comment),
-        });
+fn parse_line(
+    line: &str,
+    offset: UtcOffset,
+    goal: &EntryGoal,
+) -> std::result::Result<EditedDatapoint, String> {
+    let mut fields = line.split('\t');
+    let date_str = fields.next().unwrap_or("").trim();
+    let value_str = fields
+        .next()
+        .ok_or_else(|| {
+            "missing value; expected TIMESTAMP<tab>VALUE<tab>COMMENT<tab>ID".to_string()
+        })?
+        .trim();
+    let comment = fields.next().unwrap_or("").to_string();
+    let id = fields
+        .next()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from);
+
+    let date = PrimitiveDateTime::parse(date_str, TIMESTAMP_FORMAT)
+        .map_err(|_| format!("invalid timestamp '{date_str}'; expected YYYY-MM-DD HH:MM:SS"))?;
+    let value = entry::parse_value(value_str, goal).map_err(|err| err.to_string())?;
+
+    Ok(EditedDatapoint {
+        id,
+        timestamp: date.assume_offset(offset).to_offset(UtcOffset::UTC),
+        value,
+        comment,
+    })
+}
+
+/// Puts each error as a comment above its line, replacing the annotations
+/// of the previous round.
+pub fn annotate(text: &str, errors: &[LineError]) -> String {
+    let mut out = String::new();
+    for (idx, line) in text.lines().enumerate() {
+        if line.starts_with(ERROR_PREFIX) {
+            continue;
+        }
+        for err in errors.iter().filter(|err| err.line == idx + 1) {
+            out.push_str(ERROR_PREFIX);
+            out.push_str(&err.message);
+            out.push('\n');
+        }
+        out.push_str(line);
+        out.push('\n');
     }
+    out
+}
 
-    Ok(datapoints)
+/// Which datapoints to edit: the latest `count`, or those between `since`
+/// and `until`.
+#[derive(Debug, Default)]
+pub struct EditRange {
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub count: Option<usize>,
 }
 
-pub async fn edit_datapoints(client: &BeeminderClient, goal: &str) -> Result<()> {
-    let datapoints = client
-        .get_datapoints(goal, Some("timestamp"), Some(20), None, None)
-        .await?;
+pub async fn edit_datapoints(
+    client: &BeeminderClient,
+    goal: &str,
+    range: &EditRange,
+    yes: bool,
+) -> Result<()> {
+    let entry_goal = EntryGoal::from(&client.get_goal_full(goal, false).await?);
+    let datapoints = if range.since.is_none() && range.until.is_none() {
+        let count = u64::try_from(range.count.unwrap_or(DEFAULT_COUNT))?;
+        client
+            .get_datapoints(goal, Some("timestamp"), Some(count), None, None)
+            .await?
+    } else {
+        dp::list(
+            client,
+            goal,
+            range.since.as_deref(),
+            range.until.as_deref(),
+            range.count,
+        )
+        .await?
+    };
 
     // Create temp file with datapoints and let user edit it
     let mut temp = NamedTempFile::new()?;
     write_datapoints_tsv(&mut temp, &datapoints)?;
+    temp.flush()?;
+    let offset = UtcOffset::current_local_offset()?;
     let editor = std::env::var("EDITOR").unwrap_or_else(|_| "nvim".to_string());
-    ProcessCommand::new(editor)
-        .arg(temp.path())
-        .status()
-        .context("Failed to open editor")?;
-
-    let reader = std::io::BufReader::new(File::open(temp.path())?);
-    let edited_datapoints = read_datapoints_tsv(reader)?;
-    let orig_map: HashMap<String, &Datapoint> =
-        datapoints.iter().map(|dp| (dp.id.clone(), dp)).collect();
-    let mut ids_to_delete: HashSet<String> = datapoints.iter().map(|dp| dp.id.clone()).collect();
-
-    for dp in edited_datapoints {
-        if let EditableDatapoint { id: Some(id), .. } = dp {
-            if let Some(orig) = orig_map.get(&id) {
-                ids_to_delete.remove(&id);
-                let needs_update = dp.value != Some(orig.value)
-                    || dp.timestamp != Some(orig.timestamp)
-                    || dp.comment != orig.comment;
-                if needs_update {
-                    let update = UpdateDatapoint {
-                        id: id.clone(),
-                        timestamp: dp.timestamp,
-                        value: dp.value,
-                        comment: dp.comment,
-                    };
-                    println!("Updating datapoint '{id}'.");
-                    client.update_datapoint(goal, &update).await?;
+    let edited_datapoints = loop {
+        ProcessCommand::new(&editor)
+            .arg(temp.path())
+            .status()
+            .context("Failed to open editor")?;
+        let text = fs::read_to_string(temp.path())?;
+        match parse_datapoints_tsv(&text, offset, &entry_goal) {
+            Ok(edited) => break edited,
+            Err(errors) => {
+                for err in &errors {
+                    eprintln!("line {}: {}", err.line, err.message);
+                }
+                if !confirm("Edit again?")? {
+                    println!("Aborted; nothing was changed.");
+                    return Ok(());
                 }
-            } else {
-                eprintln!("No datapoint with ID '{id}'.");
+                fs::write(temp.path(), annotate(&text, &errors))?;
+            }
+        }
+    };
+
+    let changeset = Changeset::diff(&datapoints, &edited_datapoints);
+    for id in &changeset.unknown_ids {
+        eprintln!("No datapoint with ID '{id}'.");
+    }
+    if changeset.is_empty() {
+        println!("No changes.");
+        return Ok(());
+    }
+
+    for line in changeset.preview(offset) {
+        println!("{line}");
+    }
+    if !yes && !confirm(&format!("Apply {} to {goal}?", changeset.summary()))? {
+        println!("Aborted.");
+        return Ok(());
+    }
+    let total = changeset.changes.len();
+    let mut pending = changeset;
+    let mut failed = 0;
+    loop {
+        let result = pending.apply_checked(client, goal).await?;
+        for (change, err) in &result.failed {
+            if !matches!(err, BeeminderError::Conflict(_)) {
+                eprintln!(
+                    "Failed: {}: {}",
+                    change_label(change),
+                    err.format_for_display()
+                );
+                failed += 1;
             }
-        } else {
-            let create = CreateDatapoint {
-                timestamp: dp.timestamp,
-                value: dp.value.unwrap_or_default(),
-                comment: dp.comment,
-                daystamp: None,
-                requestid: None,
-            };
-            println!(
-                "Creating new datapoint with value '{}'.",
-                dp.value.unwrap_or_default()
-            );
-            client.create_datapoint(goal, &create).await?;
         }
+        let mut resolved = Vec::new();
+        for (change, conflict) in result.conflicts() {
+            let resolution = prompt_resolution(change, conflict, offset)?;
+            resolved.extend(change.resolve(conflict.current.as_ref(), resolution));
+        }
+        if resolved.is_empty() {
+            break;
+        }
+        pending = Changeset {
+            changes: resolved,
+            unknown_ids: Vec::new(),
+        };
     }
 
-    for id in ids_to_delete {
-        println!("Deleting datapoint '{id}'.");
-        client.delete_datapoint(goal, &id).await?;
+    if failed == 0 {
+        println!("Saved.");
+        Ok(())
+    } else {
+        Err(anyhow!("{failed} of {total} changes failed"))
     }
+}
 
-    Ok(())
+/// Shows the loaded, server and edited versions of a datapoint changed
+/// elsewhere and asks how to settle it.
+fn prompt_resolution(
+    change: &Change,
+    conflict: &Conflict,
+    offset: UtcOffset,
+) -> Result<Resolution> {
+    println!(
+        "Datapoint '{}' was changed elsewhere since it was loaded:",
+        conflict.before.id
+    );
+    println!(
+        "  loaded  {}",
+        describe(&EditedDatapoint::from(&conflict.before), offset)
+    );
+    let server = conflict.current.as_ref().map(EditedDatapoint::from);
+    println!("  server  {}", describe_or_deleted(server.as_ref(), offset));
+    println!(
+        "  yours   {}",
+        describe_or_deleted(change.proposed().as_ref(), offset)
+    );
+    loop {
+        print!("[m]erge your changes into the server version, [o]verwrite with yours, keep [t]heirs? [t] ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "m" | "merge" => return Ok(Resolution::Merge),
+            "o" | "overwrite" | "mine" => return Ok(Resolution::Mine),
+            "" | "t" | "theirs" => return Ok(Resolution::Theirs),
+            _ => {}
+        }
+    }
+}
+
+fn describe_or_deleted(dp: Option<&EditedDatapoint>, offset: UtcOffset) -> String {
+    dp.map_or_else(|| "(deleted)".to_string(), |dp| describe(dp, offset))
+}
+
+fn describe(dp: &EditedDatapoint, offset: UtcOffset) -> String {
+    let time = dp
+        .timestamp
+        .to_offset(offset)
+        .format(TIMESTAMP_FORMAT)
+        .unwrap_or_default();
+    format!("{time}  {}  {}", dp.value, dp.comment)
+}
+
+fn change_label(change: &Change) -> String {
+    match change {
+        Change::Create(dp) => format!("new datapoint with value '{}'", dp.value),
+        Change::Update { before, .. } => format!("update of '{}'", before.id),
+        Change::Delete(dp) => format!("deletion of '{}'", dp.id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use beeminder::entry::TimeUnit;
+
+    const EDITED: &str = "TIMESTAMP\tVALUE\tCOMMENT\tID\n\
+        2026-07-01 02:00:00\t5\tran\tdp1\n\
+        # a note\n\
+        2026-07-01 03:00:00\tfive\tswam\tdp2\n\
+        \n\
+        yesterday\t1\t\t\n";
+
+    #[test]
+    fn reports_every_malformed_line() {
+        let errors =
+            parse_datapoints_tsv(EDITED, UtcOffset::UTC, &EntryGoal::default()).unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                LineError {
+                    line: 4,
+                    message: "Invalid value 'five'".to_string(),
+                },
+                LineError {
+                    line: 6,
+                    message: "invalid timestamp 'yesterday'; expected YYYY-MM-DD HH:MM:SS"
+                        .to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn annotations_are_replaced_and_ignored() {
+        let errors =
+            parse_datapoints_tsv(EDITED, UtcOffset::UTC, &EntryGoal::default()).unwrap_err();
+        let annotated = annotate(EDITED, &errors);
+        assert!(annotated.contains("# error: Invalid value 'five'\n2026-07-01 03:00:00\tfive"));
+
+        let fixed = annotated
+            .replace("\tfive\t", "\t5\t")
+            .replace("yesterday", "2026-06-30 20:00:00");
+        let errors = parse_datapoints_tsv(&fixed, UtcOffset::UTC, &EntryGoal::default());
+        let datapoints = errors.unwrap();
+        assert_eq!(datapoints.len(), 3);
+        assert_eq!(datapoints[0].id.as_deref(), Some("dp1"));
+        assert_eq!(datapoints[2].id, None);
+
+        let reannotated = annotate(&fixed, &[]);
+        assert!(!reannotated.contains(ERROR_PREFIX));
+    }
+
+    #[test]
+    fn reads_values_with_the_goal_units() {
+        let goal = EntryGoal {
+            integery: false,
+            time_unit: Some(TimeUnit::Minutes),
+        };
+        let text = "2026-07-01 02:00:00\t1:30\tran\tdp1\n2026-07-01 03:00:00\t10+5\t\t\n";
+        let datapoints = parse_datapoints_tsv(text, UtcOffset::UTC, &goal).unwrap();
+        assert_eq!(datapoints[0].value, 90.0);
+        assert_eq!(datapoints[1].value, 15.0);
+
+        let integery = EntryGoal {
+            integery: true,
+            time_unit: None,
+        };
+        let errors =
+            parse_datapoints_tsv("2026-07-01 02:00:00\t1.5\t\t\n", UtcOffset::UTC, &integery)
+                .unwrap_err();
+        assert_eq!(errors[0].line, 1);
+    }
 }
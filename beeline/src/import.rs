@@ -0,0 +1,671 @@
+use anyhow::{Context, Result};
+use beeminder::day::{daystamp, parse_daystamp, DayClock};
+use beeminder::types::{CreateAllResponse, CreateDatapoint, CreateGoal, GoalType};
+use beeminder::BeeminderClient;
+use clap::ValueEnum;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+use time::format_description::{self, BorrowedFormatItem};
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+mod habitica;
+mod loop_habit;
+mod strava;
+mod toggl;
+
+/// Number of preview rows shown before asking for confirmation.
+const PREVIEW_ROWS: usize = 10;
+
+/// Options controlling how a CSV/TSV export is mapped onto datapoints.
+pub struct CsvOptions {
+    /// Column holding the date (header name or 1-based index)
+    pub date_col: String,
+    /// Column holding the value (header name or 1-based index)
+    pub value_col: String,
+    /// Optional column holding the comment (header name or 1-based index)
+    pub comment_col: Option<String>,
+    /// `time` format description for the date column, or "unix"
+    pub date_format: String,
+    /// Timezone assumed for timestamps without an offset
+    pub clock: DayClock,
+    /// Factor applied to every value
+    pub scale: f64,
+    /// Field delimiter
+    pub delimiter: u8,
+}
+
+/// Options controlling how parsed datapoints are uploaded.
+pub struct UploadOptions {
+    /// Number of datapoints per `create_all_datapoints` request
+    pub chunk_size: usize,
+    /// Only print the preview
+    pub dry_run: bool,
+    /// Skip the confirmation prompt
+    pub yes: bool,
+}
+
+/// Export formats understood by `beeline import --from`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportSource {
+    /// Generic CSV/TSV with column mapping
+    Csv,
+    /// Loop Habit Tracker `Checkmarks.csv`
+    Loop,
+    /// Habitica task history CSV
+    Habitica,
+    /// Toggl Track detailed report CSV
+    Toggl,
+    /// Strava GPX file or directory of GPX files
+    #[value(name = "strava-gpx")]
+    StravaGpx,
+}
+
+impl ImportSource {
+    const fn requestid_prefix(self) -> &'static str {
+        match self {
+            Self::Csv => "import",
+            Self::Loop => "loop",
+            Self::Habitica => "habitica",
+            Self::Toggl => "toggl",
+            Self::StravaGpx => "strava",
+        }
+    }
+}
+
+/// A named stream of datapoints from another app (a habit, task or project).
+pub struct ImportedSeries {
+    /// Name in the source app
+    pub name: String,
+    /// Units used for a newly created goal
+    pub gunits: String,
+    /// Datapoints with deterministic request IDs
+    pub datapoints: Vec<CreateDatapoint>,
+    /// Raw source date of each datapoint, used as `requestid` input
+    dates: Vec<String>,
+}
+
+impl ImportedSeries {
+    fn new(name: &str, gunits: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            gunits: gunits.to_string(),
+            datapoints: Vec::new(),
+            dates: Vec::new(),
+        }
+    }
+
+    /// Adds a datapoint read from a row dated `raw_date` in the export.
+    fn push(&mut self, raw_date: &str, dp: CreateDatapoint) {
+        self.dates.push(raw_date.to_string());
+        self.datapoints.push(dp);
+    }
+
+    /// Builds a do-more goal whose weekly rate matches the imported history.
+    ///
+    /// The goal date is set a year out so the rate/goaldate pair satisfies the
+    /// API; adjust it afterwards with `beeline goal-update`.
+    pub fn goal_template(&self, slug: &str) -> CreateGoal {
+        let now = OffsetDateTime::now_utc();
+        let first = self
+            .datapoints
+            .iter()
+            .filter_map(|dp| {
+                dp.timestamp
+                    .map(OffsetDateTime::date)
+                    .or_else(|| dp.daystamp.as_deref().and_then(parse_daystamp))
+            })
+            .min()
+            .unwrap_or_else(|| now.date());
+        let days = (now.date() - first).whole_days().max(7);
+        let total: f64 = self.datapoints.iter().map(|dp| dp.value).sum();
+        #[allow(clippy::cast_precision_loss)]
+        let weekly = total * 7.0 / days as f64;
+
+        let mut goal = CreateGoal::new(slug, self.name.clone(), GoalType::Hustler);
+        goal.rate = Some(((weekly * 100.0).round() / 100.0).max(0.01));
+        goal.runits = Some("w".to_string());
+        goal.goaldate = Some(now + Duration::days(365));
+        goal.gunits = Some(self.gunits.clone());
+        goal
+    }
+}
+
+/// Turns a name from another app into a goal slug.
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') && !slug.is_empty() {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Parses an app export into named series, scales every value by `scale`
+/// and assigns request IDs.
+///
+/// `path` is read directly for GPX imports so that directories work; with
+/// `-` the GPX in `content` (read from stdin) is parsed instead.
+pub fn parse_export(
+    source: ImportSource,
+    path: &str,
+    content: &str,
+    clock: DayClock,
+    scale: f64,
+) -> Result<Vec<ImportedSeries>> {
+    let mut series = match source {
+        ImportSource::Csv => {
+            return Err(anyhow::anyhow!(
+                "CSV imports need a goal and column mapping"
+            ))
+        }
+        ImportSource::Loop => loop_habit::parse(content)?,
+        ImportSource::Habitica => habitica::parse(content, clock)?,
+        ImportSource::Toggl => toggl::parse(content, clock)?,
+        ImportSource::StravaGpx if path == "-" => strava::parse_content(content)?,
+        ImportSource::StravaGpx => strava::parse(path)?,
+    };
+    for entry in &mut series {
+        for dp in &mut entry.datapoints {
+            dp.value *= scale;
+        }
+        assign_requestids(
+            source.requestid_prefix(),
+            entry
+                .dates
+                .iter()
+                .map(String::as_str)
+                .zip(&mut entry.datapoints),
+        );
+    }
+    Ok(series)
+}
+
+/// Parses `NAME=SLUG` mappings given with `--map`.
+pub fn parse_mappings(values: &[String]) -> Result<HashMap<String, String>> {
+    values
+        .iter()
+        .map(|value| {
+            value
+                .rsplit_once('=')
+                .map(|(name, slug)| (name.trim().to_string(), slug.trim().to_string()))
+                .filter(|(name, slug)| !name.is_empty() && !slug.is_empty())
+                .ok_or_else(|| anyhow::anyhow!("Invalid mapping '{value}'; expected NAME=SLUG"))
+        })
+        .collect()
+}
+
+/// Imports each series into its mapped goal, creating goals when asked.
+///
+/// Series without a mapping are skipped unless `create` is set, in which case
+/// a goal slugged after the series name is used (and created if missing).
+pub async fn import_series(
+    client: &BeeminderClient,
+    series: Vec<ImportedSeries>,
+    mappings: &HashMap<String, String>,
+    create: bool,
+    options: &UploadOptions,
+) -> Result<()> {
+    let existing: HashSet<String> = client
+        .get_goals()
+        .await?
+        .into_iter()
+        .chain(client.get_archived_goals().await?)
+        .map(|goal| goal.slug)
+        .collect();
+
+    for entry in series {
+        let slug = match mappings.get(&entry.name) {
+            Some(slug) => slug.clone(),
+            None if create => slugify(&entry.name),
+            None => {
+                println!(
+                    "Skipping '{}' ({} datapoints): no --map entry; use --create to create a goal",
+                    entry.name,
+                    entry.datapoints.len()
+                );
+                continue;
+            }
+        };
+
+        if existing.contains(&slug) {
+            import_datapoints(client, &slug, entry.datapoints, options).await?;
+            continue;
+        }
+        if !create {
+            return Err(anyhow::anyhow!(
+                "Goal '{slug}' (mapped from '{}') does not exist; pass --create to create it",
+                entry.name
+            ));
+        }
+
+        let goal = entry.goal_template(&slug);
+        println!(
+            "New goal {slug}: \"{}\", {} {} per week until {}",
+            goal.title,
+            goal.rate.unwrap_or_default(),
+            entry.gunits,
+            goal.goaldate
+                .map(|d| d.date().to_string())
+                .unwrap_or_default()
+        );
+        print_preview(&slug, &entry.datapoints, 0);
+        if options.dry_run {
+            continue;
+        }
+        if !options.yes && !confirm(&format!("Create goal '{slug}' and import?"))? {
+            println!("Skipped '{}'.", entry.name);
+            continue;
+        }
+        client.create_goal(&goal).await?;
+        let (created, errors) =
+            upload(client, &slug, &entry.datapoints, options.chunk_size).await?;
+        println!("Created goal {slug} with {created} datapoints.");
+        if !errors.is_empty() {
+            eprintln!(
+                "{} errors:\n{}",
+                errors.len(),
+                serde_json::to_string_pretty(&errors)
+                    .unwrap_or_else(|_| "Failed to format errors".to_string())
+            );
+        }
+    }
+    Ok(())
+}
+
+enum DateFormat<'a> {
+    Unix,
+    Items(Vec<BorrowedFormatItem<'a>>),
+}
+
+/// Returns the delimiter implied by the file extension (tab for `.tsv`).
+pub fn delimiter_for_path(path: &str) -> u8 {
+    let lower = path.to_ascii_lowercase();
+    if lower.ends_with(".tsv") || lower.ends_with(".tab") {
+        b'\t'
+    } else {
+        b','
+    }
+}
+
+/// Parses "local", "utc", an IANA name such as "Europe/Berlin" or a fixed
+/// "+HH:MM" offset. Named zones give each row the offset in effect on its
+/// date, so rows across a DST change land on the right time.
+pub fn parse_timezone(value: &str) -> Result<DayClock> {
+    let value = value.trim();
+    if let Some(clock) = DayClock::new(value) {
+        return Ok(clock);
+    }
+    match value.to_ascii_lowercase().as_str() {
+        "local" => Ok(DayClock::local()),
+        "utc" | "z" => Ok(DayClock::Fixed(UtcOffset::UTC)),
+        other => {
+            let invalid = || {
+                anyhow::anyhow!(
+                    "Invalid timezone '{value}'; expected local, utc, +HH:MM or a name like Europe/Berlin"
+                )
+            };
+            let (sign, rest) = match other.as_bytes().first() {
+                Some(b'+') => (1, &other[1..]),
+                Some(b'-') => (-1, &other[1..]),
+                _ => return Err(invalid()),
+            };
+            let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+            let hours = hours.parse::<i8>().map_err(|_| invalid())?;
+            let minutes = minutes.parse::<i8>().map_err(|_| invalid())?;
+            UtcOffset::from_hms(sign * hours, sign * minutes, 0)
+                .map(DayClock::Fixed)
+                .map_err(|_| invalid())
+        }
+    }
+}
+
+/// Reads CSV/TSV rows and maps them onto datapoints.
+///
+/// Rows whose value cell is empty are skipped. Each datapoint receives a
+/// deterministic `requestid` so re-running an import does not duplicate data.
+pub fn parse_csv(reader: impl Read, options: &CsvOptions) -> Result<Vec<CreateDatapoint>> {
+    let mut csv = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let headers = csv.headers().context("Failed to read CSV header")?.clone();
+    let date_idx = resolve_column(&headers, &options.date_col)?;
+    let value_idx = resolve_column(&headers, &options.value_col)?;
+    let comment_idx = options
+        .comment_col
+        .as_deref()
+        .map(|col| resolve_column(&headers, col))
+        .transpose()?;
+
+    let format = if options.date_format.eq_ignore_ascii_case("unix") {
+        DateFormat::Unix
+    } else {
+        DateFormat::Items(
+            format_description::parse(&options.date_format)
+                .with_context(|| format!("Invalid date format '{}'", options.date_format))?,
+        )
+    };
+
+    let mut datapoints = Vec::new();
+    let mut dates = Vec::new();
+    for (idx, record) in csv.records().enumerate() {
+        // Header is line 1
+        let line = idx + 2;
+        let record = record.with_context(|| format!("Failed to read line {line}"))?;
+        let raw_value = record.get(value_idx).unwrap_or("");
+        if raw_value.is_empty() {
+            continue;
+        }
+        let value = raw_value
+            .parse::<f64>()
+            .with_context(|| format!("Line {line}: invalid value '{raw_value}'"))?
+            * options.scale;
+        let raw_date = record.get(date_idx).unwrap_or("");
+        let mut dp = CreateDatapoint::new(value);
+        dp = match parse_date(raw_date, &format, options.clock)
+            .with_context(|| format!("Line {line}: invalid date '{raw_date}'"))?
+        {
+            ParsedDate::Day(date) => dp.with_daystamp(&daystamp(date)),
+            ParsedDate::Time(ts) => dp.with_timestamp(ts),
+        };
+        if let Some(comment) = comment_idx.and_then(|idx| record.get(idx)) {
+            if !comment.is_empty() {
+                dp = dp.with_comment(comment);
+            }
+        }
+
+        dates.push(raw_date.to_string());
+        datapoints.push(dp);
+    }
+
+    assign_requestids(
+        ImportSource::Csv.requestid_prefix(),
+        dates.iter().map(String::as_str).zip(&mut datapoints),
+    );
+    Ok(datapoints)
+}
+
+/// Gives every datapoint a deterministic `requestid` derived from the raw
+/// date of its source row, its (scaled) value and its comment. Identical
+/// rows get distinct ids by occurrence.
+fn assign_requestids<'a>(
+    prefix: &str,
+    rows: impl IntoIterator<Item = (&'a str, &'a mut CreateDatapoint)>,
+) {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for (raw_date, dp) in rows {
+        let key = format!(
+            "{raw_date}|{}|{}",
+            dp.value,
+            dp.comment.as_deref().unwrap_or("")
+        );
+        let occurrence = seen.entry(key.clone()).or_insert(0);
+        *occurrence += 1;
+        dp.requestid = Some(format!(
+            "beeline-{prefix}-{:016x}-{occurrence}",
+            fnv1a(&key)
+        ));
+    }
+}
+
+enum ParsedDate {
+    Day(Date),
+    Time(OffsetDateTime),
+}
+
+fn parse_date(value: &str, format: &DateFormat<'_>, clock: DayClock) -> Result<ParsedDate> {
+    match format {
+        DateFormat::Unix => {
+            let seconds = value.parse::<i64>()?;
+            Ok(ParsedDate::Time(OffsetDateTime::from_unix_timestamp(
+                seconds,
+            )?))
+        }
+        DateFormat::Items(items) => {
+            if let Ok(ts) = OffsetDateTime::parse(value, items) {
+                return Ok(ParsedDate::Time(ts));
+            }
+            if let Ok(naive) = PrimitiveDateTime::parse(value, items) {
+                return Ok(ParsedDate::Time(
+                    clock.local_time(naive.date(), naive.time()),
+                ));
+            }
+            Ok(ParsedDate::Day(Date::parse(value, items)?))
+        }
+    }
+}
+
+fn resolve_column(headers: &csv::StringRecord, column: &str) -> Result<usize> {
+    if let Some(idx) = headers
+        .iter()
+        .position(|header| header.eq_ignore_ascii_case(column))
+    {
+        return Ok(idx);
+    }
+    match column.parse::<usize>() {
+        Ok(idx) if idx >= 1 => Ok(idx - 1),
+        _ => Err(anyhow::anyhow!(
+            "Column '{column}' not found; available: {}",
+            headers.iter().collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// 64-bit FNV-1a; stable across platforms and Rust releases.
+pub fn fnv1a(input: &str) -> u64 {
+    input.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Drops datapoints whose `requestid` already exists on the goal.
+///
+/// Returns the remaining datapoints and the number that were skipped.
+pub async fn skip_existing(
+    client: &BeeminderClient,
+    goal: &str,
+    datapoints: Vec<CreateDatapoint>,
+) -> Result<(Vec<CreateDatapoint>, usize)> {
+    let existing = client
+        .get_datapoints(goal, None, None, None, None)
+        .await
+        .with_context(|| format!("Failed to fetch existing datapoints for {goal}"))?;
+    let known: HashSet<String> = existing.into_iter().filter_map(|dp| dp.requestid).collect();
+    let total = datapoints.len();
+    let remaining: Vec<CreateDatapoint> = datapoints
+        .into_iter()
+        .filter(|dp| dp.requestid.as_ref().is_none_or(|id| !known.contains(id)))
+        .collect();
+    let skipped = total - remaining.len();
+    Ok((remaining, skipped))
+}
+
+/// Prints a short preview of datapoints about to be created.
+pub fn print_preview(goal: &str, datapoints: &[CreateDatapoint], skipped: usize) {
+    println!(
+        "{goal}: {} new datapoints ({skipped} already imported)",
+        datapoints.len()
+    );
+    for dp in datapoints.iter().take(PREVIEW_ROWS) {
+        let date = dp.daystamp.clone().unwrap_or_else(|| {
+            dp.timestamp
+                .and_then(|ts| ts.format(beeconfig::TIMESTAMP_FORMAT).ok())
+                .unwrap_or_else(|| "now".to_string())
+        });
+        let comment = dp.comment.as_deref().unwrap_or("");
+        println!("  {date:19}  {:>10}  {comment}", dp.value);
+    }
+    if datapoints.len() > PREVIEW_ROWS {
+        println!("  ... and {} more", datapoints.len() - PREVIEW_ROWS);
+    }
+}
+
+/// Asks a yes/no question on stdin; defaults to no.
+pub fn confirm(prompt: &str) -> Result<bool> {
+    print!("{prompt} [y/N] ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+/// Uploads datapoints in chunks through `create_all_datapoints`.
+///
+/// Returns the number of created datapoints and collected errors.
+pub async fn upload(
+    client: &BeeminderClient,
+    goal: &str,
+    datapoints: &[CreateDatapoint],
+    chunk_size: usize,
+) -> Result<(usize, Vec<serde_json::Value>)> {
+    let mut created = 0;
+    let mut errors = Vec::new();
+    let chunks = datapoints.chunks(chunk_size.max(1));
+    let total = chunks.len();
+    for (idx, chunk) in chunks.enumerate() {
+        println!(
+            "Uploading chunk {}/{total} ({} datapoints)",
+            idx + 1,
+            chunk.len()
+        );
+        match client.create_all_datapoints(goal, chunk).await? {
+            CreateAllResponse::Success(successes) => created += successes.len(),
+            CreateAllResponse::Partial {
+                successes,
+                errors: chunk_errors,
+            } => {
+                created += successes.len();
+                errors.extend(chunk_errors);
+            }
+        }
+    }
+    Ok((created, errors))
+}
+
+/// Dedupes, previews, confirms and uploads datapoints for one goal.
+pub async fn import_datapoints(
+    client: &BeeminderClient,
+    goal: &str,
+    datapoints: Vec<CreateDatapoint>,
+    options: &UploadOptions,
+) -> Result<()> {
+    let (datapoints, skipped) = skip_existing(client, goal, datapoints).await?;
+    print_preview(goal, &datapoints, skipped);
+    if datapoints.is_empty() || options.dry_run {
+        return Ok(());
+    }
+    if !options.yes && !confirm(&format!("Import {} datapoints?", datapoints.len()))? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    let (created, errors) = upload(client, goal, &datapoints, options.chunk_size).await?;
+    println!("Created {created} datapoints.");
+    if !errors.is_empty() {
+        eprintln!(
+            "{} errors:\n{}",
+            errors.len(),
+            serde_json::to_string_pretty(&errors)
+                .unwrap_or_else(|_| "Failed to format errors".to_string())
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> CsvOptions {
+        CsvOptions {
+            date_col: "date".to_string(),
+            value_col: "value".to_string(),
+            comment_col: Some("note".to_string()),
+            date_format: "[year]-[month]-[day]".to_string(),
+            clock: DayClock::default(),
+            scale: 1.0,
+            delimiter: b',',
+        }
+    }
+
+    #[test]
+    fn parses_daily_rows_with_comments() {
+        let input = "date,value,note\n2026-07-01,3,first\n2026-07-02,,skipped\n2026-07-03,1.5,\n";
+        let datapoints = parse_csv(input.as_bytes(), &options()).unwrap();
+        assert_eq!(datapoints.len(), 2);
+        assert_eq!(datapoints[0].daystamp.as_deref(), Some("20260701"));
+        assert_eq!(datapoints[0].comment.as_deref(), Some("first"));
+        assert!(datapoints[1].comment.is_none());
+    }
+
+    #[test]
+    fn maps_columns_by_index_and_scales_values() {
+        let mut opts = options();
+        opts.date_col = "2".to_string();
+        opts.value_col = "1".to_string();
+        opts.comment_col = None;
+        opts.scale = 60.0;
+        opts.delimiter = b'\t';
+        opts.date_format = "[year]-[month]-[day] [hour]:[minute]".to_string();
+        let input = "minutes\twhen\n0.5\t2026-07-01 08:30\n";
+        let datapoints = parse_csv(input.as_bytes(), &opts).unwrap();
+        assert!((datapoints[0].value - 30.0).abs() < f64::EPSILON);
+        assert_eq!(
+            datapoints[0].timestamp.map(OffsetDateTime::unix_timestamp),
+            Some(1_782_894_600)
+        );
+    }
+
+    #[test]
+    fn requestids_are_deterministic_and_unique_per_row() {
+        let input = "date,value,note\n2026-07-01,1,a\n2026-07-01,1,a\n";
+        let first = parse_csv(input.as_bytes(), &options()).unwrap();
+        let second = parse_csv(input.as_bytes(), &options()).unwrap();
+        assert_eq!(first[0].requestid, second[0].requestid);
+        assert_ne!(first[0].requestid, first[1].requestid);
+        let key = fnv1a("2026-07-01|1|a");
+        assert_eq!(
+            first[0].requestid.as_deref(),
+            Some(format!("beeline-import-{key:016x}-1").as_str())
+        );
+    }
+
+    #[test]
+    fn slugifies_habit_names() {
+        assert_eq!(slugify("  Read 20 pages!  "), "read-20-pages");
+        assert_eq!(slugify("Yoga & Stretch"), "yoga-stretch");
+    }
+
+    #[test]
+    fn parses_fixed_offsets() {
+        let clock = parse_timezone("-05:30").unwrap();
+        let at = OffsetDateTime::from_unix_timestamp(1_782_871_200).unwrap();
+        assert_eq!(clock.offset_at(at).whole_minutes(), -330);
+        assert!(parse_timezone("Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn named_timezones_follow_daylight_saving() {
+        let options = CsvOptions {
+            date_format: "[year]-[month]-[day] [hour]:[minute]".to_string(),
+            clock: parse_timezone("Europe/Berlin").unwrap(),
+            comment_col: None,
+            ..options()
+        };
+        let input = "date,value\n2026-01-15 08:00,1\n2026-07-15 08:00,1\n";
+        let datapoints = parse_csv(input.as_bytes(), &options).unwrap();
+        let hours: Vec<u8> = datapoints
+            .iter()
+            .map(|dp| dp.timestamp.unwrap().to_offset(UtcOffset::UTC).hour())
+            .collect();
+        assert_eq!(hours, [7, 6]);
+    }
+}
@@ -0,0 +1,65 @@
+use super::ImportedSeries;
+use anyhow::{Context, Result};
+use beeminder::day::DayClock;
+use beeminder::types::CreateDatapoint;
+use std::collections::BTreeMap;
+use time::macros::format_description;
+use time::PrimitiveDateTime;
+
+/// Parses Habitica's task history export
+/// (`Task Name,Task ID,Task Type,Date,Value`).
+///
+/// Every row is one completion of a habit or daily and becomes a datapoint
+/// of value 1; the task value (Habitica's colour score) is ignored.
+pub fn parse(content: &str, clock: DayClock) -> Result<Vec<ImportedSeries>> {
+    let mut csv = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let headers = csv
+        .headers()
+        .context("Failed to read Habitica history header")?
+        .clone();
+    let name_idx = super::resolve_column(&headers, "Task Name")?;
+    let date_idx = super::resolve_column(&headers, "Date")?;
+
+    let mut tasks: BTreeMap<String, ImportedSeries> = BTreeMap::new();
+    for (idx, record) in csv.records().enumerate() {
+        let line = idx + 2;
+        let record = record.with_context(|| format!("Failed to read line {line}"))?;
+        let name = record.get(name_idx).unwrap_or("").to_string();
+        let raw_date = record.get(date_idx).unwrap_or("");
+        let start_time = PrimitiveDateTime::parse(
+            raw_date,
+            format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"),
+        )
+        .with_context(|| format!("Line {line}: invalid date '{raw_date}'"))?;
+        let timestamp = clock.local_time(start_time.date(), start_time.time());
+        tasks
+            .entry(name.clone())
+            .or_insert_with(|| ImportedSeries::new(&name, "times"))
+            .push(
+                raw_date,
+                CreateDatapoint::new(1.0).with_timestamp(timestamp),
+            );
+    }
+
+    Ok(tasks.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_completions_by_task() {
+        let input = "Task Name,Task ID,Task Type,Date,Value\n\
+                     Floss,abc,daily,2026-07-01 21:00:00,1.2\n\
+                     Floss,abc,daily,2026-07-02 21:00:00,1.4\n\
+                     Run,def,habit,2026-07-02 07:00:00,0.5\n";
+        let series = parse(input, DayClock::default()).unwrap();
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0].name, "Floss");
+        assert_eq!(series[0].datapoints.len(), 2);
+    }
+}
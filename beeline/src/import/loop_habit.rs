@@ -0,0 +1,110 @@
+use super::ImportedSeries;
+use anyhow::{Context, Result};
+use beeminder::day::daystamp;
+use beeminder::types::CreateDatapoint;
+use time::macros::format_description;
+use time::Date;
+
+/// Loop stores numerical habit values multiplied by 1000.
+const NUMERICAL_SCALE: f64 = 1000.0;
+/// Checkmark value for a manually checked boolean habit.
+const YES_MANUAL: f64 = 2.0;
+/// Checkmark values a boolean habit can hold: unknown (`-1`), unchecked
+/// (`0`), implicit (`1`), manual (`2`) and skipped (`3`).
+const BOOLEAN_VALUES: [f64; 5] = [-1.0, 0.0, 1.0, YES_MANUAL, 3.0];
+
+/// Parses Loop Habit Tracker's root `Checkmarks.csv` export
+/// (`Date,<habit>,<habit>,...`).
+///
+/// A habit whose column only holds checkmark values is boolean: manual
+/// checks (`2`) become 1 and every other value is skipped. Any other habit
+/// is numerical and its positive values are divided by 1000.
+pub fn parse(content: &str) -> Result<Vec<ImportedSeries>> {
+    let mut csv = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let headers = csv
+        .headers()
+        .context("Failed to read Loop checkmarks header")?
+        .clone();
+    let habits = headers.len().saturating_sub(1);
+
+    let mut rows: Vec<(String, Date, Vec<Option<f64>>)> = Vec::new();
+    for (idx, record) in csv.records().enumerate() {
+        let line = idx + 2;
+        let record = record.with_context(|| format!("Failed to read line {line}"))?;
+        let raw_date = record.get(0).unwrap_or("");
+        let date = Date::parse(raw_date, format_description!("[year]-[month]-[day]"))
+            .with_context(|| format!("Line {line}: invalid date '{raw_date}'"))?;
+        let cells = record
+            .iter()
+            .skip(1)
+            .map(|cell| cell.parse::<f64>().ok())
+            .collect();
+        rows.push((raw_date.to_string(), date, cells));
+    }
+
+    let numerical: Vec<bool> = (0..habits)
+        .map(|habit| {
+            rows.iter()
+                .filter_map(|(_, _, cells)| cells.get(habit).copied().flatten())
+                .any(|raw| !BOOLEAN_VALUES.contains(&raw))
+        })
+        .collect();
+
+    let mut series: Vec<ImportedSeries> = headers
+        .iter()
+        .skip(1)
+        .map(|name| ImportedSeries::new(name, "times"))
+        .collect();
+    for (raw_date, date, cells) in &rows {
+        for (habit, (entry, cell)) in series.iter_mut().zip(cells).enumerate() {
+            let Some(raw) = *cell else {
+                continue;
+            };
+            let value = if numerical[habit] {
+                if raw <= 0.0 {
+                    continue;
+                }
+                raw / NUMERICAL_SCALE
+            } else if (raw - YES_MANUAL).abs() < f64::EPSILON {
+                1.0
+            } else {
+                continue;
+            };
+            entry.push(
+                raw_date,
+                CreateDatapoint::new(value).with_daystamp(&daystamp(*date)),
+            );
+        }
+    }
+
+    series.retain(|habit| !habit.datapoints.is_empty());
+    Ok(series)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_manual_checks_and_numerical_values() {
+        let input = "Date,Meditate,Pages,\n2026-07-02,2,12000,\n2026-07-01,1,0,\n";
+        let series = parse(input).unwrap();
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0].name, "Meditate");
+        assert_eq!(series[0].datapoints.len(), 1);
+        assert!((series[1].datapoints[0].value - 12.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn skips_boolean_skips_and_scales_small_numerical_values() {
+        let input = "Date,Floss,Water\n2026-07-03,3,3\n2026-07-02,2,2500\n2026-07-01,3,-1\n";
+        let series = parse(input).unwrap();
+        assert_eq!(series[0].name, "Floss");
+        assert_eq!(series[0].datapoints.len(), 1);
+        let water: Vec<f64> = series[1].datapoints.iter().map(|dp| dp.value).collect();
+        assert_eq!(water, [0.003, 2.5]);
+    }
+}
@@ -0,0 +1,158 @@
+use super::ImportedSeries;
+use anyhow::{Context, Result};
+use beeminder::types::CreateDatapoint;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// A single activity extracted from a GPX file.
+struct Activity {
+    kind: String,
+    name: Option<String>,
+    start: OffsetDateTime,
+    /// Start time as written in the file
+    raw_start: String,
+    distance_km: f64,
+}
+
+/// Parses a Strava GPX file, or every `.gpx` file in a bulk-export
+/// `activities/` directory.
+///
+/// Activities are grouped by their GPX `<type>` and recorded as kilometres
+/// at the activity's start time, with the activity name as comment.
+pub fn parse(path: &str) -> Result<Vec<ImportedSeries>> {
+    let path = Path::new(path);
+    let files = if path.is_dir() {
+        let mut files = fs::read_dir(path)
+            .with_context(|| format!("Failed to read directory: {}", path.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| {
+                file.extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("gpx"))
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut kinds: BTreeMap<String, ImportedSeries> = BTreeMap::new();
+    for file in files {
+        let content = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read file: {}", file.display()))?;
+        let activity = parse_gpx(&content)
+            .with_context(|| format!("Failed to parse GPX: {}", file.display()))?;
+        add_activity(&mut kinds, activity);
+    }
+    Ok(kinds.into_values().collect())
+}
+
+/// Parses a single GPX document, e.g. one piped in on stdin.
+pub fn parse_content(content: &str) -> Result<Vec<ImportedSeries>> {
+    let mut kinds = BTreeMap::new();
+    add_activity(
+        &mut kinds,
+        parse_gpx(content).context("Failed to parse GPX")?,
+    );
+    Ok(kinds.into_values().collect())
+}
+
+fn add_activity(kinds: &mut BTreeMap<String, ImportedSeries>, activity: Activity) {
+    let mut dp = CreateDatapoint::new(activity.distance_km).with_timestamp(activity.start);
+    if let Some(name) = activity.name.as_deref() {
+        dp = dp.with_comment(name);
+    }
+    kinds
+        .entry(activity.kind.clone())
+        .or_insert_with(|| ImportedSeries::new(&activity.kind, "km"))
+        .push(&activity.raw_start, dp);
+}
+
+fn parse_gpx(content: &str) -> Result<Activity> {
+    let trk = content
+        .find("<trk>")
+        .map_or(content, |start| &content[start..]);
+    let kind = element_text(trk, "type").unwrap_or_else(|| "strava".to_string());
+    let name = element_text(trk, "name");
+
+    let mut points = Vec::new();
+    let mut start = None;
+    for chunk in content.split("<trkpt").skip(1) {
+        let lat = attribute(chunk, "lat").context("Track point without lat")?;
+        let lon = attribute(chunk, "lon").context("Track point without lon")?;
+        points.push((lat, lon));
+        if start.is_none() {
+            if let Some(time) = element_text(chunk, "time") {
+                start = Some((OffsetDateTime::parse(&time, &Rfc3339)?, time));
+            }
+        }
+    }
+    let (start, raw_start) = start.context("No track point timestamps")?;
+    let distance_km = points
+        .windows(2)
+        .map(|pair| haversine_km(pair[0], pair[1]))
+        .sum::<f64>();
+
+    Ok(Activity {
+        kind,
+        name,
+        start,
+        raw_start,
+        distance_km: (distance_km * 100.0).round() / 100.0,
+    })
+}
+
+fn element_text(content: &str, tag: &str) -> Option<String> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let start = content.find(&open)? + open.len();
+    let end = content[start..].find(&close)? + start;
+    let text = content[start..end].trim();
+    let text = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|t| t.strip_suffix("]]>"))
+        .unwrap_or(text);
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn attribute(content: &str, name: &str) -> Option<f64> {
+    let tag_end = content.find('>')?;
+    let tag = &content[..tag_end];
+    let key = format!("{name}=\"");
+    let start = tag.find(&key)? + key.len();
+    let end = tag[start..].find('"')? + start;
+    tag[start..end].parse().ok()
+}
+
+fn haversine_km((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_distance_and_start_time() {
+        let gpx = r#"<?xml version="1.0"?>
+<gpx><metadata><time>2026-07-01T06:00:00Z</time></metadata>
+<trk><name>Morning Run</name><type>running</type><trkseg>
+<trkpt lat="0.0" lon="0.0"><ele>1</ele><time>2026-07-01T06:00:05Z</time></trkpt>
+<trkpt lat="0.0" lon="0.01"><ele>1</ele><time>2026-07-01T06:05:00Z</time></trkpt>
+</trkseg></trk></gpx>"#;
+        let activity = parse_gpx(gpx).unwrap();
+        assert_eq!(activity.kind, "running");
+        assert_eq!(activity.name.as_deref(), Some("Morning Run"));
+        assert_eq!(activity.start.unix_timestamp(), 1_782_885_605);
+        assert!((activity.distance_km - 1.11).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,98 @@
+use super::ImportedSeries;
+use anyhow::{Context, Result};
+use beeminder::day::DayClock;
+use beeminder::types::CreateDatapoint;
+use std::collections::BTreeMap;
+use time::macros::format_description;
+use time::PrimitiveDateTime;
+
+const NO_PROJECT: &str = "(no project)";
+
+/// Parses a Toggl Track detailed report CSV.
+///
+/// Entries are grouped by project and recorded in hours at their start
+/// time, with the entry description as comment.
+pub fn parse(content: &str, clock: DayClock) -> Result<Vec<ImportedSeries>> {
+    let mut csv = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let headers = csv
+        .headers()
+        .context("Failed to read Toggl report header")?
+        .clone();
+    let project_idx = super::resolve_column(&headers, "Project")?;
+    let description_idx = super::resolve_column(&headers, "Description")?;
+    let date_idx = super::resolve_column(&headers, "Start date")?;
+    let time_idx = super::resolve_column(&headers, "Start time")?;
+    let duration_idx = super::resolve_column(&headers, "Duration")?;
+
+    let mut projects: BTreeMap<String, ImportedSeries> = BTreeMap::new();
+    for (idx, record) in csv.records().enumerate() {
+        let line = idx + 2;
+        let record = record.with_context(|| format!("Failed to read line {line}"))?;
+        let project = match record.get(project_idx).unwrap_or("") {
+            "" => NO_PROJECT.to_string(),
+            name => name.to_string(),
+        };
+        let start = format!(
+            "{} {}",
+            record.get(date_idx).unwrap_or(""),
+            record.get(time_idx).unwrap_or("")
+        );
+        let start_time = PrimitiveDateTime::parse(
+            &start,
+            format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"),
+        )
+        .with_context(|| format!("Line {line}: invalid start '{start}'"))?;
+        let timestamp = clock.local_time(start_time.date(), start_time.time());
+        let raw_duration = record.get(duration_idx).unwrap_or("");
+        let hours = parse_duration_hours(raw_duration)
+            .with_context(|| format!("Line {line}: invalid duration '{raw_duration}'"))?;
+
+        let mut dp = CreateDatapoint::new(hours).with_timestamp(timestamp);
+        let description = record.get(description_idx).unwrap_or("");
+        if !description.is_empty() {
+            dp = dp.with_comment(description);
+        }
+        projects
+            .entry(project.clone())
+            .or_insert_with(|| ImportedSeries::new(&project, "hours"))
+            .push(&start, dp);
+    }
+
+    Ok(projects.into_values().collect())
+}
+
+/// Converts Toggl's "HH:MM:SS" duration to hours, rounded to 1/1000.
+fn parse_duration_hours(value: &str) -> Result<f64> {
+    let parts = value
+        .split(':')
+        .map(str::parse::<u32>)
+        .collect::<Result<Vec<_>, _>>()?;
+    let [hours, minutes, seconds] = parts[..] else {
+        anyhow::bail!("expected HH:MM:SS");
+    };
+    let total = f64::from(hours) + f64::from(minutes) / 60.0 + f64::from(seconds) / 3600.0;
+    Ok((total * 1000.0).round() / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_entries_by_project_in_hours() {
+        let input = "User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags\n\
+                     me,me@x,,Writing,,Chapter 4,No,2026-07-01,09:00:00,2026-07-01,10:30:00,01:30:00,\n\
+                     me,me@x,,,,Email,No,2026-07-01,11:00:00,2026-07-01,11:15:00,00:15:00,\n";
+        let series = parse(input, DayClock::default()).unwrap();
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0].name, NO_PROJECT);
+        assert!((series[1].datapoints[0].value - 1.5).abs() < f64::EPSILON);
+        assert_eq!(
+            series[1].datapoints[0].comment.as_deref(),
+            Some("Chapter 4")
+        );
+    }
+}
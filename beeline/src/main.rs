@@ -2,8 +2,10 @@
 
 use anyhow::{Context, Result};
 use beeconfig::BeeConfig;
+use beeminder::day::DayClock;
+use beeminder::entry::{self, EntryGoal};
 use beeminder::types::{
-    CreateAllResponse, CreateDatapoint, CreateGoal, DatapointFull, GoalSummary, GoalType,
+    CreateAllResponse, CreateDatapoint, CreateGoal, DatapointFull, GoalFull, GoalSummary, GoalType,
     UpdateGoal,
 };
 use beeminder::{BeeminderClient, Error as BeeminderError};
@@ -14,13 +16,25 @@ use colored::{Color, Colorize};
 use std::fs;
 use std::io::{self, Read};
 use std::process;
-use time::{Date, Duration, Month, OffsetDateTime, UtcOffset};
+use time::{Duration, OffsetDateTime};
+mod autodata;
 mod backup;
+mod dp;
 mod edit;
+mod import;
+mod output;
+mod search;
+mod stats;
+mod transform;
+
+use output::{BatchResult, OutputFormat, RefreshResult, ReportRow};
 
 #[derive(Parser)]
 #[command(name = "beeline", about = "A CLI for Beeminder")]
 struct Cli {
+    /// Output format: human-readable table, or json/csv/tsv for scripts
+    #[arg(short, long, global = true, value_enum, default_value = "table")]
+    output: OutputFormat,
     #[command(subcommand)]
     command: Command,
 }
@@ -28,23 +42,51 @@ struct Cli {
 #[derive(Subcommand)]
 enum Command {
     /// List all goals
-    List,
+    List {
+        /// Only show goals with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+    },
     /// Add a datapoint
     Add {
         /// The name of the goal
         goal: String,
-        /// The value of the datapoint
-        value: f64,
+        /// The value: a number, arithmetic (10+15) or a duration (1:30, 90m) on goals in hours, minutes or seconds
+        #[arg(allow_hyphen_values = true, required_unless_present = "total")]
+        value: Option<String>,
         /// An optional comment for the datapoint
         comment: Option<String>,
-        /// Date for the datapoint in YYYY-MM-DD format
+        /// Running total reached, for cumulative and odometer goals; the
+        /// datapoint is computed from the goal's current value. VALUE is
+        /// then omitted and the first argument after the goal is the comment
+        #[arg(long, allow_hyphen_values = true)]
+        total: Option<String>,
+        /// Date: YYYY-MM-DD, today, yesterday, ^, -N or a weekday, optionally
+        /// followed by HH:MM; relative to the goal's Beeminder day
         #[arg(long)]
         date: Option<String>,
     },
-    /// Edit recent datapoints for a goal
+    /// List, show, update or delete datapoints without an editor
+    Dp {
+        #[command(subcommand)]
+        command: dp::DpCommand,
+    },
+    /// Edit datapoints for a goal in $EDITOR
     Edit {
         /// The name of the goal
         goal: String,
+        /// Only datapoints on or after this day (YYYY-MM-DD, yesterday, -7, mon, ...)
+        #[arg(long, allow_hyphen_values = true)]
+        since: Option<String>,
+        /// Only datapoints on or before this day
+        #[arg(long, allow_hyphen_values = true)]
+        until: Option<String>,
+        /// Number of datapoints to edit (default 20 without --since/--until)
+        #[arg(long)]
+        count: Option<usize>,
+        /// Apply the changes without asking
+        #[arg(short, long)]
+        yes: bool,
     },
     /// Backup all user data to JSON file
     Backup {
@@ -144,6 +186,70 @@ enum Command {
         /// Path to JSON file with datapoints (use - for stdin)
         file: String,
     },
+    /// Import datapoints from a CSV/TSV file or another app's export
+    #[command(
+        long_about = "Import datapoints from a CSV/TSV file or another app's export.\n\nCSV (default): beeline import <goal> <file>. Columns can be given by header name or 1-based index. Dates use a `time` format description (e.g. \"[year]-[month]-[day] [hour]:[minute]\") or \"unix\". Date-only values are sent as daystamps.\n\nOther apps: beeline import --from loop|habitica|toggl|strava-gpx <file>. Each habit, task, project or activity type becomes a series; map series onto existing goals with --map NAME=SLUG, or pass --create to create goals named after them.\n\nEvery row gets a deterministic requestid, so rows already imported are skipped on re-runs.\n\nExamples:\n  beeline import running runs.csv --date-col Date --value-col Distance --scale 0.001\n  beeline import --from loop Checkmarks.csv --map Meditate=meditation\n  beeline import --from strava-gpx activities/ --map running=run --create"
+    )]
+    Import {
+        /// Goal slug for CSV imports; the export file with --from
+        target: String,
+        /// Path to the CSV/TSV file (use - for stdin)
+        file: Option<String>,
+        /// Export format of the input
+        #[arg(long, value_enum, default_value = "csv")]
+        from: import::ImportSource,
+        /// Map a series from the export onto a goal (NAME=SLUG, repeatable)
+        #[arg(long = "map", value_name = "NAME=SLUG")]
+        mappings: Vec<String>,
+        /// Create goals for unmapped series
+        #[arg(long)]
+        create: bool,
+        /// Date column (header name or 1-based index)
+        #[arg(long, default_value = "date")]
+        date_col: String,
+        /// Value column (header name or 1-based index)
+        #[arg(long, default_value = "value")]
+        value_col: String,
+        /// Comment column (header name or 1-based index)
+        #[arg(long)]
+        comment_col: Option<String>,
+        /// Date format description, or "unix" for unix timestamps
+        #[arg(long, default_value = "[year]-[month]-[day]")]
+        date_format: String,
+        /// Timezone for timestamps without offset: local, utc, +HH:MM or a
+        /// name such as Europe/Berlin
+        #[arg(long, default_value = "local")]
+        timezone: String,
+        /// Factor applied to every value
+        #[arg(long, default_value_t = 1.0)]
+        scale: f64,
+        /// Field delimiter (default: tab for .tsv files, comma otherwise)
+        #[arg(long)]
+        delimiter: Option<char>,
+        /// Datapoints per upload request
+        #[arg(long, default_value_t = 100)]
+        chunk_size: usize,
+        /// Only show the preview, don't upload
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Push datapoints from local sources configured under [[autodata]]
+    #[command(
+        long_about = "Push datapoints from local sources configured under [[autodata]] in the beeminder config.\n\nSources:\n- git: commits per day (path, optional author)\n- wordcount: total words in the markdown files of a directory (path)\n- maildir: messages in a Maildir folder, for inboxer goals (path)\n- todotxt: completed items per day (path)\n\nEach goal gets one datapoint per day with requestid beeline-autodata-YYYYMMDD, so running this from cron repeatedly updates rather than duplicates.\n\nExample config:\n  [[autodata]]\n  goal = \"commits\"\n  source = \"git\"\n  path = \"~/src/project\""
+    )]
+    Autodata {
+        /// Only run the source configured for this goal
+        goal: Option<String>,
+        /// Number of days to report for per-day sources
+        #[arg(short, long, default_value_t = 7)]
+        days: u32,
+        /// Only show the collected datapoints, don't send them
+        #[arg(long)]
+        dry_run: bool,
+    },
     /// Short-circuit a goal (charges current pledge and increases pledge level)
     Shortcircuit {
         /// Goal slug (URL identifier)
@@ -167,6 +273,35 @@ enum Command {
         /// Filter to a specific goal
         #[arg(short, long)]
         goal: Option<String>,
+        /// Filter to goals with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+    },
+    /// Show streaks, weekly/monthly totals, averages and trend of goals
+    Stats {
+        /// Goal slug (URL identifier)
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        goal: Option<String>,
+        /// Show statistics for all goals
+        #[arg(long)]
+        all: bool,
+        /// Number of recent weeks and months to show
+        #[arg(long, default_value_t = 4)]
+        periods: usize,
+    },
+    /// Search datapoint comments across active and archived goals
+    Search {
+        /// Regex matched against comments
+        pattern: String,
+        /// Only search this goal
+        #[arg(short, long)]
+        goal: Option<String>,
+        /// Only datapoints on or after this day (YYYY-MM-DD, yesterday, -7, mon, ...)
+        #[arg(long, allow_hyphen_values = true)]
+        since: Option<String>,
+        /// Match case-insensitively
+        #[arg(short, long)]
+        ignore_case: bool,
     },
     /// Generate shell completions
     #[command(hide = true)]
@@ -179,24 +314,24 @@ enum Command {
     ListGoals,
 }
 
-#[derive(Debug)]
-pub struct EditableDatapoint {
-    pub id: Option<String>,
-    pub timestamp: Option<OffsetDateTime>,
-    pub value: Option<f64>,
-    pub comment: Option<String>,
+/// Whether the goal's current deadline day already has an entry.
+fn has_entry_today(goal: &GoalSummary, clock: &DayClock, now: OffsetDateTime) -> bool {
+    clock.goal_day(goal.deadline, now).contains(goal.lastday)
 }
 
-fn has_entry_today(goal: &GoalSummary) -> bool {
-    let now = OffsetDateTime::now_utc();
-    let today_date = UtcOffset::current_local_offset()
-        .map_or_else(|_| now, |offset| now.to_offset(offset))
-        .date();
-    goal.lastday.date() == today_date
+/// "✓" once today's entry is in, "!" while reminders are due without one.
+fn entry_marker(goal: &GoalSummary, clock: &DayClock, now: OffsetDateTime) -> &'static str {
+    let day = clock.goal_day(goal.deadline, now);
+    if day.contains(goal.lastday) {
+        "✓"
+    } else if day.reminders_due(goal.losedate, goal.leadtime) {
+        "!"
+    } else {
+        " "
+    }
 }
 
-fn format_goal(goal: &GoalSummary) -> String {
-    let has_entry_today = if has_entry_today(goal) { "✓" } else { " " };
+fn format_goal(goal: &GoalSummary, marker: &str) -> String {
     let slug_padded = format!("{:20}", goal.slug);
 
     let color = match goal.safebuf {
@@ -207,19 +342,36 @@ fn format_goal(goal: &GoalSummary) -> String {
         _ => Color::White,
     };
 
-    format!("{} {} [{}]", has_entry_today, slug_padded, goal.limsum)
+    format!("{} {} [{}]", marker, slug_padded, goal.limsum)
         .color(color)
         .to_string()
 }
 
-fn get_client() -> Result<BeeminderClient> {
-    let config = BeeConfig::load_or_onboard().with_context(|| "Failed to load beeminder config")?;
+/// Prints a goal returned by a goal command: its slug, or the full goal.
+fn print_goal(output: OutputFormat, goal: &GoalFull) -> Result<()> {
+    if output.is_table() {
+        println!("{}", goal.slug);
+        Ok(())
+    } else {
+        output::print_record(output, goal)
+    }
+}
+
+fn load_config() -> Result<BeeConfig> {
+    BeeConfig::load_or_onboard().with_context(|| "Failed to load beeminder config")
+}
+
+fn client_from_config(config: &BeeConfig) -> Result<BeeminderClient> {
     let api_key = config
         .api_key()
         .with_context(|| "Missing api_key in beeminder config")?;
     Ok(BeeminderClient::new(api_key))
 }
 
+fn get_client() -> Result<BeeminderClient> {
+    client_from_config(&load_config()?)
+}
+
 fn parse_unix_timestamp(value: Option<i64>) -> Result<Option<OffsetDateTime>> {
     value
         .map(OffsetDateTime::from_unix_timestamp)
@@ -227,33 +379,12 @@ fn parse_unix_timestamp(value: Option<i64>) -> Result<Option<OffsetDateTime>> {
         .map_err(|err| anyhow::anyhow!("Invalid unix timestamp: {err}"))
 }
 
-fn parse_date_daystamp(value: &str) -> Result<String> {
-    let invalid = || anyhow::anyhow!("Invalid date '{value}'; expected YYYY-MM-DD");
-    let [year, month, day] = value
-        .split('-')
-        .collect::<Vec<_>>()
-        .try_into()
-        .map_err(|_| invalid())?;
-
-    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
-        return Err(invalid());
-    }
-
-    let year = year.parse::<i32>().map_err(|_| invalid())?;
-    let month = month.parse::<u8>().map_err(|_| invalid())?;
-    let day = day.parse::<u8>().map_err(|_| invalid())?;
-    let parsed_month = Month::try_from(month).map_err(|_| invalid())?;
-    Date::from_calendar_date(year, parsed_month, day).map_err(|_| invalid())?;
-
-    Ok(format!("{year:04}{month:02}{day:02}"))
-}
-
-fn read_json_input(path: &str) -> Result<String> {
+fn read_input(path: &str) -> Result<String> {
     if path == "-" {
         let mut buffer = String::new();
         io::stdin()
             .read_to_string(&mut buffer)
-            .with_context(|| "Failed to read JSON from stdin")?;
+            .with_context(|| "Failed to read from stdin")?;
         Ok(buffer)
     } else {
         fs::read_to_string(path).with_context(|| format!("Failed to read file: {path}"))
@@ -297,8 +428,34 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Name of a command that only has human-readable output.
+const fn table_only_command(command: &Command) -> Option<&'static str> {
+    match command {
+        Command::Edit { .. } => Some("edit"),
+        Command::Backup { .. } => Some("backup"),
+        Command::Import { .. } => Some("import"),
+        Command::Autodata { .. } => Some("autodata"),
+        Command::Completions { .. } => Some("completions"),
+        Command::Dp {
+            command: dp::DpCommand::Transform { .. },
+        } => Some("dp transform"),
+        Command::Dp {
+            command: dp::DpCommand::Dedupe { .. },
+        } => Some("dp dedupe"),
+        _ => None,
+    }
+}
+
 #[allow(clippy::too_many_lines)]
 async fn run(cli: Cli) -> Result<()> {
+    let output = cli.output;
+    if let Some(name) = table_only_command(&cli.command).filter(|_| !output.is_table()) {
+        return Err(anyhow::anyhow!(
+            "--output {} is not supported by {name}",
+            output.name()
+        ));
+    }
+
     match cli.command {
         Command::Completions { shell } => {
             let mut cmd = Cli::command();
@@ -311,12 +468,20 @@ async fn run(cli: Cli) -> Result<()> {
                 println!("{}", goal.slug);
             }
         }
-        Command::List => {
+        Command::List { tag } => {
             let client = get_client()?;
-            let mut goals: Vec<GoalSummary> = client.get_goals().await?;
+            let (mut goals, user) = tokio::try_join!(client.get_goals(), client.get_user())?;
+            if let Some(tag) = &tag {
+                goals.retain(|goal| goal.has_tag(tag));
+            }
 
+            // The account's clock; falls back to the machine's offset for
+            // timezones the database doesn't know.
+            let clock = DayClock::for_user(&user.timezone);
+            let now = OffsetDateTime::now_utc();
+            let entered = |goal: &GoalSummary| has_entry_today(goal, &clock, now);
             goals.sort_by(|a, b| {
-                let today_cmp = has_entry_today(a).cmp(&has_entry_today(b));
+                let today_cmp = entered(a).cmp(&entered(b));
                 if today_cmp != std::cmp::Ordering::Equal {
                     return today_cmp;
                 }
@@ -324,30 +489,75 @@ async fn run(cli: Cli) -> Result<()> {
                 a.safebuf.cmp(&b.safebuf)
             });
 
-            for goal in goals {
-                println!("{}", format_goal(&goal));
+            if output.is_table() {
+                for goal in &goals {
+                    println!("{}", format_goal(goal, entry_marker(goal, &clock, now)));
+                }
+            } else {
+                output::print_records(output, &goals)?;
             }
         }
         Command::Add {
             goal,
             value,
             comment,
+            total,
             date,
         } => {
+            let (value, comment) = match (&total, value, comment) {
+                (Some(_), Some(_), Some(_)) => {
+                    return Err(anyhow::anyhow!(
+                        "--total replaces the value; pass only a comment"
+                    ))
+                }
+                (Some(_), value, comment) => (None, value.or(comment)),
+                (None, value, comment) => (value, comment),
+            };
             let client = get_client()?;
+            let goal_full = client.get_goal_full(&goal, false).await?;
+            let entry_goal = EntryGoal::from(&goal_full);
+            let value = if let Some(total) = total {
+                let total = entry::parse_value(&total, &entry_goal)?;
+                let computed = entry::total_entry(&goal_full, total)?;
+                eprintln!(
+                    "{goal}: {} -> {total} ({:+}), submitting {}",
+                    computed.previous, computed.delta, computed.value
+                );
+                computed.value
+            } else {
+                entry::parse_value(value.as_deref().unwrap_or_default(), &entry_goal)?
+            };
             let mut dp = CreateDatapoint::new(value);
             if let Some(comment) = comment {
                 dp = dp.with_comment(&comment);
             }
             if let Some(date) = date {
-                let daystamp = parse_date_daystamp(&date)?;
-                dp = dp.with_daystamp(&daystamp);
+                let clock = DayClock::for_user(&client.get_user().await?.timezone);
+                let today =
+                    clock.goal_day(goal_full.deadline.unwrap_or(0), OffsetDateTime::now_utc());
+                dp = entry::parse_date(&date, &today)?.apply(dp);
             }
-            client.create_datapoint(&goal, &dp).await?;
+            let created = client.create_datapoint(&goal, &dp).await?;
+            output::print_record(output, &created)?;
         }
-        Command::Edit { goal } => {
+        Command::Dp { command } => {
             let client = get_client()?;
-            edit::edit_datapoints(&client, &goal).await?;
+            dp::run(&client, command, output).await?;
+        }
+        Command::Edit {
+            goal,
+            since,
+            until,
+            count,
+            yes,
+        } => {
+            let client = get_client()?;
+            let range = edit::EditRange {
+                since,
+                until,
+                count,
+            };
+            edit::edit_datapoints(&client, &goal, &range, yes).await?;
         }
         Command::Backup { filename } => {
             let client = get_client()?;
@@ -392,7 +602,7 @@ async fn run(cli: Cli) -> Result<()> {
             goal.secret = secret;
             goal.datapublic = datapublic;
             let created = client.create_goal(&goal).await?;
-            println!("{}", created.slug);
+            print_goal(output, &created)?;
         }
         Command::GoalUpdate {
             goal,
@@ -420,55 +630,146 @@ async fn run(cli: Cli) -> Result<()> {
             update.datapublic = datapublic;
             update.archived = archived;
             let updated = client.update_goal(&goal, &update).await?;
-            println!("{}", updated.slug);
+            print_goal(output, &updated)?;
         }
         Command::GoalRefresh { goal } => {
             let client = get_client()?;
-            let refreshed = client.refresh_graph(&goal).await?;
-            println!("{refreshed}");
+            let queued = client.refresh_graph(&goal).await?;
+            if output.is_table() {
+                println!("{queued}");
+            } else {
+                output::print_record(output, &RefreshResult { goal, queued })?;
+            }
         }
         Command::AddBatch { goal, file } => {
             let client = get_client()?;
-            let payload = read_json_input(&file)?;
+            let payload = read_input(&file)?;
             let datapoints: Vec<CreateDatapoint> = serde_json::from_str(&payload)
                 .with_context(|| "Failed to parse datapoints JSON array")?;
-            let result = client.create_all_datapoints(&goal, &datapoints).await?;
-            match result {
-                CreateAllResponse::Success(successes) => {
-                    println!("Created {} datapoints.", successes.len());
+            let result = match client.create_all_datapoints(&goal, &datapoints).await? {
+                CreateAllResponse::Success(created) => BatchResult {
+                    created,
+                    errors: Vec::new(),
+                },
+                CreateAllResponse::Partial { successes, errors } => BatchResult {
+                    created: successes,
+                    errors,
+                },
+            };
+            match output {
+                OutputFormat::Table if result.errors.is_empty() => {
+                    println!("Created {} datapoints.", result.created.len());
                 }
-                CreateAllResponse::Partial { successes, errors } => {
+                OutputFormat::Table => {
                     println!(
                         "Created {} datapoints with {} errors.",
-                        successes.len(),
-                        errors.len()
+                        result.created.len(),
+                        result.errors.len()
                     );
-                    if !errors.is_empty() {
-                        eprintln!(
-                            "{}",
-                            serde_json::to_string_pretty(&errors)
-                                .unwrap_or_else(|_| "Failed to format errors".to_string())
-                        );
-                    }
                 }
+                OutputFormat::Json => output::print_json(&result)?,
+                OutputFormat::Csv | OutputFormat::Tsv => {
+                    output::print_records(output, &result.created)?;
+                }
+            }
+            if !result.errors.is_empty() && output != OutputFormat::Json {
+                eprintln!(
+                    "{}",
+                    serde_json::to_string_pretty(&result.errors)
+                        .unwrap_or_else(|_| "Failed to format errors".to_string())
+                );
             }
         }
+        Command::Import {
+            target,
+            file,
+            from,
+            mappings,
+            create,
+            date_col,
+            value_col,
+            comment_col,
+            date_format,
+            timezone,
+            scale,
+            delimiter,
+            chunk_size,
+            dry_run,
+            yes,
+        } => {
+            let clock = import::parse_timezone(&timezone)?;
+            let upload = import::UploadOptions {
+                chunk_size,
+                dry_run,
+                yes,
+            };
+            if from == import::ImportSource::Csv {
+                let file = file.ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "CSV imports need a goal and a file: beeline import <goal> <file>"
+                    )
+                })?;
+                let delimiter = match delimiter {
+                    Some(c) => u8::try_from(c).map_err(|_| {
+                        anyhow::anyhow!("Delimiter must be a single ASCII character")
+                    })?,
+                    None => import::delimiter_for_path(&file),
+                };
+                let options = import::CsvOptions {
+                    date_col,
+                    value_col,
+                    comment_col,
+                    date_format,
+                    clock,
+                    scale,
+                    delimiter,
+                };
+                let payload = read_input(&file)?;
+                let datapoints = import::parse_csv(payload.as_bytes(), &options)?;
+                let client = get_client()?;
+                import::import_datapoints(&client, &target, datapoints, &upload).await?;
+            } else {
+                if file.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "--from takes a single export file: beeline import --from <format> <file>"
+                    ));
+                }
+                let content = if from == import::ImportSource::StravaGpx && target != "-" {
+                    String::new()
+                } else {
+                    read_input(&target)?
+                };
+                let series = import::parse_export(from, &target, &content, clock, scale)?;
+                let mappings = import::parse_mappings(&mappings)?;
+                let client = get_client()?;
+                import::import_series(&client, series, &mappings, create, &upload).await?;
+            }
+        }
+        Command::Autodata {
+            goal,
+            days,
+            dry_run,
+        } => {
+            let config = load_config()?;
+            let client = client_from_config(&config)?;
+            autodata::run(&client, &config.autodata, goal.as_deref(), days, dry_run).await?;
+        }
         Command::Shortcircuit { goal } => {
             let client = get_client()?;
             let updated = client.shortcircuit(&goal).await?;
-            println!("{}", updated.slug);
+            print_goal(output, &updated)?;
         }
         Command::Stepdown { goal } => {
             let client = get_client()?;
             let updated = client.stepdown(&goal).await?;
-            println!("{}", updated.slug);
+            print_goal(output, &updated)?;
         }
         Command::CancelStepdown { goal } => {
             let client = get_client()?;
             let updated = client.cancel_stepdown(&goal).await?;
-            println!("{}", updated.slug);
+            print_goal(output, &updated)?;
         }
-        Command::Report { days, goal } => {
+        Command::Report { days, goal, tag } => {
             let client = get_client()?;
             let since = OffsetDateTime::now_utc() - Duration::days(i64::try_from(days)?);
             let diff = client.get_user_diff(since).await?;
@@ -481,6 +782,11 @@ async fn run(cli: Cli) -> Result<()> {
                         continue;
                     }
                 }
+                if let Some(tag) = &tag {
+                    if !g.has_tag(tag) {
+                        continue;
+                    }
+                }
                 if let Some(datapoints) = &g.datapoints {
                     for dp in datapoints {
                         all_datapoints.push((&g.slug, dp));
@@ -492,7 +798,13 @@ async fn run(cli: Cli) -> Result<()> {
             all_datapoints.sort_by_key(|b| std::cmp::Reverse(b.1.timestamp));
 
             // Display
-            if all_datapoints.is_empty() {
+            if !output.is_table() {
+                let rows: Vec<ReportRow> = all_datapoints
+                    .into_iter()
+                    .map(|(goal, datapoint)| ReportRow { goal, datapoint })
+                    .collect();
+                output::print_records(output, &rows)?;
+            } else if all_datapoints.is_empty() {
                 println!("No datapoints in the last {days} days.");
             } else {
                 for (slug, dp) in all_datapoints {
@@ -507,6 +819,31 @@ async fn run(cli: Cli) -> Result<()> {
                 }
             }
         }
+        Command::Stats {
+            goal,
+            all: _,
+            periods,
+        } => {
+            let client = get_client()?;
+            stats::run(&client, goal.as_deref(), periods, output).await?;
+        }
+        Command::Search {
+            pattern,
+            goal,
+            since,
+            ignore_case,
+        } => {
+            let client = get_client()?;
+            search::run(
+                &client,
+                &pattern,
+                goal.as_deref(),
+                since.as_deref(),
+                ignore_case,
+                output,
+            )
+            .await?;
+        }
     }
 
     Ok(())
@@ -516,6 +853,37 @@ async fn run(cli: Cli) -> Result<()> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn stats_needs_a_goal_or_all() {
+        assert!(Cli::try_parse_from(["beeline", "stats"]).is_err());
+        assert!(Cli::try_parse_from(["beeline", "stats", "reading", "--all"]).is_err());
+        let cli = Cli::try_parse_from(["beeline", "stats", "--all"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Stats {
+                goal: None,
+                all: true,
+                periods: 4
+            }
+        ));
+    }
+
+    #[test]
+    fn parses_tag_filters() {
+        let cli = Cli::try_parse_from(["beeline", "list", "--tag", "work"]).unwrap();
+        assert!(matches!(cli.command, Command::List { tag: Some(tag) } if tag == "work"));
+
+        let cli = Cli::try_parse_from(["beeline", "report", "-t", "work", "-d", "7"]).unwrap();
+        match cli.command {
+            Command::Report { days, goal, tag } => {
+                assert_eq!(days, 7);
+                assert_eq!(goal, None);
+                assert_eq!(tag.as_deref(), Some("work"));
+            }
+            _ => panic!("expected report command"),
+        }
+    }
+
     #[test]
     fn parses_add_date_option() {
         let cli = Cli::try_parse_from([
@@ -535,9 +903,10 @@ mod tests {
                 value,
                 comment,
                 date,
+                ..
             } => {
                 assert_eq!(goal, "pushups");
-                assert!((value - 25.0).abs() < f64::EPSILON);
+                assert_eq!(value.as_deref(), Some("25"));
                 assert_eq!(comment.as_deref(), Some("morning set"));
                 assert_eq!(date.as_deref(), Some("2026-07-01"));
             }
@@ -546,16 +915,113 @@ mod tests {
     }
 
     #[test]
-    fn parses_date_as_daystamp() {
-        assert_eq!(parse_date_daystamp("2026-07-01").unwrap(), "20260701");
+    fn parses_datapoint_commands() {
+        let cli = Cli::try_parse_from([
+            "beeline", "dp", "list", "reading", "--since", "-7", "--limit", "5",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Dp {
+                command:
+                    dp::DpCommand::List {
+                        goal, since, limit, ..
+                    },
+            } => {
+                assert_eq!(goal, "reading");
+                assert_eq!(since.as_deref(), Some("-7"));
+                assert_eq!(limit, Some(5));
+            }
+            _ => panic!("expected dp list command"),
+        }
+
+        let cli = Cli::try_parse_from(["beeline", "dp", "delete", "reading", "a1", "b2"]).unwrap();
+        match cli.command {
+            Command::Dp {
+                command: dp::DpCommand::Delete { ids, .. },
+            } => assert_eq!(ids, ["a1", "b2"]),
+            _ => panic!("expected dp delete command"),
+        }
+        assert!(Cli::try_parse_from(["beeline", "dp", "delete", "reading"]).is_err());
+
+        let cli = Cli::try_parse_from(["beeline", "dp", "dedupe", "reading", "--dry-run"]).unwrap();
+        match cli.command {
+            Command::Dp {
+                command:
+                    dp::DpCommand::Dedupe {
+                        window, dry_run, ..
+                    },
+            } => {
+                assert_eq!(window, "10m");
+                assert!(dry_run);
+            }
+            _ => panic!("expected dp dedupe command"),
+        }
+    }
+
+    #[test]
+    fn parses_edit_range() {
+        let cli = Cli::try_parse_from([
+            "beeline", "edit", "reading", "--since", "-30", "--count", "100", "-y",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Edit {
+                since, count, yes, ..
+            } => {
+                assert_eq!(since.as_deref(), Some("-30"));
+                assert_eq!(count, Some(100));
+                assert!(yes);
+            }
+            _ => panic!("expected edit command"),
+        }
     }
 
     #[test]
-    fn rejects_invalid_add_date() {
-        let err = parse_date_daystamp("20260701").unwrap_err();
-        assert!(
-            err.to_string().contains("YYYY-MM-DD"),
-            "unexpected error: {err}"
-        );
+    fn parses_search() {
+        let cli =
+            Cli::try_parse_from(["beeline", "search", "dune", "--since", "-30", "-i"]).unwrap();
+        match cli.command {
+            Command::Search {
+                pattern,
+                goal,
+                since,
+                ignore_case,
+            } => {
+                assert_eq!(pattern, "dune");
+                assert_eq!(goal, None);
+                assert_eq!(since.as_deref(), Some("-30"));
+                assert!(ignore_case);
+            }
+            _ => panic!("expected search command"),
+        }
+    }
+
+    #[test]
+    fn parses_add_total_without_value() {
+        let cli = Cli::try_parse_from(["beeline", "add", "reading", "--total", "312", "chapter 9"])
+            .unwrap();
+        match cli.command {
+            Command::Add {
+                value,
+                comment,
+                total,
+                ..
+            } => {
+                assert_eq!(value.as_deref(), Some("chapter 9"));
+                assert_eq!(comment, None);
+                assert_eq!(total.as_deref(), Some("312"));
+            }
+            _ => panic!("expected add command"),
+        }
+        assert!(Cli::try_parse_from(["beeline", "add", "reading"]).is_err());
+    }
+
+    #[test]
+    fn parses_negative_add_values() {
+        let cli = Cli::try_parse_from(["beeline", "add", "weight", "-1.5"]).unwrap();
+        match cli.command {
+            Command::Add { value, .. } => assert_eq!(value.as_deref(), Some("-1.5")),
+            _ => panic!("expected add command"),
+        }
     }
 }
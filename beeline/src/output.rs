@@ -0,0 +1,304 @@
+use anyhow::{Context, Result};
+use beeminder::search::SearchHit;
+use beeminder::types::{Datapoint, DatapointFull, GoalFull, GoalSummary};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io;
+use time::OffsetDateTime;
+
+/// Output formats selected with the global `--output` flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Table,
+    /// JSON using the API field names
+    Json,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Tab-separated values with a header row
+    Tsv,
+}
+
+impl OutputFormat {
+    pub const fn is_table(self) -> bool {
+        matches!(self, Self::Table)
+    }
+
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_string())
+    }
+}
+
+/// A row type with fixed CSV/TSV columns. The columns are part of the
+/// output schema, so only append to them.
+pub trait Record {
+    const COLUMNS: &'static [&'static str];
+
+    fn fields(&self) -> Vec<String>;
+}
+
+/// Prints a list as a JSON array or as CSV/TSV rows.
+pub fn print_records<T: Serialize + Record>(format: OutputFormat, items: &[T]) -> Result<()> {
+    match format {
+        OutputFormat::Json => print_json(&items),
+        OutputFormat::Csv => write_delimited(b',', items),
+        OutputFormat::Tsv => write_delimited(b'\t', items),
+        // Each command renders its own table.
+        OutputFormat::Table => Ok(()),
+    }
+}
+
+/// Prints a single item as a JSON object or as one CSV/TSV row.
+pub fn print_record<T: Serialize + Record>(format: OutputFormat, item: &T) -> Result<()> {
+    match format {
+        OutputFormat::Json => print_json(item),
+        _ => print_records(format, std::slice::from_ref(item)),
+    }
+}
+
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    let json = serde_json::to_string_pretty(value).context("Failed to serialize output")?;
+    println!("{json}");
+    Ok(())
+}
+
+fn write_delimited<T: Record>(delimiter: u8, items: &[T]) -> Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(io::stdout());
+    writer.write_record(T::COLUMNS)?;
+    for item in items {
+        writer.write_record(item.fields())?;
+    }
+    writer.flush().context("Failed to write output")?;
+    Ok(())
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn unix(value: OffsetDateTime) -> String {
+    value.unix_timestamp().to_string()
+}
+
+/// A datapoint from `report`, tagged with its goal.
+#[derive(Serialize)]
+pub struct ReportRow<'a> {
+    pub goal: &'a str,
+    #[serde(flatten)]
+    pub datapoint: &'a DatapointFull,
+}
+
+/// Result of `goal-refresh`.
+#[derive(Serialize)]
+pub struct RefreshResult {
+    pub goal: String,
+    pub queued: bool,
+}
+
+/// Result of `add-batch`; `errors` holds the API's error objects.
+#[derive(Serialize)]
+pub struct BatchResult {
+    pub created: Vec<DatapointFull>,
+    pub errors: Vec<serde_json::Value>,
+}
+
+const GOAL_COLUMNS: &[&str] = &[
+    "slug",
+    "title",
+    "goal_type",
+    "safebuf",
+    "losedate",
+    "limsum",
+    "rate",
+    "goalval",
+    "goaldate",
+    "lastday",
+    "tags",
+];
+
+const DATAPOINT_COLUMNS: &[&str] = &[
+    "id",
+    "timestamp",
+    "daystamp",
+    "value",
+    "comment",
+    "requestid",
+];
+
+impl Record for GoalSummary {
+    const COLUMNS: &'static [&'static str] = GOAL_COLUMNS;
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.slug.clone(),
+            self.title.clone(),
+            self.goal_type.clone(),
+            self.safebuf.to_string(),
+            unix(self.losedate),
+            self.limsum.clone(),
+            opt(self.rate),
+            opt(self.goalval),
+            opt(self.goaldate.map(unix)),
+            unix(self.lastday),
+            self.tags.join(";"),
+        ]
+    }
+}
+
+impl Record for GoalFull {
+    const COLUMNS: &'static [&'static str] = GOAL_COLUMNS;
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.slug.clone(),
+            opt(self.title.as_ref()),
+            opt(self.goal_type.as_ref()),
+            opt(self.safebuf),
+            opt(self.losedate.map(unix)),
+            opt(self.limsum.as_ref()),
+            opt(self.rate),
+            opt(self.goalval),
+            opt(self.goaldate.map(unix)),
+            opt(self.lastday.map(unix)),
+            self.tags.as_deref().unwrap_or_default().join(";"),
+        ]
+    }
+}
+
+impl Record for Datapoint {
+    const COLUMNS: &'static [&'static str] = DATAPOINT_COLUMNS;
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            unix(self.timestamp),
+            self.daystamp.clone(),
+            self.value.to_string(),
+            opt(self.comment.as_ref()),
+            opt(self.requestid.as_ref()),
+        ]
+    }
+}
+
+impl Record for SearchHit {
+    const COLUMNS: &'static [&'static str] = &[
+        "goal",
+        "id",
+        "timestamp",
+        "daystamp",
+        "value",
+        "comment",
+        "requestid",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        let mut fields = vec![self.goal.clone()];
+        fields.extend(self.datapoint.fields());
+        fields
+    }
+}
+
+impl Record for DatapointFull {
+    const COLUMNS: &'static [&'static str] = DATAPOINT_COLUMNS;
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            unix(self.timestamp),
+            self.daystamp.clone(),
+            opt(self.value),
+            opt(self.comment.as_ref()),
+            opt(self.requestid.as_ref()),
+        ]
+    }
+}
+
+impl Record for ReportRow<'_> {
+    const COLUMNS: &'static [&'static str] = &[
+        "goal",
+        "id",
+        "timestamp",
+        "daystamp",
+        "value",
+        "comment",
+        "requestid",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        let mut fields = vec![self.goal.to_string()];
+        fields.extend(self.datapoint.fields());
+        fields
+    }
+}
+
+impl Record for RefreshResult {
+    const COLUMNS: &'static [&'static str] = &["goal", "queued"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![self.goal.clone(), self.queued.to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use beeminder::testing::{self, datapoint_full};
+
+    fn datapoint(comment: Option<&str>) -> DatapointFull {
+        datapoint_full(&Datapoint {
+            comment: comment.map(String::from),
+            ..testing::datapoint("abc", 2.5, "")
+        })
+    }
+
+    #[test]
+    fn report_rows_prefix_the_goal() {
+        let dp = datapoint(Some("a, b"));
+        let row = ReportRow {
+            goal: "reading",
+            datapoint: &dp,
+        };
+        assert_eq!(ReportRow::COLUMNS.len(), row.fields().len());
+        assert_eq!(
+            row.fields(),
+            vec![
+                "reading",
+                "abc",
+                "1782871200",
+                "20260630",
+                "2.5",
+                "a, b",
+                ""
+            ]
+        );
+
+        let json = serde_json::to_value(&row).unwrap();
+        assert_eq!(json["goal"], "reading");
+        assert_eq!(json["id"], "abc");
+        assert_eq!(json["timestamp"], testing::ENTERED_AT);
+    }
+
+    #[test]
+    fn goal_columns_match_fields() {
+        let goal: GoalFull = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "slug": "reading",
+            "tags": ["work", "books"],
+        }))
+        .unwrap();
+        let fields = goal.fields();
+        assert_eq!(fields.len(), GOAL_COLUMNS.len());
+        assert_eq!(fields[0], "reading");
+        assert_eq!(fields[10], "work;books");
+    }
+
+    #[test]
+    fn parses_output_names() {
+        assert_eq!(OutputFormat::from_str("tsv", true), Ok(OutputFormat::Tsv));
+        assert_eq!(OutputFormat::Json.name(), "json");
+    }
+}
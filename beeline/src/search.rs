@@ -0,0 +1,45 @@
+use crate::output::{self, OutputFormat};
+use anyhow::Result;
+use beeminder::search::{CommentSearch, SearchError};
+use beeminder::BeeminderClient;
+
+/// Searches the comments of one goal, or of all active and archived goals,
+/// and prints the matching datapoints newest first.
+pub async fn run(
+    client: &BeeminderClient,
+    pattern: &str,
+    goal: Option<&str>,
+    since: Option<&str>,
+    ignore_case: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let hits = CommentSearch::new(pattern, ignore_case)?
+        .goal(goal)
+        .since(since)
+        .run(client)
+        .await
+        .map_err(|err| match err {
+            // Keep API errors downcastable for `handle_error`.
+            SearchError::Api(err) => anyhow::Error::from(err),
+            other => other.into(),
+        })?;
+    if !output.is_table() {
+        return output::print_records(output, &hits);
+    }
+    if hits.is_empty() {
+        println!("No matches.");
+        return Ok(());
+    }
+    let width = hits.iter().map(|hit| hit.goal.len()).max().unwrap_or(0);
+    for hit in &hits {
+        let dp = &hit.datapoint;
+        println!(
+            "{:width$}  {}  {:>10}  {}",
+            hit.goal,
+            dp.daystamp,
+            dp.value,
+            dp.comment.as_deref().unwrap_or("")
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use crate::output::{self, OutputFormat, Record};
+use anyhow::{Context, Result};
+use beeminder::day::DayClock;
+use beeminder::stats::{goal_stats, GoalStats, PeriodTotal, StatsOptions, AVERAGE_WINDOWS};
+use beeminder::BeeminderClient;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+/// Statistics of one goal, as printed by `beeline stats`.
+#[derive(Serialize)]
+pub struct GoalStatsRow {
+    pub goal: String,
+    #[serde(flatten)]
+    pub stats: GoalStats,
+}
+
+impl Record for GoalStatsRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "goal",
+        "entry_days",
+        "first_day",
+        "last_day",
+        "current_streak",
+        "longest_streak",
+        "average_7d",
+        "average_30d",
+        "average_90d",
+        "trend_per_day",
+        "on_road_percent",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        let stats = &self.stats;
+        let mut fields = vec![
+            self.goal.clone(),
+            stats.entry_days.to_string(),
+            stats.first_day.clone().unwrap_or_default(),
+            stats.last_day.clone().unwrap_or_default(),
+            stats.current_streak.to_string(),
+            stats.longest_streak.to_string(),
+        ];
+        for window in AVERAGE_WINDOWS {
+            fields.push(
+                stats
+                    .rolling_averages
+                    .iter()
+                    .find(|average| average.days == window)
+                    .map(|average| average.average.to_string())
+                    .unwrap_or_default(),
+            );
+        }
+        fields.push(
+            stats
+                .trend_per_day
+                .map(|t| t.to_string())
+                .unwrap_or_default(),
+        );
+        fields.push(
+            stats
+                .on_road_percent
+                .map(|p| p.to_string())
+                .unwrap_or_default(),
+        );
+        fields
+    }
+}
+
+/// Fetches the datapoints of one goal, or of all goals, and prints their
+/// statistics.
+pub async fn run(
+    client: &BeeminderClient,
+    goal: Option<&str>,
+    periods: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let slugs = match goal {
+        Some(goal) => vec![goal.to_string()],
+        None => client
+            .get_goals()
+            .await?
+            .into_iter()
+            .map(|goal| goal.slug)
+            .collect(),
+    };
+
+    let clock = DayClock::for_user(&client.get_user().await?.timezone);
+    let now = OffsetDateTime::now_utc();
+    let mut rows = Vec::new();
+    for slug in slugs {
+        let goal = client.get_goal_full(&slug, true).await?;
+        let today = clock.goal_day(goal.deadline.unwrap_or(0), now).today;
+        let options = StatsOptions::for_goal(&goal, today)
+            .with_context(|| format!("Can't compute statistics for {slug}"))?;
+        let datapoints = goal.datapoints.as_deref().unwrap_or_default();
+        rows.push(GoalStatsRow {
+            goal: slug,
+            stats: goal_stats(datapoints, &options),
+        });
+    }
+
+    if !output.is_table() {
+        return output::print_records(output, &rows);
+    }
+    for (idx, row) in rows.iter().enumerate() {
+        if idx > 0 {
+            println!();
+        }
+        print_stats(row, periods);
+    }
+    Ok(())
+}
+
+fn print_stats(row: &GoalStatsRow, periods: usize) {
+    let stats = &row.stats;
+    println!("{}", row.goal);
+    let (Some(first), Some(last)) = (&stats.first_day, &stats.last_day) else {
+        println!("  no datapoints");
+        return;
+    };
+    println!("  entries   {} days, {first} to {last}", stats.entry_days);
+    println!(
+        "  streak    {} days (longest {})",
+        stats.current_streak, stats.longest_streak
+    );
+    if !stats.rolling_averages.is_empty() {
+        let averages: Vec<String> = stats
+            .rolling_averages
+            .iter()
+            .map(|average| format!("{}d {}", average.days, round(average.average)))
+            .collect();
+        println!("  averages  {}", averages.join("  "));
+    }
+    if let Some(trend) = stats.trend_per_day {
+        println!("  trend     {:+}/day", round(trend));
+    }
+    if let Some(percent) = stats.on_road_percent {
+        println!("  on road   {percent:.0}% of days");
+    }
+    println!(
+        "  weeks     {}",
+        recent_periods(&stats.weekly_totals, periods)
+    );
+    println!(
+        "  months    {}",
+        recent_periods(&stats.monthly_totals, periods)
+    );
+}
+
+fn recent_periods(totals: &[PeriodTotal], count: usize) -> String {
+    let skip = totals.len().saturating_sub(count);
+    totals[skip..]
+        .iter()
+        .map(|total| format!("{}: {}", total.period, round(total.total)))
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
@@ -0,0 +1,249 @@
+use crate::import::confirm;
+use anyhow::{anyhow, Result};
+use beeconfig::TIMESTAMP_FORMAT;
+use beeminder::changeset::{Change, Changeset};
+use beeminder::entry::{self, EntryGoal, TimeUnit};
+use beeminder::types::{Datapoint, UpdateDatapoint};
+use beeminder::{BeeminderClient, Error as BeeminderError};
+use regex::Regex;
+use std::io::{self, Write};
+use time::Duration;
+
+/// Number of changes shown in the preview.
+const PREVIEW_CHANGES: usize = 20;
+
+/// Edits applied to every selected datapoint.
+#[derive(Debug, Default)]
+pub struct Transform {
+    pub scale: Option<f64>,
+    pub offset: Option<f64>,
+    pub shift: Option<Duration>,
+    pub replace: Option<(Regex, String)>,
+    /// Only datapoints whose comment matches
+    pub matching: Option<Regex>,
+    pub delete: bool,
+}
+
+impl Transform {
+    pub const fn is_empty(&self) -> bool {
+        !self.delete
+            && self.scale.is_none()
+            && self.offset.is_none()
+            && self.shift.is_none()
+            && self.replace.is_none()
+    }
+
+    /// Plans the changes for `datapoints`, skipping points left unchanged.
+    pub fn plan(&self, datapoints: &[Datapoint]) -> Changeset {
+        let changes = datapoints
+            .iter()
+            .filter(|dp| {
+                self.matching
+                    .as_ref()
+                    .is_none_or(|re| re.is_match(dp.comment.as_deref().unwrap_or("")))
+            })
+            .filter_map(|dp| {
+                if self.delete {
+                    return Some(Change::Delete(dp.clone()));
+                }
+                let after = self.apply(dp);
+                let changed =
+                    after.value.is_some() || after.timestamp.is_some() || after.comment.is_some();
+                changed.then(|| Change::Update {
+                    before: dp.clone(),
+                    after,
+                })
+            })
+            .collect();
+        Changeset {
+            changes,
+            unknown_ids: Vec::new(),
+        }
+    }
+
+    /// The update for one datapoint, holding only the fields that change.
+    fn apply(&self, dp: &Datapoint) -> UpdateDatapoint {
+        let mut update = UpdateDatapoint::new(dp.id.clone());
+        let value = dp.value * self.scale.unwrap_or(1.0) + self.offset.unwrap_or(0.0);
+        if value != dp.value {
+            update.value = Some(value);
+        }
+        if let Some(shift) = self.shift.filter(|shift| !shift.is_zero()) {
+            update.timestamp = Some(dp.timestamp + shift);
+        }
+        if let Some((re, with)) = &self.replace {
+            let comment = dp.comment.as_deref().unwrap_or("");
+            let replaced = re.replace_all(comment, with.as_str());
+            if replaced != comment {
+                update.comment = Some(replaced.into_owned());
+            }
+        }
+        update
+    }
+}
+
+/// Parses a duration such as `-2h`, `90m` or `1:30`.
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let goal = EntryGoal {
+        integery: false,
+        time_unit: Some(TimeUnit::Seconds),
+    };
+    let seconds = entry::parse_value(value, &goal)
+        .map_err(|_| anyhow!("Invalid duration '{value}'; expected e.g. -2h, 90m or 1:30"))?;
+    Ok(Duration::seconds_f64(seconds))
+}
+
+/// Previews, confirms and applies the changes in batches of concurrent
+/// requests. Failures don't stop the run; they are reported at the end.
+pub async fn run(
+    client: &BeeminderClient,
+    goal: &str,
+    changeset: &Changeset,
+    batch_size: usize,
+    dry_run: bool,
+    yes: bool,
+) -> Result<()> {
+    print_preview(&changeset.changes);
+    if changeset.is_empty() || dry_run {
+        return Ok(());
+    }
+    let count = changeset.changes.len();
+    if !yes && !confirm(&format!("Apply {count} changes to {goal}?"))? {
+        println!("Aborted.");
+        return Ok(());
+    }
+    apply(client, goal, changeset, batch_size).await
+}
+
+/// Applies the changes in batches of concurrent requests, reporting
+/// progress on stderr. Points changed elsewhere since they were loaded
+/// are skipped; they and other failures are listed once every batch has
+/// run.
+pub async fn apply(
+    client: &BeeminderClient,
+    goal: &str,
+    changeset: &Changeset,
+    batch_size: usize,
+) -> Result<()> {
+    let total = changeset.changes.len();
+    let result = changeset
+        .apply_checked_batched(client, goal, batch_size, |done| {
+            eprint!("\rApplied {done}/{total}");
+            // Progress is cosmetic; a failed flush only delays it
+            let _ = io::stderr().flush();
+        })
+        .await?;
+    eprintln!();
+
+    if result.is_ok() {
+        println!("Applied {total} changes to {goal}.");
+        return Ok(());
+    }
+    for (change, err) in &result.failed {
+        let id = change.loaded().map_or("new datapoint", |dp| dp.id.as_str());
+        match err {
+            BeeminderError::Conflict(_) => eprintln!("  {id}: changed elsewhere, skipped"),
+            err => eprintln!("  {id}: {}", err.format_for_display()),
+        }
+    }
+    Err(anyhow!("{} of {total} changes failed", result.failed.len()))
+}
+
+fn print_preview(changes: &[Change]) {
+    if changes.is_empty() {
+        println!("No datapoints to change.");
+        return;
+    }
+    let deletes = changes
+        .iter()
+        .filter(|change| matches!(change, Change::Delete(_)))
+        .count();
+    println!("{} updates, {deletes} deletions:", changes.len() - deletes);
+    for change in changes.iter().take(PREVIEW_CHANGES) {
+        match change {
+            Change::Create(_) => {}
+            Change::Delete(dp) => println!("- {}", describe(dp)),
+            Change::Update { before, after } => {
+                println!("- {}", describe(before));
+                let value = after.value.unwrap_or(before.value);
+                let timestamp = after.timestamp.unwrap_or(before.timestamp);
+                let comment = after.comment.as_deref().or(before.comment.as_deref());
+                println!(
+                    "+ {}  {value:>10}  {}",
+                    format_time(timestamp),
+                    comment.unwrap_or("")
+                );
+            }
+        }
+    }
+    if changes.len() > PREVIEW_CHANGES {
+        println!("... and {} more", changes.len() - PREVIEW_CHANGES);
+    }
+}
+
+pub fn describe(dp: &Datapoint) -> String {
+    format!(
+        "{}  {:>10}  {}",
+        format_time(dp.timestamp),
+        dp.value,
+        dp.comment.as_deref().unwrap_or("")
+    )
+}
+
+fn format_time(timestamp: time::OffsetDateTime) -> String {
+    let offset = time::UtcOffset::current_local_offset().unwrap_or(time::UtcOffset::UTC);
+    timestamp
+        .to_offset(offset)
+        .format(TIMESTAMP_FORMAT)
+        .unwrap_or_else(|_| timestamp.unix_timestamp().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use beeminder::testing::datapoint;
+
+    #[test]
+    fn plans_only_changed_fields() {
+        let points = [
+            datapoint("a", 1000.0, "ran km"),
+            datapoint("b", 0.0, "rest"),
+        ];
+        let transform = Transform {
+            scale: Some(0.001),
+            replace: Some((Regex::new("km").unwrap(), "k".to_string())),
+            ..Transform::default()
+        };
+        let changeset = transform.plan(&points);
+        assert_eq!(changeset.changes.len(), 1);
+        let Change::Update { before, after } = &changeset.changes[0] else {
+            panic!("expected an update");
+        };
+        assert_eq!(before.id, "a");
+        assert_eq!(after.value, Some(1.0));
+        assert_eq!(after.comment.as_deref(), Some("ran k"));
+        assert_eq!(after.timestamp, None);
+    }
+
+    #[test]
+    fn deletes_matching_comments() {
+        let points = [
+            datapoint("a", 1.0, "test entry"),
+            datapoint("b", 2.0, "real"),
+        ];
+        let transform = Transform {
+            matching: Some(Regex::new("^test").unwrap()),
+            delete: true,
+            ..Transform::default()
+        };
+        let changeset = transform.plan(&points);
+        assert!(matches!(changeset.changes.as_slice(), [Change::Delete(dp)] if dp.id == "a"));
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("-2h").unwrap(), Duration::hours(-2));
+        assert_eq!(parse_duration("1:30").unwrap(), Duration::minutes(90));
+        assert!(parse_duration("soon").is_err());
+    }
+}
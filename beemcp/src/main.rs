@@ -2,6 +2,12 @@
 
 use anyhow::{Context, Result};
 use beeconfig::BeeConfig;
+use beeminder::changeset::{Change, Changeset, EditedDatapoint};
+use beeminder::day::DayClock;
+use beeminder::duplicates;
+use beeminder::entry::{self, EntryGoal, TotalEntry};
+use beeminder::search::{CommentSearch, SearchError, SearchHit};
+use beeminder::stats::{goal_stats, GoalStats, StatsOptions};
 use beeminder::types::{
     CreateAllResponse, CreateDatapoint, CreateGoal, Datapoint, GoalSummary, GoalType,
     UpdateDatapoint, UpdateGoal,
@@ -17,10 +23,12 @@ use rmcp::{
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use time::OffsetDateTime;
+use time::{Duration, OffsetDateTime, UtcOffset};
 
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 struct DatapointInput {
+    #[serde(default)]
+    id: Option<String>,
     value: f64,
     #[serde(default)]
     timestamp: Option<i64>,
@@ -44,6 +52,12 @@ struct BeeminderRequest {
     #[serde(default)]
     value: Option<f64>,
     #[serde(default)]
+    value_expr: Option<String>,
+    #[serde(default)]
+    total: Option<f64>,
+    #[serde(default)]
+    date: Option<String>,
+    #[serde(default)]
     comment: Option<String>,
     #[serde(default)]
     timestamp: Option<i64>,
@@ -56,6 +70,8 @@ struct BeeminderRequest {
     #[serde(default)]
     datapoints: Option<Vec<DatapointInput>>,
     #[serde(default)]
+    delete_ids: Option<Vec<String>>,
+    #[serde(default)]
     sort: Option<String>,
     #[serde(default)]
     count: Option<u64>,
@@ -90,9 +106,19 @@ struct BeeminderRequest {
     #[serde(default)]
     include_archived: Option<bool>,
     #[serde(default)]
+    tags: Option<Vec<String>>,
+    #[serde(default)]
     max_datapoints_per_goal: Option<u64>,
     #[serde(default)]
     max_goals: Option<u64>,
+    #[serde(default)]
+    window_minutes: Option<i64>,
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default)]
+    since: Option<String>,
+    #[serde(default)]
+    dry_run: Option<bool>,
 }
 
 #[derive(Clone)]
@@ -137,6 +163,14 @@ fn tool_json<T: Serialize>(value: &T) -> CallToolResult {
         .map_or_else(|_| tool_error("Failed to serialize response"), tool_text)
 }
 
+/// Keeps goals carrying any of `tags`; no filter when `tags` is absent or empty.
+fn filter_by_tags(mut goals: Vec<GoalSummary>, tags: Option<&[String]>) -> Vec<GoalSummary> {
+    if let Some(tags) = tags.filter(|tags| !tags.is_empty()) {
+        goals.retain(|goal| tags.iter().any(|tag| goal.has_tag(tag)));
+    }
+    goals
+}
+
 fn format_beeminder_error(err: &BeeminderError) -> String {
     err.format_for_display()
 }
@@ -145,7 +179,7 @@ fn format_beeminder_error(err: &BeeminderError) -> String {
 impl BeeminderService {
     #[tool(
         name = "beeminder",
-        description = "Unified Beeminder tool. Use action plus optional fields.\n\nActions: list, list-archived, add, edit, get-datapoints, update-datapoint, delete-datapoint, backup, goal-create, goal-update, goal-refresh, add-batch, shortcircuit, stepdown, cancel-stepdown.\n\nNotes: goal-create requires goal (slug), title, goal_type, gunits, and exactly two of goalval/rate/goaldate. goal-update accepts archived=true/false. add-batch accepts datapoints[] with value + optional timestamp/comment/daystamp/requestid."
+        description = "Unified Beeminder tool. Use action plus optional fields.\n\nActions: list, list-archived, add, edit, get-datapoints, update-datapoint, delete-datapoint, backup, goal-create, goal-update, goal-refresh, add-batch, shortcircuit, stepdown, cancel-stepdown, stats, find-duplicates, search-datapoints.\n\nNotes: goal-create requires goal (slug), title, goal_type, gunits, and exactly two of goalval/rate/goaldate. list and list-archived accept tags[] and return goals with any of them. stats returns streaks, weekly/monthly totals, rolling averages, trend and on-road percentage for goal, or for all goals (optionally filtered by tags[]). goal-update accepts archived=true/false. add accepts value_expr instead of value (arithmetic like 10+15 or durations like 1:30/90m on goals measured in hours, minutes or seconds; checked against integery goals), or total for cumulative/odometer goals (the running total reached; the change from the goal's current value is computed and returned alongside the datapoint) and date (YYYY-MM-DD, today, yesterday, ^, -N or a weekday, optionally with HH:MM, in the account timezone and goal deadline). add-batch accepts datapoints[] with value + optional timestamp/comment/daystamp/requestid. find-duplicates reports groups of datapoints in goal sharing a requestid, or the same value, daystamp and comment entered within window_minutes (default 10) of each other unless their requestids differ; it deletes nothing, use delete-datapoint on the extras. edit returns the recent datapoints (count, default 20); edit with datapoints[] and/or delete_ids[] changes those datapoints: entries with id update that datapoint (omitted timestamp/comment stay as they were), entries without id are created, and only ids listed in delete_ids are deleted; datapoints left out are kept. Changes to datapoints edited elsewhere in the meantime are skipped and listed under conflicts. It returns a summary and one line per change (times in UTC); pass dry_run=true to only preview. search-datapoints matches the regex query case-insensitively against datapoint comments of goal, or of all active and archived goals, newest first; since (YYYY-MM-DD, yesterday, -N or a weekday) limits the days scanned and count the hits returned. Use it to answer questions like \"when did I last...\"."
     )]
     async fn beeminder(
         &self,
@@ -156,41 +190,26 @@ impl BeeminderService {
 
         let result = match action.as_str() {
             "list" | "listgoals" => match client.get_goals().await {
-                Ok(goals) => tool_json(&goals),
+                Ok(goals) => tool_json(&filter_by_tags(goals, request.tags.as_deref())),
                 Err(err) => tool_error(format_beeminder_error(&err)),
             },
             "listarchived" | "listarchivedgoals" => match client.get_archived_goals().await {
-                Ok(goals) => tool_json(&goals),
+                Ok(goals) => tool_json(&filter_by_tags(goals, request.tags.as_deref())),
                 Err(err) => tool_error(format_beeminder_error(&err)),
             },
             "add" | "adddatapoint" => {
                 let Some(goal) = request.goal.as_deref() else {
                     return Ok(tool_error("Missing required field: goal"));
                 };
-                let Some(value) = request.value else {
-                    return Ok(tool_error("Missing required field: value"));
-                };
-                let timestamp = match parse_unix_timestamp(request.timestamp) {
-                    Ok(ts) => ts,
+                let (datapoint, total) = match entry_datapoint(client, goal, &request).await {
+                    Ok(entry) => entry,
                     Err(err) => return Ok(tool_error(err)),
                 };
-                let mut datapoint = CreateDatapoint::new(value);
-                if let Some(timestamp) = timestamp {
-                    datapoint = datapoint.with_timestamp(timestamp);
-                }
-                if let Some(daystamp) = request.daystamp.as_deref() {
-                    datapoint = datapoint.with_daystamp(daystamp);
-                }
-                if let Some(comment) = request.comment.as_deref() {
-                    datapoint = datapoint.with_comment(comment);
-                }
-                if let Some(requestid) = request.requestid.as_deref() {
-                    datapoint = datapoint.with_requestid(requestid);
-                }
 
-                match client.create_datapoint(goal, &datapoint).await {
-                    Ok(datapoint) => tool_json(&datapoint),
-                    Err(err) => tool_error(format_beeminder_error(&err)),
+                match (client.create_datapoint(goal, &datapoint).await, total) {
+                    (Ok(datapoint), Some(total)) => tool_json(&AddTotalResult { datapoint, total }),
+                    (Ok(datapoint), None) => tool_json(&datapoint),
+                    (Err(err), _) => tool_error(format_beeminder_error(&err)),
                 }
             }
             "getdatapoints" | "edit" | "editdatapoints" => {
@@ -203,6 +222,14 @@ impl BeeminderService {
                     .as_deref()
                     .or(if is_edit { Some("timestamp") } else { None });
                 let count = request.count.or(if is_edit { Some(20) } else { None });
+                if is_edit && (request.datapoints.is_some() || request.delete_ids.is_some()) {
+                    return Ok(
+                        match edit_datapoints(client, goal, sort, count, &request).await {
+                            Ok(result) => tool_json(&result),
+                            Err(err) => tool_error(err),
+                        },
+                    );
+                }
 
                 match client
                     .get_datapoints(goal, sort, count, request.page, request.per)
@@ -431,7 +458,27 @@ impl BeeminderService {
                 Ok(data) => tool_json(&data),
                 Err(err) => tool_error(err),
             },
-            _ => tool_error("Unknown action. Try: list, add, edit, goal-create, goal-update, goal-refresh, add-batch, shortcircuit, stepdown, cancel-stepdown, get-datapoints, update-datapoint, delete-datapoint, backup"),
+            "stats" | "goalstats" => match stats(client, &request).await {
+                Ok(stats) => tool_json(&stats),
+                Err(err) => tool_error(err),
+            },
+            "searchdatapoints" | "search" => match search(client, &request).await {
+                Ok(hits) => tool_json(&hits),
+                Err(err) => tool_error(err),
+            },
+            "findduplicates" | "duplicates" => {
+                let Some(goal) = request.goal.as_deref() else {
+                    return Ok(tool_error("Missing required field: goal"));
+                };
+                let window = request
+                    .window_minutes
+                    .map_or(duplicates::DEFAULT_WINDOW, Duration::minutes);
+                match client.get_datapoints(goal, None, None, None, None).await {
+                    Ok(datapoints) => tool_json(&duplicates::find_duplicates(&datapoints, window)),
+                    Err(err) => tool_error(format_beeminder_error(&err)),
+                }
+            }
+            _ => tool_error("Unknown action. Try: list, add, edit, goal-create, goal-update, goal-refresh, add-batch, shortcircuit, stepdown, cancel-stepdown, get-datapoints, update-datapoint, delete-datapoint, backup, stats, find-duplicates, search-datapoints"),
         };
 
         Ok(result)
@@ -535,6 +582,261 @@ async fn backup(
     })
 }
 
+#[derive(Serialize)]
+struct GoalStatsResult {
+    goal: String,
+    #[serde(flatten)]
+    stats: GoalStats,
+}
+
+#[derive(Serialize)]
+struct EditResult {
+    summary: String,
+    changes: Vec<String>,
+    unknown_ids: Vec<String>,
+    applied: bool,
+    /// Changes skipped because the datapoint was changed elsewhere
+    conflicts: Vec<String>,
+    errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct AddTotalResult {
+    datapoint: Datapoint,
+    total: TotalEntry,
+}
+
+/// Builds the datapoint for `add`. `value_expr` and `date` are read with the
+/// shared entry grammar against the goal's units and deadline; `total` is
+/// turned into the change from the goal's current value.
+async fn entry_datapoint(
+    client: &BeeminderClient,
+    goal: &str,
+    request: &BeeminderRequest,
+) -> Result<(CreateDatapoint, Option<TotalEntry>), String> {
+    let goal_full =
+        if request.value_expr.is_some() || request.date.is_some() || request.total.is_some() {
+            Some(
+                client
+                    .get_goal_full(goal, false)
+                    .await
+                    .map_err(|err| format_beeminder_error(&err))?,
+            )
+        } else {
+            None
+        };
+
+    let total = match (request.total, goal_full.as_ref()) {
+        (Some(total), Some(goal)) => {
+            Some(entry::total_entry(goal, total).map_err(|err| err.to_string())?)
+        }
+        _ => None,
+    };
+    let value = match (total, request.value_expr.as_deref(), goal_full.as_ref()) {
+        (Some(total), _, _) => total.value,
+        (None, Some(expr), Some(goal)) => {
+            entry::parse_value(expr, &EntryGoal::from(goal)).map_err(|err| err.to_string())?
+        }
+        _ => request
+            .value
+            .ok_or("Missing required field: value (or value_expr or total)")?,
+    };
+    let mut datapoint = CreateDatapoint::new(value);
+    if let Some(timestamp) = parse_unix_timestamp(request.timestamp)? {
+        datapoint = datapoint.with_timestamp(timestamp);
+    }
+    if let Some(daystamp) = request.daystamp.as_deref() {
+        datapoint = datapoint.with_daystamp(daystamp);
+    }
+    if let (Some(date), Some(goal)) = (request.date.as_deref(), goal_full.as_ref()) {
+        let user = client
+            .get_user()
+            .await
+            .map_err(|err| format_beeminder_error(&err))?;
+        let today = DayClock::for_user(&user.timezone)
+            .goal_day(goal.deadline.unwrap_or(0), OffsetDateTime::now_utc());
+        datapoint = entry::parse_date(date, &today)
+            .map_err(|err| err.to_string())?
+            .apply(datapoint);
+    }
+    if let Some(comment) = request.comment.as_deref() {
+        datapoint = datapoint.with_comment(comment);
+    }
+    if let Some(requestid) = request.requestid.as_deref() {
+        datapoint = datapoint.with_requestid(requestid);
+    }
+    Ok((datapoint, total))
+}
+
+/// The account's clock; falls back to the machine's offset for timezones
+/// the database doesn't know.
+async fn stats(
+    client: &BeeminderClient,
+    request: &BeeminderRequest,
+) -> Result<Vec<GoalStatsResult>, String> {
+    let slugs = match request.goal.as_deref() {
+        Some(goal) => vec![goal.to_string()],
+        None => {
+            let goals = client
+                .get_goals()
+                .await
+                .map_err(|err| format_beeminder_error(&err))?;
+            filter_by_tags(goals, request.tags.as_deref())
+                .into_iter()
+                .map(|goal| goal.slug)
+                .collect()
+        }
+    };
+
+    let user = client
+        .get_user()
+        .await
+        .map_err(|err| format_beeminder_error(&err))?;
+    let clock = DayClock::for_user(&user.timezone);
+    let now = OffsetDateTime::now_utc();
+    let mut results = Vec::new();
+    for slug in slugs {
+        let goal = client
+            .get_goal_full(&slug, true)
+            .await
+            .map_err(|err| format_beeminder_error(&err))?;
+        let today = clock.goal_day(goal.deadline.unwrap_or(0), now).today;
+        let options =
+            StatsOptions::for_goal(&goal, today).map_err(|err| format!("{slug}: {err}"))?;
+        let datapoints = goal.datapoints.as_deref().unwrap_or_default();
+        results.push(GoalStatsResult {
+            goal: slug,
+            stats: goal_stats(datapoints, &options),
+        });
+    }
+    Ok(results)
+}
+
+/// Applies `edit` with `datapoints[]` and `delete_ids[]` to the datapoints
+/// fetched with the same sort and count. Rows with an `id` update that
+/// point, missing fields keeping their values; rows without one are
+/// created. Only the points in `delete_ids` are deleted, so points the
+/// caller never saw are left alone.
+async fn edit_datapoints(
+    client: &BeeminderClient,
+    goal: &str,
+    sort: Option<&str>,
+    count: Option<u64>,
+    request: &BeeminderRequest,
+) -> Result<EditResult, String> {
+    let edited = request.datapoints.as_deref().unwrap_or_default();
+    let delete_ids = request.delete_ids.as_deref().unwrap_or_default();
+    if let Some(id) = edited
+        .iter()
+        .filter_map(|input| input.id.as_deref())
+        .find(|id| delete_ids.iter().any(|delete| delete == id))
+    {
+        return Err(format!("Datapoint {id} is both edited and in delete_ids"));
+    }
+    let original = client
+        .get_datapoints(goal, sort, count, None, None)
+        .await
+        .map_err(|err| format_beeminder_error(&err))?;
+    let now = OffsetDateTime::now_utc();
+    let mut rows = Vec::with_capacity(original.len() + edited.len());
+    for input in edited {
+        let before = input
+            .id
+            .as_deref()
+            .and_then(|id| original.iter().find(|dp| dp.id == id));
+        let timestamp = parse_unix_timestamp(input.timestamp)?;
+        rows.push(EditedDatapoint {
+            id: input.id.clone(),
+            timestamp: timestamp.or(before.map(|dp| dp.timestamp)).unwrap_or(now),
+            value: input.value,
+            comment: input
+                .comment
+                .clone()
+                .or_else(|| before.and_then(|dp| dp.comment.clone()))
+                .unwrap_or_default(),
+        });
+    }
+    // Points neither edited nor deleted stay as they are
+    rows.extend(
+        original
+            .iter()
+            .filter(|dp| {
+                !delete_ids.contains(&dp.id)
+                    && !edited.iter().any(|input| input.id.as_ref() == Some(&dp.id))
+            })
+            .map(EditedDatapoint::from),
+    );
+
+    let changeset = Changeset::diff(&original, &rows);
+    let mut unknown_ids = changeset.unknown_ids.clone();
+    unknown_ids.extend(
+        delete_ids
+            .iter()
+            .filter(|id| !original.iter().any(|dp| &dp.id == *id))
+            .cloned(),
+    );
+    let mut result = EditResult {
+        summary: changeset.summary(),
+        changes: changeset.preview(UtcOffset::UTC),
+        unknown_ids,
+        applied: false,
+        conflicts: Vec::new(),
+        errors: Vec::new(),
+    };
+    if request.dry_run.unwrap_or(false) || changeset.is_empty() {
+        return Ok(result);
+    }
+    let applied = changeset
+        .apply_checked(client, goal)
+        .await
+        .map_err(|err| format_beeminder_error(&err))?;
+    result.applied = true;
+    for (change, err) in &applied.failed {
+        match err {
+            BeeminderError::Conflict(conflict) => result.conflicts.push(format!(
+                "{} was {} elsewhere; {} skipped",
+                conflict.before.id,
+                if conflict.current.is_some() {
+                    "changed"
+                } else {
+                    "deleted"
+                },
+                match change {
+                    Change::Create(_) => "create",
+                    Change::Update { .. } => "update",
+                    Change::Delete(_) => "delete",
+                }
+            )),
+            err => result.errors.push(format_beeminder_error(err)),
+        }
+    }
+    Ok(result)
+}
+
+async fn search(
+    client: &BeeminderClient,
+    request: &BeeminderRequest,
+) -> Result<Vec<SearchHit>, String> {
+    let query = request
+        .query
+        .as_deref()
+        .ok_or("Missing required field: query")?;
+    let mut hits = CommentSearch::new(query, true)
+        .map_err(|err| err.to_string())?
+        .goal(request.goal.as_deref())
+        .since(request.since.as_deref())
+        .run(client)
+        .await
+        .map_err(|err| match err {
+            SearchError::Api(err) => format_beeminder_error(&err),
+            other => other.to_string(),
+        })?;
+    if let Some(count) = request.count {
+        hits.truncate(usize::try_from(count).unwrap_or(usize::MAX));
+    }
+    Ok(hits)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let config = BeeConfig::load_or_onboard().with_context(|| "Failed to load beeminder config")?;
@@ -0,0 +1,626 @@
+//! Datapoint changesets: the creates, updates and deletes that turn a goal's
+//! datapoints into an edited list of them.
+//!
+//! Every editor works the same way: load some datapoints, let the user
+//! change, add and remove rows, then diff the rows against what was loaded
+//! and apply the difference. The applied changes can be inverted to undo a
+//! save, and the loaded `updated_at` stamps reveal points that were changed
+//! elsewhere in the meantime; such conflicts are settled with a
+//! `Resolution` and the resolved changes applied again.
+
+use crate::types::{CreateDatapoint, Datapoint, UpdateDatapoint};
+use crate::{BeeminderClient, Error};
+use futures::future::join_all;
+use std::collections::{HashMap, HashSet};
+use time::{OffsetDateTime, UtcOffset};
+
+/// A datapoint as an editor leaves it; `id` is `None` for new points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditedDatapoint {
+    pub id: Option<String>,
+    pub timestamp: OffsetDateTime,
+    pub value: f64,
+    pub comment: String,
+}
+
+impl From<&Datapoint> for EditedDatapoint {
+    fn from(dp: &Datapoint) -> Self {
+        Self {
+            id: Some(dp.id.clone()),
+            timestamp: dp.timestamp,
+            value: dp.value,
+            comment: dp.comment.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// One change to a goal's datapoints.
+#[derive(Debug, Clone)]
+pub enum Change {
+    Create(CreateDatapoint),
+    /// `after` holds only the fields that change
+    Update {
+        before: Datapoint,
+        after: UpdateDatapoint,
+    },
+    Delete(Datapoint),
+}
+
+/// The changes between two lists of datapoints, in the order they are
+/// applied: creates, then updates, then deletes.
+#[derive(Debug, Clone, Default)]
+pub struct Changeset {
+    pub changes: Vec<Change>,
+    /// Edited IDs that were not among the original datapoints
+    pub unknown_ids: Vec<String>,
+}
+
+/// A datapoint that changed since it was loaded.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    /// The datapoint as it was loaded
+    pub before: Datapoint,
+    /// The datapoint as it is now, `None` once deleted
+    pub current: Option<Datapoint>,
+}
+
+/// How to settle a change to a datapoint that was changed elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Apply only the fields this side changed on top of the other version
+    Merge,
+    /// Write this side's version over the other one
+    Mine,
+    /// Drop this side's change
+    Theirs,
+}
+
+impl Change {
+    /// The loaded datapoint an update or delete is based on.
+    #[must_use]
+    pub const fn loaded(&self) -> Option<&Datapoint> {
+        match self {
+            Self::Update { before, .. } | Self::Delete(before) => Some(before),
+            Self::Create(_) => None,
+        }
+    }
+
+    /// The datapoint as this change leaves it; `None` for deletes.
+    #[must_use]
+    pub fn proposed(&self) -> Option<EditedDatapoint> {
+        match self {
+            Self::Create(create) => Some(EditedDatapoint {
+                id: None,
+                timestamp: create.timestamp.unwrap_or_else(OffsetDateTime::now_utc),
+                value: create.value,
+                comment: create.comment.clone().unwrap_or_default(),
+            }),
+            Self::Update { before, after } => Some(EditedDatapoint {
+                id: Some(before.id.clone()),
+                timestamp: after.timestamp.unwrap_or(before.timestamp),
+                value: after.value.unwrap_or(before.value),
+                comment: after
+                    .comment
+                    .clone()
+                    .or_else(|| before.comment.clone())
+                    .unwrap_or_default(),
+            }),
+            Self::Delete(_) => None,
+        }
+    }
+
+    /// Rebases the change onto `current`, the datapoint as it is now (`None`
+    /// once deleted elsewhere). Returns `None` when nothing is left to write.
+    #[must_use]
+    pub fn resolve(&self, current: Option<&Datapoint>, resolution: Resolution) -> Option<Self> {
+        if resolution == Resolution::Theirs {
+            return None;
+        }
+        let Self::Update { after, .. } = self else {
+            return match (self, current) {
+                (Self::Delete(_), Some(current)) => Some(Self::Delete(current.clone())),
+                (Self::Delete(_), None) => None,
+                _ => Some(self.clone()),
+            };
+        };
+        let mine = self.proposed()?;
+        let Some(current) = current else {
+            // Recreate the point with this side's values.
+            let mut create = CreateDatapoint::new(mine.value).with_timestamp(mine.timestamp);
+            if !mine.comment.is_empty() {
+                create = create.with_comment(&mine.comment);
+            }
+            return Some(Self::Create(create));
+        };
+        let update = if resolution == Resolution::Merge {
+            let mut merged = UpdateDatapoint::new(current.id.clone());
+            merged.timestamp = after.timestamp.filter(|at| *at != current.timestamp);
+            merged.value = after
+                .value
+                .filter(|value| (value - current.value).abs() > f64::EPSILON);
+            merged.comment = after
+                .comment
+                .clone()
+                .filter(|comment| comment != current.comment.as_deref().unwrap_or(""));
+            merged
+        } else {
+            update_for(current, &mine)
+        };
+        let changed =
+            update.timestamp.is_some() || update.value.is_some() || update.comment.is_some();
+        changed.then(|| Self::Update {
+            before: current.clone(),
+            after: update,
+        })
+    }
+}
+
+/// A change that went through.
+#[derive(Debug, Clone)]
+pub enum Applied {
+    Created(Datapoint),
+    Updated { before: Datapoint, after: Datapoint },
+    Deleted(Datapoint),
+}
+
+/// Outcome of `Changeset::apply`.
+#[derive(Debug, Default)]
+pub struct ApplyResult {
+    pub applied: Vec<Applied>,
+    pub failed: Vec<(Change, Error)>,
+}
+
+impl Changeset {
+    /// Diffs the `edited` rows against the `original` datapoints. Original
+    /// points missing from `edited` are deleted; rows without an ID are
+    /// created.
+    #[must_use]
+    pub fn diff(original: &[Datapoint], edited: &[EditedDatapoint]) -> Self {
+        let by_id: HashMap<&str, &Datapoint> =
+            original.iter().map(|dp| (dp.id.as_str(), dp)).collect();
+        let mut kept = HashSet::new();
+        let mut creates = Vec::new();
+        let mut updates = Vec::new();
+        let mut unknown_ids = Vec::new();
+
+        for row in edited {
+            let Some(id) = row.id.as_deref() else {
+                let mut create = CreateDatapoint::new(row.value).with_timestamp(row.timestamp);
+                if !row.comment.trim().is_empty() {
+                    create = create.with_comment(&row.comment);
+                }
+                creates.push(Change::Create(create));
+                continue;
+            };
+            let Some(before) = by_id.get(id) else {
+                unknown_ids.push(id.to_string());
+                continue;
+            };
+            if !kept.insert(id) {
+                continue;
+            }
+            let after = update_for(before, row);
+            if after.value.is_some() || after.timestamp.is_some() || after.comment.is_some() {
+                updates.push(Change::Update {
+                    before: (*before).clone(),
+                    after,
+                });
+            }
+        }
+
+        let deletes = original
+            .iter()
+            .filter(|dp| !kept.contains(dp.id.as_str()))
+            .map(|dp| Change::Delete(dp.clone()));
+
+        let mut changes = creates;
+        changes.extend(updates);
+        changes.extend(deletes);
+        Self {
+            changes,
+            unknown_ids,
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Counts of created, updated and deleted datapoints.
+    #[must_use]
+    pub fn counts(&self) -> (usize, usize, usize) {
+        self.changes
+            .iter()
+            .fold((0, 0, 0), |(c, u, d), change| match change {
+                Change::Create(_) => (c + 1, u, d),
+                Change::Update { .. } => (c, u + 1, d),
+                Change::Delete(_) => (c, u, d + 1),
+            })
+    }
+
+    /// One-line summary such as "1 new, 2 updated, 0 deleted".
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let (created, updated, deleted) = self.counts();
+        format!("{created} new, {updated} updated, {deleted} deleted")
+    }
+
+    /// One line per change, with times shown at `offset`.
+    #[must_use]
+    pub fn preview(&self, offset: UtcOffset) -> Vec<String> {
+        self.changes
+            .iter()
+            .map(|change| match change {
+                Change::Create(create) => format!(
+                    "+ {}  {}  {}",
+                    create
+                        .timestamp
+                        .map_or_else(|| "now".to_string(), |at| format_time(at, offset)),
+                    create.value,
+                    create.comment.as_deref().unwrap_or("")
+                ),
+                Change::Update { before, after } => {
+                    let mut fields = Vec::new();
+                    if let Some(at) = after.timestamp {
+                        fields.push(format!(
+                            "time {} -> {}",
+                            format_time(before.timestamp, offset),
+                            format_time(at, offset)
+                        ));
+                    }
+                    if let Some(value) = after.value {
+                        fields.push(format!("value {} -> {value}", before.value));
+                    }
+                    if let Some(comment) = &after.comment {
+                        fields.push(format!(
+                            "comment \"{}\" -> \"{comment}\"",
+                            before.comment.as_deref().unwrap_or("")
+                        ));
+                    }
+                    format!("~ {}  {}", before.id, fields.join(", "))
+                }
+                Change::Delete(dp) => format!(
+                    "- {}  {}  {}  {}",
+                    format_time(dp.timestamp, offset),
+                    dp.value,
+                    dp.comment.as_deref().unwrap_or(""),
+                    dp.id
+                ),
+            })
+            .collect()
+    }
+
+    /// Applies the changes in order. A failed change doesn't stop the
+    /// others; it is returned alongside what went through.
+    pub async fn apply(&self, client: &BeeminderClient, goal: &str) -> ApplyResult {
+        self.apply_with(client, goal, None, 1, |_| {}).await
+    }
+
+    /// Like `apply`, but first fetches the datapoints updated since the
+    /// oldest loaded one. Updates and deletes of points changed or deleted
+    /// in the meantime fail with `Error::Conflict` instead of writing.
+    ///
+    /// # Errors
+    /// Returns an error, without applying anything, if that fetch fails.
+    pub async fn apply_checked(
+        &self,
+        client: &BeeminderClient,
+        goal: &str,
+    ) -> Result<ApplyResult, Error> {
+        self.apply_checked_batched(client, goal, 1, |_| {}).await
+    }
+
+    /// Like `apply_checked`, but sends up to `batch_size` requests at once
+    /// and calls `progress` with the number of changes done after each
+    /// batch.
+    ///
+    /// # Errors
+    /// Returns an error, without applying anything, if the fetch of
+    /// recently updated datapoints fails.
+    pub async fn apply_checked_batched(
+        &self,
+        client: &BeeminderClient,
+        goal: &str,
+        batch_size: usize,
+        progress: impl FnMut(usize),
+    ) -> Result<ApplyResult, Error> {
+        let oldest = self
+            .changes
+            .iter()
+            .filter_map(Change::loaded)
+            .map(|dp| dp.updated_at)
+            .min();
+        let recent = match oldest {
+            Some(since) => client.get_datapoints_updated_since(goal, since).await?,
+            None => Vec::new(),
+        };
+        Ok(self
+            .apply_with(client, goal, Some(&recent), batch_size, progress)
+            .await)
+    }
+
+    async fn apply_with(
+        &self,
+        client: &BeeminderClient,
+        goal: &str,
+        recent: Option<&[Datapoint]>,
+        batch_size: usize,
+        mut progress: impl FnMut(usize),
+    ) -> ApplyResult {
+        let mut result = ApplyResult::default();
+        let mut done = 0;
+        for batch in self.changes.chunks(batch_size.max(1)) {
+            let outcomes = join_all(
+                batch
+                    .iter()
+                    .map(|change| apply_change(client, goal, change, recent)),
+            )
+            .await;
+            for (change, outcome) in batch.iter().zip(outcomes) {
+                match outcome {
+                    Ok(applied) => result.applied.push(applied),
+                    Err(err) => result.failed.push((change.clone(), err)),
+                }
+            }
+            done += batch.len();
+            progress(done);
+        }
+        result
+    }
+}
+
+/// Applies one change, first checking it against `recent` when given.
+async fn apply_change(
+    client: &BeeminderClient,
+    goal: &str,
+    change: &Change,
+    recent: Option<&[Datapoint]>,
+) -> Result<Applied, Error> {
+    if let (Some(loaded), Some(recent)) = (change.loaded(), recent) {
+        check_unchanged(loaded, recent)?;
+    }
+    match change {
+        Change::Create(create) => client
+            .create_datapoint(goal, create)
+            .await
+            .map(Applied::Created),
+        Change::Update { before, after } => {
+            client
+                .update_datapoint(goal, after)
+                .await
+                .map(|after| Applied::Updated {
+                    before: before.clone(),
+                    after,
+                })
+        }
+        Change::Delete(dp) => client
+            .delete_datapoint(goal, &dp.id)
+            .await
+            .map(Applied::Deleted),
+    }
+}
+
+impl ApplyResult {
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// The failed changes whose datapoint was changed elsewhere.
+    #[must_use]
+    pub fn conflicts(&self) -> Vec<(&Change, &Conflict)> {
+        self.failed
+            .iter()
+            .filter_map(|(change, err)| match err {
+                Error::Conflict(conflict) => Some((change, conflict.as_ref())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The changeset reverting what was applied: created points are
+    /// deleted, deleted ones recreated and updated ones restored.
+    #[must_use]
+    pub fn inverse(&self) -> Changeset {
+        let mut creates = Vec::new();
+        let mut updates = Vec::new();
+        let mut deletes = Vec::new();
+        for applied in &self.applied {
+            match applied {
+                Applied::Created(dp) => deletes.push(Change::Delete(dp.clone())),
+                Applied::Updated { before, after } => {
+                    let mut restore = UpdateDatapoint::new(before.id.clone())
+                        .with_timestamp(before.timestamp)
+                        .with_value(before.value);
+                    restore.comment = Some(before.comment.clone().unwrap_or_default());
+                    updates.push(Change::Update {
+                        before: after.clone(),
+                        after: restore,
+                    });
+                }
+                Applied::Deleted(dp) => {
+                    let mut create = CreateDatapoint::new(dp.value).with_timestamp(dp.timestamp);
+                    if let Some(comment) = dp.comment.as_deref().filter(|c| !c.is_empty()) {
+                        create = create.with_comment(comment);
+                    }
+                    creates.push(Change::Create(create));
+                }
+            }
+        }
+        let mut changes = creates;
+        changes.extend(updates);
+        changes.extend(deletes);
+        Changeset {
+            changes,
+            unknown_ids: Vec::new(),
+        }
+    }
+}
+
+/// Fails with `Error::Conflict` unless `recent`, the datapoints updated at
+/// or after `loaded.updated_at`, holds `loaded` unchanged. Every surviving
+/// point is in `recent`, so a missing one was deleted in the meantime.
+pub(crate) fn check_unchanged(loaded: &Datapoint, recent: &[Datapoint]) -> Result<(), Error> {
+    let current = recent.iter().find(|dp| dp.id == loaded.id);
+    if current.is_some_and(|dp| dp.updated_at == loaded.updated_at) {
+        return Ok(());
+    }
+    Err(Error::Conflict(Box::new(Conflict {
+        before: loaded.clone(),
+        current: current.cloned(),
+    })))
+}
+
+/// The update turning `before` into `row`, holding only the fields that
+/// change.
+fn update_for(before: &Datapoint, row: &EditedDatapoint) -> UpdateDatapoint {
+    let mut update = UpdateDatapoint::new(before.id.clone());
+    if row.timestamp != before.timestamp {
+        update.timestamp = Some(row.timestamp);
+    }
+    if (row.value - before.value).abs() > f64::EPSILON {
+        update.value = Some(row.value);
+    }
+    if row.comment != before.comment.as_deref().unwrap_or("") {
+        update.comment = Some(row.comment.clone());
+    }
+    update
+}
+
+/// Formats a time as "YYYY-MM-DD HH:MM:SS" at `offset`.
+fn format_time(at: OffsetDateTime, offset: UtcOffset) -> String {
+    let at = at.to_offset(offset);
+    format!(
+        "{} {:02}:{:02}:{:02}",
+        at.date(),
+        at.hour(),
+        at.minute(),
+        at.second()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::datapoint;
+
+    #[test]
+    fn diffs_creates_updates_and_deletes() {
+        let original = [
+            datapoint("a", 1.0, "keep"),
+            datapoint("b", 2.0, "old"),
+            datapoint("c", 3.0, "gone"),
+        ];
+        let mut b = EditedDatapoint::from(&original[1]);
+        b.comment = "new".to_string();
+        let mut added = EditedDatapoint::from(&original[0]);
+        added.id = None;
+        added.value = 4.0;
+        let mut stale = EditedDatapoint::from(&original[0]);
+        stale.id = Some("zzz".to_string());
+        let edited = [EditedDatapoint::from(&original[0]), b, added, stale];
+
+        let changeset = Changeset::diff(&original, &edited);
+        assert_eq!(changeset.counts(), (1, 1, 1));
+        assert_eq!(changeset.unknown_ids, ["zzz"]);
+        assert!(matches!(&changeset.changes[0], Change::Create(dp) if dp.value == 4.0));
+        let Change::Update { before, after } = &changeset.changes[1] else {
+            panic!("expected an update");
+        };
+        assert_eq!(before.id, "b");
+        assert_eq!(after.comment.as_deref(), Some("new"));
+        assert_eq!(after.value, None);
+        assert!(matches!(&changeset.changes[2], Change::Delete(dp) if dp.id == "c"));
+
+        let preview = changeset.preview(UtcOffset::UTC);
+        assert_eq!(preview[0], "+ 2026-07-01 02:00:00  4  keep");
+        assert_eq!(preview[1], "~ b  comment \"old\" -> \"new\"");
+        assert_eq!(preview[2], "- 2026-07-01 02:00:00  3  gone  c");
+    }
+
+    #[test]
+    fn unchanged_rows_make_an_empty_changeset() {
+        let original = [datapoint("a", 1.0, "")];
+        let edited: Vec<EditedDatapoint> = original.iter().map(EditedDatapoint::from).collect();
+        assert!(Changeset::diff(&original, &edited).is_empty());
+    }
+
+    #[test]
+    fn inverts_applied_changes() {
+        let result = ApplyResult {
+            applied: vec![
+                Applied::Created(datapoint("new", 1.0, "")),
+                Applied::Updated {
+                    before: datapoint("b", 2.0, "old"),
+                    after: datapoint("b", 5.0, ""),
+                },
+                Applied::Deleted(datapoint("c", 3.0, "gone")),
+            ],
+            failed: Vec::new(),
+        };
+        let inverse = result.inverse();
+        assert_eq!(inverse.counts(), (1, 1, 1));
+        assert!(
+            matches!(&inverse.changes[0], Change::Create(dp) if dp.comment.as_deref() == Some("gone"))
+        );
+        let Change::Update { after, .. } = &inverse.changes[1] else {
+            panic!("expected an update");
+        };
+        assert_eq!(after.value, Some(2.0));
+        assert_eq!(after.comment.as_deref(), Some("old"));
+        assert!(matches!(&inverse.changes[2], Change::Delete(dp) if dp.id == "new"));
+    }
+
+    #[test]
+    fn detects_points_changed_or_deleted_elsewhere() {
+        let (a, b, c) = (
+            datapoint("a", 1.0, ""),
+            datapoint("b", 2.0, ""),
+            datapoint("c", 3.0, ""),
+        );
+        let mut edited_elsewhere = datapoint("a", 1.5, "");
+        edited_elsewhere.updated_at += time::Duration::minutes(1);
+        let recent = [edited_elsewhere, c.clone()];
+
+        let conflict = |dp| match check_unchanged(dp, &recent) {
+            Err(Error::Conflict(conflict)) => Some(conflict.current),
+            _ => None,
+        };
+        assert_eq!(conflict(&a).flatten().map(|dp| dp.value), Some(1.5));
+        assert!(matches!(conflict(&b), Some(None)));
+        assert!(check_unchanged(&c, &recent).is_ok());
+    }
+
+    #[test]
+    fn resolves_conflicting_updates() {
+        let before = datapoint("a", 1.0, "run");
+        let change = Change::Update {
+            after: UpdateDatapoint::new("a".to_string()).with_value(2.0),
+            before,
+        };
+        let mut current = datapoint("a", 1.0, "long run");
+        current.updated_at += time::Duration::minutes(1);
+
+        let Some(Change::Update { before, after }) =
+            change.resolve(Some(&current), Resolution::Merge)
+        else {
+            panic!("expected an update");
+        };
+        assert_eq!(before.comment.as_deref(), Some("long run"));
+        assert_eq!((after.value, after.comment), (Some(2.0), None));
+
+        let Some(Change::Update { after, .. }) = change.resolve(Some(&current), Resolution::Mine)
+        else {
+            panic!("expected an update");
+        };
+        assert_eq!(after.value, Some(2.0));
+        assert_eq!(after.comment.as_deref(), Some("run"));
+
+        assert!(change.resolve(Some(&current), Resolution::Theirs).is_none());
+        assert!(matches!(
+            change.resolve(None, Resolution::Mine),
+            Some(Change::Create(dp)) if dp.value == 2.0
+        ));
+    }
+}
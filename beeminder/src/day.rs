@@ -0,0 +1,220 @@
+//! Beeminder days: which calendar day an instant belongs to for a goal.
+//!
+//! Beeminder counts days in the account's timezone, and a goal's day ends at
+//! its `deadline`, an offset in seconds from midnight. With a 3am deadline an
+//! entry at 1am still counts for the previous day; with an 11pm deadline
+//! (`-3600`) an entry at 11:30pm already counts for the next one.
+
+use time::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+use time_tz::{timezones, Offset, TimeZone, Tz};
+
+/// The account's timezone, used to place instants on Beeminder days.
+#[derive(Debug, Clone, Copy)]
+pub enum DayClock {
+    /// An IANA timezone such as `America/Los_Angeles`
+    Zone(&'static Tz),
+    /// A fixed offset, e.g. the machine's while the account's is unknown
+    Fixed(UtcOffset),
+}
+
+impl DayClock {
+    /// Looks up an IANA timezone name as found in `UserInfo::timezone`.
+    #[must_use]
+    pub fn new(timezone: &str) -> Option<Self> {
+        timezones::get_by_name(timezone).map(Self::Zone)
+    }
+
+    /// The machine's timezone, for when the account's is not known yet.
+    /// Falls back to the current UTC offset if the system zone can't be
+    /// determined.
+    #[must_use]
+    pub fn local() -> Self {
+        time_tz::system::get_timezone().map_or_else(
+            |_| Self::Fixed(UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC)),
+            Self::Zone,
+        )
+    }
+
+    /// The clock for an account's `UserInfo::timezone`, falling back to
+    /// [`DayClock::local`] for names the timezone database does not know.
+    #[must_use]
+    pub fn for_user(timezone: &str) -> Self {
+        Self::new(timezone).unwrap_or_else(Self::local)
+    }
+
+    /// UTC offset of the timezone at `at`.
+    #[must_use]
+    pub fn offset_at(&self, at: OffsetDateTime) -> UtcOffset {
+        match self {
+            Self::Zone(tz) => tz.get_offset_utc(&at).to_utc(),
+            Self::Fixed(offset) => *offset,
+        }
+    }
+
+    /// The Beeminder day `at` falls on for a goal with the given deadline.
+    #[must_use]
+    pub fn day_of(&self, at: OffsetDateTime, deadline: i64) -> Date {
+        let local = at.to_offset(self.offset_at(at));
+        (local - Duration::seconds(deadline)).date()
+    }
+
+    /// The instant a wall-clock `time` on `date` names in this timezone.
+    #[must_use]
+    pub fn local_time(&self, date: Date, time: Time) -> OffsetDateTime {
+        let local = PrimitiveDateTime::new(date, time);
+        let guess = local.assume_utc();
+        let offset = self
+            .offset_at(guess - Duration::seconds(i64::from(self.offset_at(guess).whole_seconds())));
+        local.assume_offset(offset)
+    }
+
+    /// The current day of a goal.
+    #[must_use]
+    pub fn goal_day(&self, deadline: i64, now: OffsetDateTime) -> GoalDay {
+        GoalDay {
+            clock: *self,
+            deadline,
+            today: self.day_of(now, deadline),
+        }
+    }
+}
+
+impl Default for DayClock {
+    fn default() -> Self {
+        Self::Fixed(UtcOffset::UTC)
+    }
+}
+
+/// "Today" for one goal.
+#[derive(Debug, Clone, Copy)]
+pub struct GoalDay {
+    clock: DayClock,
+    deadline: i64,
+    pub today: Date,
+}
+
+impl GoalDay {
+    /// Whether `at` falls on today, e.g. a goal's `lastday`.
+    #[must_use]
+    pub fn contains(&self, at: OffsetDateTime) -> bool {
+        self.clock.day_of(at, self.deadline) == self.today
+    }
+
+    /// The account clock this day was computed with.
+    #[must_use]
+    pub const fn clock(&self) -> DayClock {
+        self.clock
+    }
+
+    /// Today as a `YYYYMMDD` daystamp.
+    #[must_use]
+    pub fn daystamp(&self) -> String {
+        daystamp(self.today)
+    }
+
+    /// Whether reminders have started: the derailment at `losedate` is at
+    /// most `leadtime` days away.
+    #[must_use]
+    pub fn reminders_due(&self, losedate: OffsetDateTime, leadtime: i64) -> bool {
+        let derail_day = self.clock.day_of(losedate, self.deadline);
+        (derail_day - self.today).whole_days() <= leadtime
+    }
+}
+
+/// Formats a date as a `YYYYMMDD` daystamp.
+#[must_use]
+pub fn daystamp(day: Date) -> String {
+    format!(
+        "{:04}{:02}{:02}",
+        day.year(),
+        u8::from(day.month()),
+        day.day()
+    )
+}
+
+/// Parses a `YYYYMMDD` daystamp.
+#[must_use]
+pub fn parse_daystamp(daystamp: &str) -> Option<Date> {
+    if daystamp.len() != 8 || !daystamp.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let year = daystamp[..4].parse().ok()?;
+    let month = Month::try_from(daystamp[4..6].parse::<u8>().ok()?).ok()?;
+    let day = daystamp[6..].parse().ok()?;
+    Date::from_calendar_date(year, month, day).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn la() -> DayClock {
+        DayClock::new("America/Los_Angeles").unwrap()
+    }
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    /// An instant on March 2026, given in UTC.
+    fn march_utc(day: u8, hour: u8, minute: u8) -> OffsetDateTime {
+        date(2026, Month::March, day)
+            .with_hms(hour, minute, 0)
+            .unwrap()
+            .assume_utc()
+    }
+
+    #[test]
+    fn unknown_timezones_are_rejected() {
+        assert!(DayClock::new("Mars/Olympus_Mons").is_none());
+        let at = march_utc(10, 6, 30);
+        assert_eq!(
+            DayClock::for_user("Mars/Olympus_Mons").offset_at(at),
+            DayClock::local().offset_at(at)
+        );
+    }
+
+    #[test]
+    fn uses_the_account_timezone() {
+        // 23:30 PDT on March 9, already March 10 in UTC.
+        let at = march_utc(10, 6, 30);
+        assert_eq!(la().day_of(at, 0), date(2026, Month::March, 9));
+        assert_eq!(
+            DayClock::default().day_of(at, 0),
+            date(2026, Month::March, 10)
+        );
+    }
+
+    #[test]
+    fn applies_late_and_early_deadlines() {
+        // 02:30 PDT: before a 3am deadline the day is still March 9.
+        let night = march_utc(10, 9, 30);
+        assert_eq!(la().day_of(night, 3 * 3600), date(2026, Month::March, 9));
+        assert_eq!(la().day_of(night, 0), date(2026, Month::March, 10));
+
+        // 23:30 PDT: past an 11pm deadline the next day has begun.
+        let evening = march_utc(10, 6, 30);
+        assert_eq!(la().day_of(evening, -3600), date(2026, Month::March, 10));
+    }
+
+    #[test]
+    fn goal_day_matches_entries_and_reminders() {
+        let day = la().goal_day(3 * 3600, march_utc(10, 9, 30));
+        assert_eq!(day.daystamp(), "20260309");
+        assert!(day.contains(march_utc(9, 20, 0)));
+        assert!(!day.contains(march_utc(10, 12, 0)));
+
+        // 02:59 PDT on March 12 is still March 11 for this goal.
+        let losedate = march_utc(12, 9, 59);
+        assert!(day.reminders_due(losedate, 2));
+        assert!(!day.reminders_due(losedate, 1));
+    }
+
+    #[test]
+    fn round_trips_daystamps() {
+        let day = parse_daystamp("20260301").unwrap();
+        assert_eq!(daystamp(day), "20260301");
+        assert!(parse_daystamp("2026-03-01").is_none());
+        assert!(parse_daystamp("20260230").is_none());
+    }
+}
@@ -0,0 +1,206 @@
+//! Duplicate datapoint detection.
+//!
+//! Double submits leave points with the same `requestid`, or with the same
+//! value, daystamp and comment entered moments apart. The earliest point of
+//! each group is kept; the rest are reported as extras.
+
+use crate::types::Datapoint;
+use serde::Serialize;
+use std::collections::HashMap;
+use time::Duration;
+
+/// Default time between two entries for them to count as a double submit.
+pub const DEFAULT_WINDOW: Duration = Duration::minutes(10);
+
+/// Why the points of a group are considered duplicates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    /// Same `requestid`, at any distance
+    RequestId,
+    /// Same value, daystamp and comment within the window, e.g. a retried
+    /// batch that re-sent a point without its `requestid`
+    SameEntry,
+}
+
+/// A datapoint and the later points duplicating it.
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup<'a> {
+    pub reason: DuplicateReason,
+    pub keep: &'a Datapoint,
+    pub extras: Vec<&'a Datapoint>,
+}
+
+/// Groups duplicate datapoints, oldest first. Points with the same
+/// `requestid` match at any distance. Otherwise points match on value,
+/// daystamp and comment when entered within `window` of the point they
+/// duplicate, unless they carry different request ids; a resend without a
+/// request id thus still matches the original.
+#[must_use]
+pub fn find_duplicates(datapoints: &[Datapoint], window: Duration) -> Vec<DuplicateGroup<'_>> {
+    let mut sorted: Vec<&Datapoint> = datapoints.iter().collect();
+    sorted.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.id.cmp(&b.id)));
+
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    // The request id of each group in `groups`, if any of its points has one.
+    let mut group_requests: Vec<Option<&str>> = Vec::new();
+    // Index into `groups` of each request id, and of the latest group of
+    // each entry; an entry outside the window starts a new group.
+    let mut by_request: HashMap<&str, usize> = HashMap::new();
+    let mut by_entry: HashMap<EntryKey, usize> = HashMap::new();
+    for dp in sorted {
+        let request = request_id(dp);
+        let existing = request
+            .and_then(|id| by_request.get(id).copied())
+            .map(|idx| (idx, DuplicateReason::RequestId))
+            .or_else(|| {
+                by_entry
+                    .get(&entry_key(dp))
+                    .copied()
+                    .filter(|&idx| {
+                        let group = &groups[idx];
+                        let last = group.extras.last().unwrap_or(&group.keep);
+                        dp.timestamp - last.timestamp <= window
+                            && (request.is_none() || group_requests[idx].is_none())
+                    })
+                    .map(|idx| (idx, DuplicateReason::SameEntry))
+            });
+        if let Some((idx, reason)) = existing {
+            let group = &mut groups[idx];
+            group.extras.push(dp);
+            if reason == DuplicateReason::SameEntry {
+                group.reason = reason;
+            }
+            if let Some(id) = request.filter(|_| group_requests[idx].is_none()) {
+                group_requests[idx] = Some(id);
+                by_request.insert(id, idx);
+            }
+            continue;
+        }
+        if let Some(id) = request {
+            by_request.insert(id, groups.len());
+        }
+        by_entry.insert(entry_key(dp), groups.len());
+        group_requests.push(request);
+        groups.push(DuplicateGroup {
+            reason: if request.is_some() {
+                DuplicateReason::RequestId
+            } else {
+                DuplicateReason::SameEntry
+            },
+            keep: dp,
+            extras: Vec::new(),
+        });
+    }
+    groups.retain(|group| !group.extras.is_empty());
+    groups
+}
+
+/// Value bits, daystamp and comment of a point.
+type EntryKey<'a> = (u64, &'a str, &'a str);
+
+fn entry_key(dp: &Datapoint) -> EntryKey<'_> {
+    // Adding 0.0 turns -0.0 into 0.0 so both land in the same bucket.
+    (
+        (dp.value + 0.0).to_bits(),
+        dp.daystamp.as_str(),
+        dp.comment.as_deref().unwrap_or(""),
+    )
+}
+
+fn request_id(dp: &Datapoint) -> Option<&str> {
+    dp.requestid.as_deref().filter(|id| !id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::datapoint;
+
+    /// A "run" entered `minute` minutes after the shared test instant.
+    fn entry(id: &str, minute: i64, value: f64, requestid: Option<&str>) -> Datapoint {
+        let dp = datapoint(id, value, "run");
+        Datapoint {
+            timestamp: dp.timestamp + Duration::minutes(minute),
+            requestid: requestid.map(String::from),
+            ..dp
+        }
+    }
+
+    #[test]
+    fn groups_entries_within_the_window() {
+        let points = [
+            entry("c", 4, 5.0, None),
+            entry("a", 0, 5.0, None),
+            entry("b", 1, 5.0, None),
+            entry("d", 1, 6.0, None),
+            entry("e", 30, 5.0, None),
+        ];
+        let groups = find_duplicates(&points, DEFAULT_WINDOW);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].reason, DuplicateReason::SameEntry);
+        assert_eq!(groups[0].keep.id, "a");
+        let extras: Vec<&str> = groups[0].extras.iter().map(|dp| dp.id.as_str()).collect();
+        assert_eq!(extras, ["b", "c"]);
+    }
+
+    #[test]
+    fn starts_a_new_group_after_a_gap() {
+        let points = [
+            entry("a", 0, 5.0, None),
+            entry("b", 5, 5.0, None),
+            entry("c", 30, 5.0, None),
+            entry("d", 35, 5.0, None),
+        ];
+        let groups = find_duplicates(&points, DEFAULT_WINDOW);
+        let pairs: Vec<(&str, &str)> = groups
+            .iter()
+            .map(|group| (group.keep.id.as_str(), group.extras[0].id.as_str()))
+            .collect();
+        assert_eq!(pairs, [("a", "b"), ("c", "d")]);
+    }
+
+    #[test]
+    fn groups_request_ids_at_any_distance() {
+        let points = [
+            entry("a", 0, 1.0, Some("import-1")),
+            entry("b", 600, 2.0, Some("import-1")),
+            entry("c", 0, 1.0, Some("import-2")),
+        ];
+        let groups = find_duplicates(&points, DEFAULT_WINDOW);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].reason, DuplicateReason::RequestId);
+        assert_eq!(groups[0].keep.id, "a");
+        assert_eq!(groups[0].extras[0].id, "b");
+    }
+
+    #[test]
+    fn matches_a_resend_without_the_request_id() {
+        let points = [
+            entry("a", 0, 1.0, Some("import-1")),
+            entry("b", 1, 1.0, None),
+            entry("c", 2, 1.0, Some("import-2")),
+            entry("d", 3, 1.0, Some("import-1")),
+        ];
+        let groups = find_duplicates(&points, DEFAULT_WINDOW);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].reason, DuplicateReason::SameEntry);
+        assert_eq!(groups[0].keep.id, "a");
+        let extras: Vec<&str> = groups[0].extras.iter().map(|dp| dp.id.as_str()).collect();
+        assert_eq!(extras, ["b", "d"]);
+    }
+
+    #[test]
+    fn matches_an_original_without_the_request_id() {
+        let points = [
+            entry("a", 0, 1.0, None),
+            entry("b", 1, 1.0, Some("import-1")),
+            entry("c", 2, 1.0, Some("import-2")),
+        ];
+        let groups = find_duplicates(&points, DEFAULT_WINDOW);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].keep.id, "a");
+        let extras: Vec<&str> = groups[0].extras.iter().map(|dp| dp.id.as_str()).collect();
+        assert_eq!(extras, ["b"]);
+    }
+}
@@ -0,0 +1,572 @@
+//! Input grammar for datapoint entry, shared by the clients.
+//!
+//! Values accept arithmetic (`10+15+20`, `2*(3+4)`) and durations (`1:30`,
+//! `90m`, `1h30m`), converted to the goal's time unit. Dates accept
+//! `today`, `yesterday`, `^` per day back, `-N` days back, weekday names,
+//! `YYYY-MM-DD` or `YYYYMMDD`, each optionally followed by an `HH:MM` time.
+//! Running totals on cumulative and odometer goals become datapoints with
+//! [`total_entry`].
+
+use crate::day::{parse_daystamp, DayClock, GoalDay};
+use crate::types::{CreateDatapoint, GoalFull, GoalSummary};
+use serde::Serialize;
+use time::{Date, Duration, Month, OffsetDateTime, Time, Weekday};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EntryError {
+    #[error("Invalid value '{0}'")]
+    InvalidValue(String),
+    #[error("{0} is not a whole number, but the goal only accepts integers")]
+    NotInteger(String),
+    #[error("'{0}' is a duration, but the goal's units are not hours, minutes or seconds")]
+    NotTimeGoal(String),
+    #[error("{0} is neither a cumulative (kyoom) nor an odometer goal")]
+    NotCumulative(String),
+    #[error("Invalid date '{0}'; expected YYYY-MM-DD, today, yesterday, ^, -N or a weekday, optionally followed by HH:MM")]
+    InvalidDate(String),
+}
+
+/// Unit a goal measures time in, guessed from its `gunits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Hours,
+    Minutes,
+    Seconds,
+}
+
+impl TimeUnit {
+    /// Reads units such as "hours", "hrs", "mins" or "seconds"; `None` for
+    /// goals that do not measure time, such as "pages".
+    #[must_use]
+    pub fn from_gunits(gunits: &str) -> Option<Self> {
+        let gunits = gunits.trim().to_lowercase();
+        if gunits.starts_with("hour") || gunits.starts_with("hr") || gunits == "h" {
+            Some(Self::Hours)
+        } else if gunits.starts_with("min") {
+            Some(Self::Minutes)
+        } else if gunits.starts_with("sec") {
+            Some(Self::Seconds)
+        } else {
+            None
+        }
+    }
+
+    const fn seconds(self) -> f64 {
+        match self {
+            Self::Hours => 3600.0,
+            Self::Minutes => 60.0,
+            Self::Seconds => 1.0,
+        }
+    }
+}
+
+/// The goal settings that decide how a value is read.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EntryGoal {
+    pub integery: bool,
+    /// Unit durations convert to; durations are rejected without one
+    pub time_unit: Option<TimeUnit>,
+}
+
+impl From<&GoalSummary> for EntryGoal {
+    fn from(goal: &GoalSummary) -> Self {
+        Self {
+            integery: goal.integery,
+            time_unit: TimeUnit::from_gunits(&goal.gunits),
+        }
+    }
+}
+
+impl From<&GoalFull> for EntryGoal {
+    fn from(goal: &GoalFull) -> Self {
+        Self {
+            integery: goal.integery.unwrap_or(false),
+            time_unit: TimeUnit::from_gunits(goal.gunits.as_deref().unwrap_or_default()),
+        }
+    }
+}
+
+/// A parsed date: a whole day, or an explicit time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryDate {
+    Day(Date),
+    Time(OffsetDateTime),
+}
+
+impl EntryDate {
+    /// Sets the datapoint's daystamp or timestamp.
+    pub fn apply(self, datapoint: CreateDatapoint) -> CreateDatapoint {
+        match self {
+            Self::Day(day) => datapoint.with_daystamp(&crate::day::daystamp(day)),
+            Self::Time(at) => datapoint.with_timestamp(at),
+        }
+    }
+
+    /// The calendar day in the account timezone, for use as a range bound.
+    #[must_use]
+    pub fn day(self, clock: &DayClock) -> Date {
+        match self {
+            Self::Day(day) => day,
+            Self::Time(at) => at.to_offset(clock.offset_at(at)).date(),
+        }
+    }
+}
+
+/// A running total turned into a datapoint.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TotalEntry {
+    /// The goal's total before this entry
+    pub previous: f64,
+    /// Change from `previous`
+    pub delta: f64,
+    /// Value to submit: the delta, or the reading itself on odometer goals
+    pub value: f64,
+}
+
+/// Computes the datapoint that brings a cumulative or odometer goal to
+/// `total`. Odometer goals take the reading as is, compared against the
+/// last datapoint; cumulative goals get the difference from `curval`.
+///
+/// # Errors
+/// Returns [`EntryError::NotCumulative`] for goals with neither `odom` nor
+/// `kyoom` set.
+pub fn total_entry(goal: &GoalFull, total: f64) -> Result<TotalEntry, EntryError> {
+    if goal.odom == Some(true) {
+        let previous = goal
+            .last_datapoint
+            .as_ref()
+            .and_then(|dp| dp.value)
+            .or(goal.curval)
+            .unwrap_or(0.0);
+        Ok(TotalEntry {
+            previous,
+            delta: total - previous,
+            value: total,
+        })
+    } else if goal.kyoom == Some(true) {
+        let previous = goal.curval.unwrap_or(0.0);
+        Ok(TotalEntry {
+            previous,
+            delta: total - previous,
+            value: total - previous,
+        })
+    } else {
+        Err(EntryError::NotCumulative(goal.slug.clone()))
+    }
+}
+
+/// Evaluates a value expression and checks it against the goal.
+///
+/// # Errors
+/// Returns an error for malformed input, non-finite results, durations on
+/// goals that do not measure time, or fractions entered on an integery goal.
+pub fn parse_value(input: &str, goal: &EntryGoal) -> Result<f64, EntryError> {
+    let invalid = || EntryError::InvalidValue(input.trim().to_string());
+    let mut parser = ValueParser {
+        input: input.trim().as_bytes(),
+        pos: 0,
+        unit: goal.time_unit,
+        unitless_duration: None,
+    };
+    let value = parser.expr().ok_or_else(|| {
+        parser
+            .unitless_duration
+            .take()
+            .map_or_else(invalid, EntryError::NotTimeGoal)
+    })?;
+    parser.skip_whitespace();
+    if parser.pos != parser.input.len() || !value.is_finite() {
+        return Err(invalid());
+    }
+    if goal.integery && value.fract() != 0.0 {
+        return Err(EntryError::NotInteger(value.to_string()));
+    }
+    Ok(value)
+}
+
+/// Recursive descent over `+ - * /`, unary minus and parentheses.
+struct ValueParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    unit: Option<TimeUnit>,
+    /// A duration seen while the goal has no time unit
+    unitless_duration: Option<String>,
+}
+
+impl ValueParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        if self.input.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Option<f64> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat(b'+') {
+                value += self.term()?;
+            } else if self.eat(b'-') {
+                value -= self.term()?;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Option<f64> {
+        let mut value = self.factor()?;
+        loop {
+            if self.eat(b'*') {
+                value *= self.factor()?;
+            } else if self.eat(b'/') {
+                value /= self.factor()?;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Option<f64> {
+        if self.eat(b'-') {
+            return self.factor().map(|value| -value);
+        }
+        if self.eat(b'(') {
+            let value = self.expr()?;
+            return self.eat(b')').then_some(value);
+        }
+        self.skip_whitespace();
+        let start = self.pos;
+        while let Some(&byte) = self.input.get(self.pos) {
+            if !(byte.is_ascii_alphanumeric()
+                || matches!(byte, b'.' | b':')
+                || self.at_exponent_sign(start))
+            {
+                break;
+            }
+            self.pos += 1;
+        }
+        let atom = std::str::from_utf8(&self.input[start..self.pos]).ok()?;
+        if atom.is_empty() {
+            return None;
+        }
+        if let Ok(number) = atom.parse::<f64>() {
+            return Some(number);
+        }
+        let seconds = parse_duration(atom)?;
+        match self.unit {
+            Some(unit) => Some(seconds / unit.seconds()),
+            None => {
+                self.unitless_duration = Some(atom.to_string());
+                None
+            }
+        }
+    }
+
+    /// Whether the byte at `pos` is the sign of an exponent, as in `1e-3`.
+    fn at_exponent_sign(&self, start: usize) -> bool {
+        let atom = &self.input[start..self.pos];
+        matches!(self.input.get(self.pos), Some(b'+' | b'-'))
+            && matches!(atom.split_last(), Some((b'e' | b'E', mantissa))
+                if !mantissa.is_empty()
+                    && mantissa.iter().all(|b| b.is_ascii_digit() || *b == b'.'))
+    }
+}
+
+/// Parses `H:MM`, `H:MM:SS` or unit sequences like `1h30m` into seconds.
+fn parse_duration(atom: &str) -> Option<f64> {
+    if atom.contains(':') {
+        let parts: Vec<&str> = atom.split(':').collect();
+        if parts.len() > 3 || parts.iter().any(|part| part.is_empty()) {
+            return None;
+        }
+        let mut seconds = 0.0;
+        let mut scale = 3600.0;
+        for (idx, part) in parts.iter().enumerate() {
+            let value: f64 = part.parse().ok()?;
+            if idx > 0 && (part.len() != 2 || value >= 60.0) {
+                return None;
+            }
+            seconds += value * scale;
+            scale /= 60.0;
+        }
+        return Some(seconds);
+    }
+
+    let mut seconds = 0.0;
+    let mut rest = atom.to_lowercase();
+    let mut last_scale = f64::INFINITY;
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| c.is_ascii_alphabetic())?;
+        let (number, tail) = rest.split_at(split);
+        let unit_len = tail
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(tail.len());
+        let scale = match &tail[..unit_len] {
+            "h" | "hr" | "hrs" | "hour" | "hours" => 3600.0,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60.0,
+            "s" | "sec" | "secs" | "second" | "seconds" => 1.0,
+            _ => return None,
+        };
+        // Units must shrink left to right: `1h30m`, not `30m1h`.
+        if scale >= last_scale {
+            return None;
+        }
+        last_scale = scale;
+        seconds += number.parse::<f64>().ok()? * scale;
+        rest = tail[unit_len..].to_string();
+    }
+    Some(seconds)
+}
+
+/// Parses a date relative to the goal's current day.
+///
+/// # Errors
+/// Returns [`EntryError::InvalidDate`] for input outside the grammar.
+pub fn parse_date(input: &str, today: &GoalDay) -> Result<EntryDate, EntryError> {
+    let invalid = || EntryError::InvalidDate(input.trim().to_string());
+    let mut input = input.trim().to_lowercase();
+    // Accept ISO `YYYY-MM-DDTHH:MM` alongside `YYYY-MM-DD HH:MM`.
+    if input.len() > 10 && input.as_bytes()[10] == b't' {
+        input.replace_range(10..11, " ");
+    }
+    let mut words = input.split_whitespace();
+    let (day, time) = match (words.next(), words.next(), words.next()) {
+        (Some(word), None, None) if word.contains(':') => (None, Some(word)),
+        (Some(word), None, None) => (Some(word), None),
+        (Some(word), Some(time), None) => (Some(word), Some(time)),
+        _ => return Err(invalid()),
+    };
+
+    let day = match day {
+        Some(word) => parse_day(word, today.today).ok_or_else(invalid)?,
+        None => today.today,
+    };
+    match time {
+        Some(time) => {
+            let time = parse_time(time).ok_or_else(invalid)?;
+            Ok(EntryDate::Time(today.clock().local_time(day, time)))
+        }
+        None => Ok(EntryDate::Day(day)),
+    }
+}
+
+fn parse_day(word: &str, today: Date) -> Option<Date> {
+    let days_back = match word {
+        "today" => Some(0),
+        "yesterday" => Some(1),
+        _ if !word.is_empty() && word.bytes().all(|b| b == b'^') => Some(word.len() as i64),
+        _ => word
+            .strip_prefix('-')
+            .filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|n| n.parse().ok()),
+    };
+    if let Some(days) = days_back {
+        return today.checked_sub(Duration::days(days));
+    }
+    if let Some(weekday) = parse_weekday(word) {
+        let back =
+            today.weekday().number_days_from_monday() + 7 - weekday.number_days_from_monday();
+        return today.checked_sub(Duration::days(i64::from(back % 7)));
+    }
+    parse_daystamp(word).or_else(|| parse_iso_date(word))
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    const NAMES: [(&str, Weekday); 7] = [
+        ("monday", Weekday::Monday),
+        ("tuesday", Weekday::Tuesday),
+        ("wednesday", Weekday::Wednesday),
+        ("thursday", Weekday::Thursday),
+        ("friday", Weekday::Friday),
+        ("saturday", Weekday::Saturday),
+        ("sunday", Weekday::Sunday),
+    ];
+    if word.len() < 3 {
+        return None;
+    }
+    NAMES
+        .iter()
+        .find(|(name, _)| name.starts_with(word))
+        .map(|(_, weekday)| *weekday)
+}
+
+fn parse_iso_date(word: &str) -> Option<Date> {
+    let [year, month, day] = word.split('-').collect::<Vec<_>>().try_into().ok()?;
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let month = Month::try_from(month.parse::<u8>().ok()?).ok()?;
+    Date::from_calendar_date(year.parse().ok()?, month, day.parse().ok()?).ok()
+}
+
+fn parse_time(word: &str) -> Option<Time> {
+    let (hour, minute) = word.split_once(':')?;
+    if hour.is_empty() || hour.len() > 2 || minute.len() != 2 {
+        return None;
+    }
+    Time::from_hms(hour.parse().ok()?, minute.parse().ok()?, 0).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours() -> EntryGoal {
+        EntryGoal {
+            integery: false,
+            time_unit: Some(TimeUnit::Hours),
+        }
+    }
+
+    fn minutes() -> EntryGoal {
+        EntryGoal {
+            integery: false,
+            time_unit: Some(TimeUnit::Minutes),
+        }
+    }
+
+    /// 2026-07-01 02:00 UTC, still Tuesday 2026-06-30 in New York.
+    fn today() -> GoalDay {
+        let now = OffsetDateTime::from_unix_timestamp(1_782_871_200).unwrap();
+        DayClock::new("America/New_York").unwrap().goal_day(0, now)
+    }
+
+    fn day(date: &str) -> EntryDate {
+        EntryDate::Day(parse_daystamp(date).unwrap())
+    }
+
+    #[test]
+    fn evaluates_arithmetic() {
+        assert_eq!(parse_value("10+15+20", &hours()), Ok(45.0));
+        assert_eq!(parse_value("2*(3+4)", &hours()), Ok(14.0));
+        assert_eq!(parse_value("-1.5", &hours()), Ok(-1.5));
+        assert_eq!(parse_value("10 - 2 / 4", &hours()), Ok(9.5));
+        assert!(parse_value("1/0", &hours()).is_err());
+        assert!(parse_value("3+", &hours()).is_err());
+        assert!(parse_value("abc", &hours()).is_err());
+        assert_eq!(parse_value("1e3", &hours()), Ok(1000.0));
+        assert_eq!(parse_value("-1e-3", &hours()), Ok(-0.001));
+        assert_eq!(parse_value("2.5E+2-50", &hours()), Ok(200.0));
+        assert!(parse_value("inf", &hours()).is_err());
+    }
+
+    #[test]
+    fn converts_durations_to_goal_units() {
+        assert_eq!(parse_value("1:30", &hours()), Ok(1.5));
+        assert_eq!(parse_value("90m", &hours()), Ok(1.5));
+        assert_eq!(parse_value("1h30m", &minutes()), Ok(90.0));
+        assert_eq!(parse_value("1:30+45m", &minutes()), Ok(135.0));
+        assert!(parse_value("1:75", &hours()).is_err());
+        assert!(parse_value("30m1h", &hours()).is_err());
+        assert_eq!(TimeUnit::from_gunits("Minutes"), Some(TimeUnit::Minutes));
+        assert_eq!(TimeUnit::from_gunits("hrs"), Some(TimeUnit::Hours));
+        assert_eq!(TimeUnit::from_gunits("pages"), None);
+    }
+
+    #[test]
+    fn rejects_durations_on_goals_without_time_units() {
+        let pages = EntryGoal::default();
+        assert_eq!(parse_value("12+3", &pages), Ok(15.0));
+        assert_eq!(
+            parse_value("1:30", &pages),
+            Err(EntryError::NotTimeGoal("1:30".to_string()))
+        );
+        assert_eq!(
+            parse_value("2+90m", &pages),
+            Err(EntryError::NotTimeGoal("90m".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_fractions_on_integery_goals() {
+        let goal = EntryGoal {
+            integery: true,
+            ..EntryGoal::default()
+        };
+        assert_eq!(parse_value("3+4", &goal), Ok(7.0));
+        assert_eq!(
+            parse_value("7/2", &goal),
+            Err(EntryError::NotInteger("3.5".to_string()))
+        );
+    }
+
+    #[test]
+    fn computes_totals_for_cumulative_and_odometer_goals() {
+        let goal = |fields: serde_json::Value| -> GoalFull {
+            let mut goal = serde_json::json!({"id": "1", "slug": "reading"});
+            goal.as_object_mut()
+                .unwrap()
+                .extend(fields.as_object().unwrap().clone());
+            serde_json::from_value(goal).unwrap()
+        };
+
+        let kyoom = goal(serde_json::json!({"kyoom": true, "curval": 300.0}));
+        let entry = total_entry(&kyoom, 312.0).unwrap();
+        assert_eq!(
+            (entry.previous, entry.delta, entry.value),
+            (300.0, 12.0, 12.0)
+        );
+
+        let odom = goal(serde_json::json!({
+            "odom": true,
+            "curval": 1300.0,
+            "last_datapoint": {"id": "a", "timestamp": 0, "daystamp": "20260101", "value": 300.0},
+        }));
+        let entry = total_entry(&odom, 312.0).unwrap();
+        assert_eq!(
+            (entry.previous, entry.delta, entry.value),
+            (300.0, 12.0, 312.0)
+        );
+
+        let plain = goal(serde_json::json!({}));
+        assert_eq!(
+            total_entry(&plain, 1.0),
+            Err(EntryError::NotCumulative("reading".to_string()))
+        );
+    }
+
+    #[test]
+    fn parses_relative_dates() {
+        let today = today();
+        assert_eq!(parse_date("today", &today), Ok(day("20260630")));
+        assert_eq!(parse_date("yesterday", &today), Ok(day("20260629")));
+        assert_eq!(parse_date("^^", &today), Ok(day("20260628")));
+        assert_eq!(parse_date("-3", &today), Ok(day("20260627")));
+        assert_eq!(parse_date("tue", &today), Ok(day("20260630")));
+        assert_eq!(parse_date("Monday", &today), Ok(day("20260629")));
+        assert_eq!(parse_date("wed", &today), Ok(day("20260624")));
+        assert_eq!(parse_date("2026-07-01", &today), Ok(day("20260701")));
+        assert_eq!(parse_date("20260701", &today), Ok(day("20260701")));
+        assert!(parse_date("2026-02-30", &today).is_err());
+        assert!(parse_date("soon", &today).is_err());
+    }
+
+    #[test]
+    fn parses_explicit_times_in_the_account_timezone() {
+        let today = today();
+        let at = |unix| {
+            Ok(EntryDate::Time(
+                OffsetDateTime::from_unix_timestamp(unix).unwrap(),
+            ))
+        };
+        // 21:15 EDT on 2026-06-30 is 01:15 UTC on 2026-07-01.
+        assert_eq!(parse_date("21:15", &today), at(1_782_868_500));
+        assert_eq!(parse_date("yesterday 21:15", &today), at(1_782_782_100));
+        assert_eq!(parse_date("2026-06-30T21:15", &today), at(1_782_868_500));
+        assert!(parse_date("25:00", &today).is_err());
+    }
+}
@@ -1,6 +1,15 @@
 #![allow(clippy::multiple_crate_versions)]
 
+pub mod changeset;
+pub mod day;
+pub mod duplicates;
+pub mod entry;
+pub mod search;
+pub mod stats;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod types;
+use crate::changeset::{check_unchanged, Conflict};
 use crate::types::{
     AuthTokenResponse, Charge, CreateAllResponse, CreateCharge, CreateDatapoint, CreateGoal,
     Datapoint, DatapointFull, DatapointResponse, Goal, GoalFull, GoalResponse, GoalSummary,
@@ -9,6 +18,9 @@ use crate::types::{
 use reqwest::Client;
 use time::OffsetDateTime;
 
+/// Datapoints requested per page by `get_datapoints_since`.
+const DATAPOINT_PAGE_SIZE: u64 = 300;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("HTTP error: {0}")]
@@ -21,6 +33,8 @@ pub enum Error {
     },
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Datapoint {} was changed elsewhere since it was loaded", .0.before.id)]
+    Conflict(Box<Conflict>),
 }
 
 impl Error {
@@ -319,6 +333,81 @@ impl BeeminderClient {
         self.fetch_datapoints(goal, sort, count, page, per).await
     }
 
+    /// Retrieves a goal's datapoints on or after the `since` daystamp
+    /// (`YYYYMMDD`), newest day first, or all of them without `since`.
+    ///
+    /// Pages are fetched until one reaches past `since`, so searching the
+    /// recent days of a long history doesn't download all of it.
+    ///
+    /// # Errors
+    /// Returns an error if an HTTP request fails or a response cannot be parsed.
+    pub async fn get_datapoints_since(
+        &self,
+        goal: &str,
+        since: Option<&str>,
+    ) -> Result<Vec<Datapoint>, Error> {
+        let mut datapoints: Vec<Datapoint> = Vec::new();
+        let mut page = 1;
+        loop {
+            let batch: Vec<Datapoint> = self
+                .fetch_datapoints(
+                    goal,
+                    Some("daystamp"),
+                    None,
+                    Some(page),
+                    Some(DATAPOINT_PAGE_SIZE),
+                )
+                .await?;
+            let last_page = (batch.len() as u64) < DATAPOINT_PAGE_SIZE
+                || since.is_some_and(|since| {
+                    batch.last().is_some_and(|dp| dp.daystamp.as_str() < since)
+                });
+            datapoints.extend(batch);
+            if last_page {
+                break;
+            }
+            page += 1;
+        }
+        if let Some(since) = since {
+            datapoints.retain(|dp| dp.daystamp.as_str() >= since);
+        }
+        Ok(datapoints)
+    }
+
+    /// Retrieves a goal's datapoints created or updated at or after `since`,
+    /// most recently updated first, a page at a time.
+    ///
+    /// # Errors
+    /// Returns an error if an HTTP request fails or a response cannot be parsed.
+    pub async fn get_datapoints_updated_since(
+        &self,
+        goal: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Datapoint>, Error> {
+        let mut datapoints: Vec<Datapoint> = Vec::new();
+        let mut page = 1;
+        loop {
+            let batch: Vec<Datapoint> = self
+                .fetch_datapoints(
+                    goal,
+                    Some("updated_at"),
+                    None,
+                    Some(page),
+                    Some(DATAPOINT_PAGE_SIZE),
+                )
+                .await?;
+            let last_page = (batch.len() as u64) < DATAPOINT_PAGE_SIZE
+                || batch.last().is_some_and(|dp| dp.updated_at < since);
+            datapoints.extend(batch);
+            if last_page {
+                break;
+            }
+            page += 1;
+        }
+        datapoints.retain(|dp| dp.updated_at >= since);
+        Ok(datapoints)
+    }
+
     /// Private helper for fetching datapoints with generic return type
     async fn fetch_datapoints<T: DatapointResponse>(
         &self,
@@ -395,6 +484,43 @@ impl BeeminderClient {
         self.delete(&endpoint, &()).await
     }
 
+    /// Updates a datapoint unless it changed on the server since `loaded`
+    /// was fetched, going by `updated_at`.
+    ///
+    /// # Errors
+    /// Returns `Error::Conflict` with both versions if the datapoint changed
+    /// or was deleted, or an error if an HTTP request fails.
+    pub async fn update_datapoint_checked(
+        &self,
+        goal: &str,
+        loaded: &Datapoint,
+        update: &UpdateDatapoint,
+    ) -> Result<Datapoint, Error> {
+        let recent = self
+            .get_datapoints_updated_since(goal, loaded.updated_at)
+            .await?;
+        check_unchanged(loaded, &recent)?;
+        self.update_datapoint(goal, update).await
+    }
+
+    /// Deletes a datapoint unless it changed on the server since `loaded`
+    /// was fetched, going by `updated_at`.
+    ///
+    /// # Errors
+    /// Returns `Error::Conflict` with both versions if the datapoint changed
+    /// or was deleted, or an error if an HTTP request fails.
+    pub async fn delete_datapoint_checked(
+        &self,
+        goal: &str,
+        loaded: &Datapoint,
+    ) -> Result<Datapoint, Error> {
+        let recent = self
+            .get_datapoints_updated_since(goal, loaded.updated_at)
+            .await?;
+        check_unchanged(loaded, &recent)?;
+        self.delete_datapoint(goal, &loaded.id).await
+    }
+
     /// Creates multiple datapoints for a goal.
     ///
     /// # Errors
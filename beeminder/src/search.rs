@@ -0,0 +1,127 @@
+//! Searching datapoint comments across goals.
+
+use crate::day::{daystamp, DayClock};
+use crate::entry::{self, EntryError};
+use crate::types::Datapoint;
+use crate::{BeeminderClient, Error};
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use time::OffsetDateTime;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SearchError {
+    #[error("Invalid pattern: {0}")]
+    Pattern(#[from] regex::Error),
+    #[error(transparent)]
+    Since(#[from] EntryError),
+    #[error(transparent)]
+    Api(#[from] Error),
+}
+
+/// A comment search as a user enters it: a regex, optionally limited to one
+/// goal and to the days since an entry date such as `yesterday` or `-7`.
+#[derive(Debug)]
+pub struct CommentSearch {
+    regex: Regex,
+    goal: Option<String>,
+    since: Option<String>,
+}
+
+impl CommentSearch {
+    /// Compiles the comment pattern.
+    ///
+    /// # Errors
+    /// Returns [`SearchError::Pattern`] for an invalid regex.
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<Self, SearchError> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()?;
+        Ok(Self {
+            regex,
+            goal: None,
+            since: None,
+        })
+    }
+
+    /// Searches only `goal` instead of all active and archived goals.
+    #[must_use]
+    pub fn goal(mut self, goal: Option<&str>) -> Self {
+        self.goal = goal.map(str::to_string);
+        self
+    }
+
+    /// Skips days before `since`, in the grammar of [`entry::parse_date`].
+    #[must_use]
+    pub fn since(mut self, since: Option<&str>) -> Self {
+        self.since = since.map(str::to_string);
+        self
+    }
+
+    /// Runs the search, newest hits first.
+    ///
+    /// # Errors
+    /// Returns an error for an invalid `since` or a failed request.
+    pub async fn run(&self, client: &BeeminderClient) -> Result<Vec<SearchHit>, SearchError> {
+        // Goals have their own deadlines; `since` is read as a calendar day.
+        let since = match self.since.as_deref() {
+            Some(since) => {
+                let clock = DayClock::for_user(&client.get_user().await?.timezone);
+                let today = clock.goal_day(0, OffsetDateTime::now_utc());
+                Some(daystamp(entry::parse_date(since, &today)?.day(&clock)))
+            }
+            None => None,
+        };
+        let goals: Vec<String> = match self.goal.as_deref() {
+            Some(goal) => vec![goal.to_string()],
+            None => client
+                .get_goals()
+                .await?
+                .into_iter()
+                .chain(client.get_archived_goals().await?)
+                .map(|goal| goal.slug)
+                .collect(),
+        };
+        Ok(
+            search_comments(client, &goals, since.as_deref(), |comment| {
+                self.regex.is_match(comment)
+            })
+            .await?,
+        )
+    }
+}
+
+/// A datapoint whose comment matched, with the goal it belongs to.
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub goal: String,
+    #[serde(flatten)]
+    pub datapoint: Datapoint,
+}
+
+/// Scans the comments of each goal's datapoints on or after the `since`
+/// daystamp and returns the matches, newest first.
+///
+/// # Errors
+/// Returns an error if fetching the datapoints of any goal fails.
+pub async fn search_comments(
+    client: &BeeminderClient,
+    goals: &[String],
+    since: Option<&str>,
+    is_match: impl Fn(&str) -> bool,
+) -> Result<Vec<SearchHit>, Error> {
+    let mut hits = Vec::new();
+    for goal in goals {
+        let datapoints = client.get_datapoints_since(goal, since).await?;
+        hits.extend(
+            datapoints
+                .into_iter()
+                .filter(|dp| dp.comment.as_deref().is_some_and(&is_match))
+                .map(|datapoint| SearchHit {
+                    goal: goal.clone(),
+                    datapoint,
+                }),
+        );
+    }
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.datapoint.timestamp));
+    Ok(hits)
+}
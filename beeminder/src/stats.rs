@@ -0,0 +1,670 @@
+//! Statistics over a goal's datapoints: streaks, period totals, rolling
+//! averages, trend and time on the road.
+//!
+//! Datapoints are first reduced to one value per day with the goal's
+//! `aggday` method, as Beeminder does when plotting.
+
+use crate::day::parse_daystamp;
+use crate::types::{Datapoint, DatapointFull, GoalFull};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use time::{Date, Duration, OffsetDateTime};
+
+/// Windows, in days, of the rolling averages in [`GoalStats`].
+pub const AVERAGE_WINDOWS: [u32; 3] = [7, 30, 90];
+
+/// How the datapoints of one day are combined into the day's value, as
+/// documented for the goal's `aggday` setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggday {
+    Last,
+    First,
+    Min,
+    Max,
+    Sum,
+    /// Mean of all values (`truemean`)
+    Mean,
+    /// Mean of the distinct values (`mean`, `uniqmean`)
+    Uniqmean,
+    Median,
+    /// Most frequent value, the smallest one on ties
+    Mode,
+    /// Trimmed mean; Beeminder computes it as the plain mean
+    Trimmean,
+    Count,
+    /// 1 for any day with data (`binary`, `jolly`)
+    Binary,
+    /// 1 if any value is nonzero
+    Nonzero,
+    /// n(n+1)/2 of the sum
+    Triangle,
+    /// Square of the sum
+    Square,
+    /// Sum of the differences of successive pairs, e.g. start and stop
+    /// times
+    Clocky,
+    /// Sum capped at 2600
+    Kyshoc,
+    /// Sum capped at the goal's daily rate
+    Skatesum,
+    /// Sum capped at 1
+    Cap1,
+}
+
+/// Error returned for an unsupported `aggday` name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AggdayParseError(pub String);
+
+impl std::fmt::Display for AggdayParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unsupported aggday '{}'", self.0)
+    }
+}
+
+impl std::error::Error for AggdayParseError {}
+
+impl FromStr for Aggday {
+    type Err = AggdayParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last" => Ok(Self::Last),
+            "first" => Ok(Self::First),
+            "min" => Ok(Self::Min),
+            "max" => Ok(Self::Max),
+            "sum" => Ok(Self::Sum),
+            "truemean" => Ok(Self::Mean),
+            "mean" | "uniqmean" => Ok(Self::Uniqmean),
+            "median" => Ok(Self::Median),
+            "mode" => Ok(Self::Mode),
+            "trimmean" => Ok(Self::Trimmean),
+            "count" => Ok(Self::Count),
+            "binary" | "jolly" => Ok(Self::Binary),
+            "nonzero" => Ok(Self::Nonzero),
+            "triangle" => Ok(Self::Triangle),
+            "square" => Ok(Self::Square),
+            "clocky" => Ok(Self::Clocky),
+            "kyshoc" => Ok(Self::Kyshoc),
+            "skatesum" => Ok(Self::Skatesum),
+            "cap1" => Ok(Self::Cap1),
+            other => Err(AggdayParseError(other.to_string())),
+        }
+    }
+}
+
+impl Aggday {
+    /// Combines the values of one day, given in timestamp order.
+    /// `daily_rate` caps `skatesum`, which is a plain sum without one.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn aggregate(self, values: &[f64], daily_rate: Option<f64>) -> Option<f64> {
+        let first = *values.first()?;
+        let sum = || values.iter().sum::<f64>();
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        let value = match self {
+            Self::Last => values[values.len() - 1],
+            Self::First => first,
+            Self::Min => values.iter().copied().fold(first, f64::min),
+            Self::Max => values.iter().copied().fold(first, f64::max),
+            Self::Sum => sum(),
+            Self::Mean | Self::Trimmean => mean(values),
+            Self::Uniqmean => {
+                let mut unique: Vec<f64> = Vec::new();
+                for value in values {
+                    if !unique.contains(value) {
+                        unique.push(*value);
+                    }
+                }
+                mean(&unique)
+            }
+            Self::Median => {
+                let sorted = sorted(values);
+                let mid = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    (sorted[mid - 1] + sorted[mid]) / 2.0
+                } else {
+                    sorted[mid]
+                }
+            }
+            Self::Mode => {
+                let sorted = sorted(values);
+                let mut best = (sorted[0], 0);
+                for run in sorted.chunk_by(|a, b| a == b) {
+                    if run.len() > best.1 {
+                        best = (run[0], run.len());
+                    }
+                }
+                best.0
+            }
+            Self::Count => values.len() as f64,
+            Self::Binary => 1.0,
+            Self::Nonzero => f64::from(u8::from(values.iter().any(|value| *value != 0.0))),
+            Self::Triangle => sum() * (sum() + 1.0) / 2.0,
+            Self::Square => sum().powi(2),
+            Self::Clocky => values.chunks_exact(2).map(|pair| pair[1] - pair[0]).sum(),
+            Self::Kyshoc => sum().min(2600.0),
+            Self::Skatesum => daily_rate.map_or_else(sum, |rate| sum().min(rate)),
+            Self::Cap1 => sum().min(1.0),
+        };
+        Some(value)
+    }
+}
+
+fn sorted(values: &[f64]) -> Vec<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted
+}
+
+/// A datapoint as seen by the statistics: its day, time and value.
+pub trait Measurement {
+    fn daystamp(&self) -> &str;
+    fn timestamp(&self) -> OffsetDateTime;
+    fn measured_value(&self) -> Option<f64>;
+}
+
+impl Measurement for Datapoint {
+    fn daystamp(&self) -> &str {
+        &self.daystamp
+    }
+
+    fn timestamp(&self) -> OffsetDateTime {
+        self.timestamp
+    }
+
+    fn measured_value(&self) -> Option<f64> {
+        Some(self.value)
+    }
+}
+
+impl Measurement for DatapointFull {
+    fn daystamp(&self) -> &str {
+        &self.daystamp
+    }
+
+    fn timestamp(&self) -> OffsetDateTime {
+        self.timestamp
+    }
+
+    fn measured_value(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+/// Goal settings the statistics depend on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatsOptions {
+    pub aggday: Aggday,
+    /// Whether the goal sums its daily values (`kyoom`)
+    pub cumulative: bool,
+    /// Slope of the bright red line per day, if known
+    pub daily_rate: Option<f64>,
+    /// 1 if more is better, -1 if less is better
+    pub yaw: i8,
+    /// Last day included in the statistics
+    pub today: Date,
+}
+
+impl StatsOptions {
+    /// Reads aggday, kyoom, rate and direction from a goal. Without an
+    /// aggday, Beeminder's default for the goal is used.
+    ///
+    /// # Errors
+    /// Returns an error naming the goal's aggday if it is not one of the
+    /// documented methods.
+    pub fn for_goal(goal: &GoalFull, today: Date) -> Result<Self, AggdayParseError> {
+        let cumulative = goal.kyoom.unwrap_or(false);
+        let default_aggday = if cumulative {
+            Aggday::Sum
+        } else {
+            Aggday::Last
+        };
+        let aggday = match goal.aggday.as_deref() {
+            Some(aggday) => aggday.parse()?,
+            None => default_aggday,
+        };
+        let daily_rate = goal
+            .rate
+            .zip(goal.runits.as_deref())
+            .and_then(|(rate, runits)| runits_days(runits).map(|days| rate / days));
+        let yaw = match goal.extra.get("yaw").and_then(serde_json::Value::as_i64) {
+            Some(yaw) if yaw < 0 => -1,
+            Some(_) => 1,
+            None => match goal.goal_type.as_deref() {
+                Some("fatloser" | "drinker" | "inboxer") => -1,
+                _ => 1,
+            },
+        };
+        Ok(Self {
+            aggday,
+            cumulative,
+            daily_rate,
+            yaw,
+            today,
+        })
+    }
+}
+
+fn runits_days(runits: &str) -> Option<f64> {
+    match runits {
+        "y" => Some(365.25),
+        "m" => Some(30.4375),
+        "w" => Some(7.0),
+        "d" => Some(1.0),
+        "h" => Some(1.0 / 24.0),
+        _ => None,
+    }
+}
+
+/// Total of the daily values in one week or month.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeriodTotal {
+    /// `YYYY-MM-DD` of the Monday for weeks, `YYYY-MM` for months
+    pub period: String,
+    pub total: f64,
+}
+
+/// Average daily value over the last `days` days.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RollingAverage {
+    pub days: u32,
+    pub average: f64,
+}
+
+/// Statistics of one goal.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GoalStats {
+    /// Days with at least one datapoint
+    pub entry_days: usize,
+    /// `YYYY-MM-DD` of the first day with a datapoint
+    pub first_day: Option<String>,
+    /// `YYYY-MM-DD` of the last day with a datapoint
+    pub last_day: Option<String>,
+    /// Consecutive entry days ending today, or yesterday if today has none yet
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// Oldest first
+    pub weekly_totals: Vec<PeriodTotal>,
+    /// Oldest first
+    pub monthly_totals: Vec<PeriodTotal>,
+    pub rolling_averages: Vec<RollingAverage>,
+    /// Least-squares slope of the daily values, per day
+    pub trend_per_day: Option<f64>,
+    /// Share of days since the first entry spent on the good side of the
+    /// bright red line, approximated as a straight line from the first
+    /// datapoint at the goal's current rate
+    pub on_road_percent: Option<f64>,
+}
+
+/// Reduces datapoints to one value per day with the goal's aggday method.
+#[must_use]
+pub fn daily_values<D: Measurement>(
+    datapoints: &[D],
+    options: &StatsOptions,
+) -> BTreeMap<Date, f64> {
+    let mut sorted: Vec<&D> = datapoints.iter().collect();
+    sorted.sort_by_key(|dp| dp.timestamp());
+
+    let mut days: BTreeMap<Date, Vec<f64>> = BTreeMap::new();
+    for dp in sorted {
+        let (Some(day), Some(value)) = (parse_daystamp(dp.daystamp()), dp.measured_value()) else {
+            continue;
+        };
+        days.entry(day).or_default().push(value);
+    }
+    days.into_iter()
+        .filter_map(|(day, values)| {
+            options
+                .aggday
+                .aggregate(&values, options.daily_rate)
+                .map(|value| (day, value))
+        })
+        .collect()
+}
+
+/// Computes all statistics for a goal's datapoints.
+#[must_use]
+pub fn goal_stats<D: Measurement>(datapoints: &[D], options: &StatsOptions) -> GoalStats {
+    let days = daily_values(datapoints, options);
+    let (current_streak, longest_streak) = streaks(&days, options.today);
+    GoalStats {
+        entry_days: days.len(),
+        first_day: days.keys().next().map(|day| format_day(*day)),
+        last_day: days.keys().next_back().map(|day| format_day(*day)),
+        current_streak,
+        longest_streak,
+        weekly_totals: period_totals(&days, |day| {
+            let monday = day - Duration::days(i64::from(day.weekday().number_days_from_monday()));
+            format_day(monday)
+        }),
+        monthly_totals: period_totals(&days, |day| {
+            format!("{:04}-{:02}", day.year(), u8::from(day.month()))
+        }),
+        rolling_averages: AVERAGE_WINDOWS
+            .iter()
+            .filter_map(|window| rolling_average(&days, options, *window))
+            .collect(),
+        trend_per_day: linear_trend(&days),
+        on_road_percent: on_road_percent(&days, options),
+    }
+}
+
+/// Current and longest run of consecutive days with entries.
+#[must_use]
+pub fn streaks(days: &BTreeMap<Date, f64>, today: Date) -> (u32, u32) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<Date> = None;
+    for day in days.keys().copied().filter(|day| *day <= today) {
+        run = match previous {
+            Some(prev) if prev.next_day() == Some(day) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(day);
+    }
+    let yesterday = today.previous_day();
+    let current = match previous {
+        Some(last) if last == today || Some(last) == yesterday => run,
+        _ => 0,
+    };
+    (current, longest)
+}
+
+fn period_totals(days: &BTreeMap<Date, f64>, period: impl Fn(Date) -> String) -> Vec<PeriodTotal> {
+    let mut totals: Vec<PeriodTotal> = Vec::new();
+    for (day, value) in days {
+        let key = period(*day);
+        match totals.last_mut() {
+            Some(last) if last.period == key => last.total += value,
+            _ => totals.push(PeriodTotal {
+                period: key,
+                total: *value,
+            }),
+        }
+    }
+    totals
+}
+
+/// Average over the last `window` days up to today. Cumulative goals count
+/// days without entries as zero; other goals average the days with entries.
+#[must_use]
+pub fn rolling_average(
+    days: &BTreeMap<Date, f64>,
+    options: &StatsOptions,
+    window: u32,
+) -> Option<RollingAverage> {
+    let start = options.today - Duration::days(i64::from(window) - 1);
+    let values: Vec<f64> = days
+        .range(start..=options.today)
+        .map(|(_, value)| *value)
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    let divisor = if options.cumulative {
+        f64::from(window)
+    } else {
+        f64::from(u32::try_from(values.len()).unwrap_or(u32::MAX))
+    };
+    Some(RollingAverage {
+        days: window,
+        average: values.iter().sum::<f64>() / divisor,
+    })
+}
+
+/// Least-squares slope of the daily values against the day number.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn linear_trend(days: &BTreeMap<Date, f64>) -> Option<f64> {
+    let first = *days.keys().next()?;
+    let points: Vec<(f64, f64)> = days
+        .iter()
+        .map(|(day, value)| ((*day - first).whole_days() as f64, *value))
+        .collect();
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if variance == 0.0 {
+        return None;
+    }
+    Some(covariance / variance)
+}
+
+/// Percentage of days from the first entry to today on the good side of a
+/// straight line starting at the first day's value with the daily rate.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn on_road_percent(days: &BTreeMap<Date, f64>, options: &StatsOptions) -> Option<f64> {
+    let rate = options.daily_rate?;
+    let (&first, &first_value) = days.iter().next()?;
+    if first > options.today {
+        return None;
+    }
+
+    let mut value = first_value;
+    let mut on_road = 0_u32;
+    let mut total = 0_u32;
+    let mut day = first;
+    loop {
+        if day != first {
+            if let Some(today_value) = days.get(&day) {
+                value = if options.cumulative {
+                    value + today_value
+                } else {
+                    *today_value
+                };
+            }
+        }
+        let elapsed = (day - first).whole_days() as f64;
+        let road = first_value + rate * elapsed;
+        let good = if options.yaw < 0 {
+            value <= road
+        } else {
+            value >= road
+        };
+        on_road += u32::from(good);
+        total += 1;
+
+        match day.next_day() {
+            Some(next) if next <= options.today => day = next,
+            _ => break,
+        }
+    }
+    Some(f64::from(on_road) * 100.0 / f64::from(total))
+}
+
+fn format_day(day: Date) -> String {
+    format!(
+        "{:04}-{:02}-{:02}",
+        day.year(),
+        u8::from(day.month()),
+        day.day()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::datapoint;
+    use time::Month;
+
+    fn point(daystamp: &str, timestamp: i64, value: f64) -> Datapoint {
+        Datapoint {
+            daystamp: daystamp.to_string(),
+            timestamp: OffsetDateTime::UNIX_EPOCH + Duration::seconds(timestamp),
+            ..datapoint(&format!("{daystamp}-{timestamp}"), value, "")
+        }
+    }
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    fn options(today: Date) -> StatsOptions {
+        StatsOptions {
+            aggday: Aggday::Sum,
+            cumulative: true,
+            daily_rate: Some(1.0),
+            yaw: 1,
+            today,
+        }
+    }
+
+    #[test]
+    fn aggregates_days_in_timestamp_order() {
+        let points = vec![
+            point("20260302", 30, 4.0),
+            point("20260302", 20, 1.0),
+            point("20260301", 10, 2.0),
+        ];
+        let with = |aggday| StatsOptions {
+            aggday,
+            ..options(date(2026, Month::March, 2))
+        };
+        let last = daily_values(&points, &with(Aggday::Last));
+        assert_eq!(last[&date(2026, Month::March, 2)], 4.0);
+        let first = daily_values(&points, &with(Aggday::First));
+        assert_eq!(first[&date(2026, Month::March, 2)], 1.0);
+        let sum = daily_values(&points, &with(Aggday::Sum));
+        assert_eq!(sum[&date(2026, Month::March, 2)], 5.0);
+        assert_eq!(sum[&date(2026, Month::March, 1)], 2.0);
+        assert_eq!(
+            Aggday::Uniqmean.aggregate(&[1.0, 1.0, 4.0], None),
+            Some(2.5)
+        );
+        assert_eq!("truemean".parse(), Ok(Aggday::Mean));
+        assert_eq!("mean".parse(), Ok(Aggday::Uniqmean));
+        assert!("fancy".parse::<Aggday>().is_err());
+    }
+
+    #[test]
+    fn aggregates_every_documented_method() {
+        let values = [3.0, 1.0, 3.0, 5.0];
+        let cases = [
+            ("median", 3.0),
+            ("mode", 3.0),
+            ("trimmean", 3.0),
+            ("jolly", 1.0),
+            ("triangle", 78.0),
+            ("square", 144.0),
+            ("clocky", 0.0),
+            ("kyshoc", 12.0),
+            ("skatesum", 10.0),
+            ("cap1", 1.0),
+        ];
+        for (name, expected) in cases {
+            let aggday: Aggday = name.parse().unwrap();
+            assert_eq!(
+                aggday.aggregate(&values, Some(10.0)),
+                Some(expected),
+                "{name}"
+            );
+        }
+        assert_eq!(
+            Aggday::Clocky.aggregate(&[9.0, 11.5, 14.0, 15.0], None),
+            Some(3.5)
+        );
+        assert_eq!(Aggday::Skatesum.aggregate(&values, None), Some(12.0));
+    }
+
+    #[test]
+    fn rejects_unsupported_aggday_methods() {
+        let goal = |aggday: &str| -> GoalFull {
+            serde_json::from_value(serde_json::json!({
+                "id": "g1",
+                "slug": "reading",
+                "aggday": aggday,
+            }))
+            .unwrap()
+        };
+        let today = date(2026, Month::March, 2);
+        let options = StatsOptions::for_goal(&goal("skatesum"), today).unwrap();
+        assert_eq!(options.aggday, Aggday::Skatesum);
+        let err = StatsOptions::for_goal(&goal("fancy"), today).unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported aggday 'fancy'");
+    }
+
+    #[test]
+    fn computes_streaks_allowing_a_missing_today() {
+        let points = vec![
+            point("20260301", 1, 1.0),
+            point("20260302", 2, 1.0),
+            point("20260303", 3, 1.0),
+            point("20260310", 4, 1.0),
+            point("20260311", 5, 1.0),
+        ];
+        let days = daily_values(&points, &options(date(2026, Month::March, 7)));
+        assert_eq!(streaks(&days, date(2026, Month::March, 12)), (2, 3));
+        assert_eq!(streaks(&days, date(2026, Month::March, 13)), (0, 3));
+    }
+
+    #[test]
+    fn totals_weeks_and_months() {
+        let points = vec![
+            point("20260330", 1, 1.0),
+            point("20260331", 2, 2.0),
+            point("20260401", 3, 3.0),
+            point("20260406", 4, 4.0),
+        ];
+        let stats = goal_stats(&points, &options(date(2026, Month::April, 6)));
+        assert_eq!(
+            stats.weekly_totals,
+            vec![
+                PeriodTotal {
+                    period: "2026-03-30".to_string(),
+                    total: 6.0
+                },
+                PeriodTotal {
+                    period: "2026-04-06".to_string(),
+                    total: 4.0
+                },
+            ]
+        );
+        assert_eq!(stats.monthly_totals.len(), 2);
+        assert_eq!(stats.monthly_totals[0].period, "2026-03");
+        assert_eq!(stats.monthly_totals[1].total, 7.0);
+        assert_eq!(stats.first_day.as_deref(), Some("2026-03-30"));
+        assert_eq!(stats.entry_days, 4);
+    }
+
+    #[test]
+    fn averages_trend_and_road() {
+        let points: Vec<Datapoint> = (1..=7)
+            .map(|day| point(&format!("202603{day:02}"), i64::from(day), f64::from(day)))
+            .collect();
+        let today = date(2026, Month::March, 7);
+        let stats = goal_stats(&points, &options(today));
+
+        assert_eq!(
+            stats.rolling_averages[0],
+            RollingAverage {
+                days: 7,
+                average: 4.0
+            }
+        );
+        assert_eq!(stats.trend_per_day, Some(1.0));
+        // Cumulative totals 1, 3, 6, ... stay above the line 1 + day.
+        assert_eq!(stats.on_road_percent, Some(100.0));
+
+        let behind = StatsOptions {
+            daily_rate: Some(10.0),
+            ..options(today)
+        };
+        let days = daily_values(&points, &options(date(2026, Month::March, 7)));
+        let percent = on_road_percent(&days, &behind).unwrap();
+        assert!(percent < 20.0, "{percent}");
+
+        let less_is_better = StatsOptions { yaw: -1, ..behind };
+        assert_eq!(on_road_percent(&days, &less_is_better), Some(100.0));
+    }
+}
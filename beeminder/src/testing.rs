@@ -0,0 +1,30 @@
+//! Datapoints for unit tests, shared with the other workspace crates through
+//! the `testing` feature.
+
+use crate::types::{Datapoint, DatapointFull};
+use time::{Duration, OffsetDateTime};
+
+/// When test datapoints are entered: 2026-07-01 02:00 UTC.
+pub const ENTERED_AT: i64 = 1_782_871_200;
+
+/// A datapoint entered at [`ENTERED_AT`] on day 20260630. Set other fields
+/// with struct update syntax.
+#[must_use]
+pub fn datapoint(id: &str, value: f64, comment: &str) -> Datapoint {
+    let at = OffsetDateTime::UNIX_EPOCH + Duration::seconds(ENTERED_AT);
+    Datapoint {
+        id: id.to_string(),
+        value,
+        timestamp: at,
+        daystamp: "20260630".to_string(),
+        comment: Some(comment.to_string()),
+        updated_at: at,
+        requestid: None,
+    }
+}
+
+/// The full API representation of `dp`.
+#[must_use]
+pub fn datapoint_full(dp: &Datapoint) -> DatapointFull {
+    serde_json::from_value(serde_json::to_value(dp).unwrap()).unwrap()
+}
@@ -8,7 +8,7 @@ use time::OffsetDateTime;
 
 /// Efficient datapoint representation with 7 commonly-needed fields.
 /// Use `DatapointFull` if you need all API fields.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Datapoint {
     /// A unique ID, used to identify a datapoint when deleting or editing it
     pub id: String,
@@ -332,12 +332,49 @@ pub struct GoalSummary {
     /// Unix timestamp of the last (explicitly entered) datapoint
     #[serde(with = "time::serde::timestamp")]
     pub lastday: OffsetDateTime,
+    /// Seconds offset from midnight for deadline
+    #[serde(default)]
+    pub deadline: i64,
+    /// Days before derailing to start reminders
+    #[serde(default)]
+    pub leadtime: i64,
+    /// Goal units, e.g. "hours" or "pages"
+    #[serde(default)]
+    pub gunits: String,
+    /// Whether to assume integer values
+    #[serde(default)]
+    pub integery: bool,
+    /// List of goal tags
+    #[serde(default)]
+    pub tags: Vec<String>,
 
     /// Catch-all for any additional fields from the API
     #[serde(flatten)]
     pub extra: HashMap<String, serde_json::Value>,
 }
 
+impl GoalSummary {
+    /// Whether the goal carries `tag`, ignoring case and a leading `#`.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        tag_matches(&self.tags, tag)
+    }
+}
+
+impl GoalFull {
+    /// Whether the goal carries `tag`, ignoring case and a leading `#`.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .as_deref()
+            .is_some_and(|tags| tag_matches(tags, tag))
+    }
+}
+
+fn tag_matches(tags: &[String], tag: &str) -> bool {
+    let tag = tag.trim().trim_start_matches('#');
+    tags.iter()
+        .any(|candidate| candidate.eq_ignore_ascii_case(tag))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserInfo {
     /// Username of the Beeminder account
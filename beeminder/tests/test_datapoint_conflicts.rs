@@ -0,0 +1,87 @@
+mod common;
+
+use beeminder::changeset::{Applied, Change, Changeset};
+use beeminder::types::{Datapoint, UpdateDatapoint};
+use beeminder::Error;
+use common::mock_server::BeeminderMock;
+
+fn loaded(id: &str, updated_at: i64) -> Datapoint {
+    serde_json::from_value(serde_json::json!({
+        "id": id,
+        "timestamp": 1700000000,
+        "daystamp": "20240101",
+        "value": 1.0,
+        "comment": "Morning workout",
+        "updated_at": updated_at,
+        "requestid": null
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_update_datapoint_checked_conflict() {
+    let mock = BeeminderMock::start().await;
+    mock.mount_fixture("datapoints/get_datapoints_updated_valid.json")
+        .await;
+
+    let client = mock.client();
+    let dp = loaded("dp1234567890", 1700000000);
+    let update = UpdateDatapoint::new(dp.id.clone()).with_value(2.0);
+    let err = client
+        .update_datapoint_checked("exercise", &dp, &update)
+        .await
+        .unwrap_err();
+
+    let Error::Conflict(conflict) = err else {
+        panic!("expected a conflict, got {err:?}");
+    };
+    assert_eq!(conflict.before.comment.as_deref(), Some("Morning workout"));
+    let current = conflict.current.expect("current version");
+    assert_eq!(current.comment.as_deref(), Some("Edited on the website"));
+}
+
+#[tokio::test]
+async fn test_delete_datapoint_checked_unchanged() {
+    let mock = BeeminderMock::start().await;
+    mock.mount_fixture("datapoints/get_datapoints_updated_valid.json")
+        .await;
+    mock.mount_fixture("datapoints/delete_datapoint_valid.json")
+        .await;
+
+    let client = mock.client();
+    let dp = loaded("dp0987654321", 1700003600);
+    let deleted = client
+        .delete_datapoint_checked("exercise", &dp)
+        .await
+        .unwrap();
+    assert_eq!(deleted.id, "dp0987654321");
+}
+
+#[tokio::test]
+async fn test_apply_checked_batched_skips_conflicts() {
+    let mock = BeeminderMock::start().await;
+    mock.mount_fixture("datapoints/get_datapoints_updated_valid.json")
+        .await;
+    mock.mount_fixture("datapoints/delete_datapoint_valid.json")
+        .await;
+
+    let client = mock.client();
+    let changeset = Changeset {
+        changes: vec![
+            Change::Delete(loaded("dp1234567890", 1700000000)),
+            Change::Delete(loaded("dp0987654321", 1700003600)),
+        ],
+        unknown_ids: Vec::new(),
+    };
+    let mut progress = Vec::new();
+    let result = changeset
+        .apply_checked_batched(&client, "exercise", 2, |done| progress.push(done))
+        .await
+        .unwrap();
+
+    assert_eq!(progress, [2]);
+    assert!(matches!(result.applied.as_slice(), [Applied::Deleted(dp)] if dp.id == "dp0987654321"));
+    let conflicts = result.conflicts();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].1.before.id, "dp1234567890");
+}
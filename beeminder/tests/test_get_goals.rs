@@ -14,4 +14,7 @@ async fn test_get_goals_valid() {
     assert_eq!(goals[0].slug, "exercise");
     assert_eq!(goals[0].title, "Daily Exercise");
     assert_eq!(goals[0].safebuf, 2);
+    assert_eq!(goals[0].tags, vec!["fitness", "daily"]);
+    assert!(goals[0].has_tag("#Fitness"));
+    assert!(!goals[0].has_tag("work"));
 }
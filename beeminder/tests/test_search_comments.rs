@@ -0,0 +1,106 @@
+mod common;
+
+use beeminder::search::{search_comments, CommentSearch};
+use common::mock_server::BeeminderMock;
+use serde_json::json;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Serves `body` for one `sort=daystamp` page of the reading goal, which
+/// must be requested `times` times.
+async fn mount_page(mock: &BeeminderMock, page: u32, times: u64, body: serde_json::Value) {
+    Mock::given(method("GET"))
+        .and(path("/api/v1/users/me/goals/reading/datapoints.json"))
+        .and(query_param("sort", "daystamp"))
+        .and(query_param("page", page.to_string()))
+        .respond_with(ResponseTemplate::new(200).set_body_json(body))
+        .expect(times)
+        .mount(&mock.server)
+        .await;
+}
+
+fn datapoint(id: usize, daystamp: &str) -> serde_json::Value {
+    json!({
+        "id": format!("dp_{id}"),
+        "timestamp": 1_704_445_200,
+        "daystamp": daystamp,
+        "value": 1.0,
+        "updated_at": 1_704_445_200,
+    })
+}
+
+#[tokio::test]
+async fn test_get_datapoints_since_trims_older_days() {
+    let mock = BeeminderMock::start().await;
+    mock.mount_fixture("datapoints/get_datapoints_page_valid.json")
+        .await;
+
+    let client = mock.client();
+    let datapoints = client
+        .get_datapoints_since("reading", Some("20240101"))
+        .await
+        .unwrap();
+
+    let ids: Vec<&str> = datapoints.iter().map(|dp| dp.id.as_str()).collect();
+    assert_eq!(ids, ["dp_reading_3", "dp_reading_2"]);
+}
+
+#[tokio::test]
+async fn test_get_datapoints_since_pages_until_past_since() {
+    let mock = BeeminderMock::start().await;
+    let full: Vec<_> = (0..300).map(|id| datapoint(id, "20240110")).collect();
+    mount_page(&mock, 1, 1, json!(full)).await;
+    let mut tail: Vec<_> = (300..600).map(|id| datapoint(id, "20240105")).collect();
+    tail[299] = datapoint(599, "20240104");
+    mount_page(&mock, 2, 1, json!(tail)).await;
+    // Page 2 already reaches past `since`, so page 3 is never requested.
+    mount_page(&mock, 3, 0, json!([datapoint(600, "20240103")])).await;
+
+    let client = mock.client();
+    let datapoints = client
+        .get_datapoints_since("reading", Some("20240105"))
+        .await
+        .unwrap();
+
+    assert_eq!(datapoints.len(), 599);
+    assert_eq!(datapoints[0].id, "dp_0");
+    assert_eq!(datapoints[598].id, "dp_598");
+}
+
+#[tokio::test]
+async fn test_search_comments_valid() {
+    let mock = BeeminderMock::start().await;
+    mock.mount_fixture("datapoints/get_datapoints_page_valid.json")
+        .await;
+
+    let client = mock.client();
+    let hits = search_comments(&client, &["reading".to_string()], None, |comment| {
+        comment.contains("Dune")
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].goal, "reading");
+    assert_eq!(hits[0].datapoint.comment.as_deref(), Some("ch 4 of Dune"));
+    assert_eq!(hits[1].datapoint.id, "dp_reading_1");
+}
+
+#[tokio::test]
+async fn test_comment_search_of_one_goal() {
+    let mock = BeeminderMock::start().await;
+    mock.mount_fixture("datapoints/get_datapoints_page_valid.json")
+        .await;
+
+    let client = mock.client();
+    let hits = CommentSearch::new("dune", true)
+        .unwrap()
+        .goal(Some("reading"))
+        .run(&client)
+        .await
+        .unwrap();
+
+    let ids: Vec<&str> = hits.iter().map(|hit| hit.datapoint.id.as_str()).collect();
+    assert_eq!(ids, ["dp_reading_3", "dp_reading_1"]);
+    assert!(CommentSearch::new("(", false).is_err());
+}
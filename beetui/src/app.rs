@@ -1,22 +1,43 @@
 //! Main application state and logic.
 
+use crate::command::{parse_commands, Command, CommandPalette};
+use crate::form::{CreateGoalState, SettingsState};
+use crate::keys::{Context, Keymap};
 use crate::state::{
-    clamp_index, DetailState, MainInput, Screen, StatusKind, StatusMessage, STATUS_TTL,
+    clamp_index, DetailState, GoalAction, GoalActionState, MainInput, SaveUndo, Screen, StatusKind,
+    StatusMessage, DIFF_SLACK, STATUS_TTL,
+};
+use crate::tasks::{TaskKind, TaskOutput, Tasks};
+use crate::theme::{Theme, Zone};
+use crate::ui::goal_pledge;
+use beeconfig::{format_timestamp, BeeConfig, GroupMode, SortMode};
+use beeminder::day::DayClock;
+use beeminder::entry::{self, EntryGoal, TotalEntry};
+use beeminder::types::{
+    CreateDatapoint, Datapoint, GoalFull, GoalSummary, UpdateGoal, UserInfoDiff,
 };
-use anyhow::{Context, Result};
-use beeconfig::BeeConfig;
-use beeminder::types::{CreateDatapoint, GoalSummary};
 use beeminder::BeeminderClient;
 use ratatui::widgets::TableState;
-use std::time::Instant;
-use time::{OffsetDateTime, UtcOffset};
-use tokio::runtime::Runtime;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tokio::runtime::Handle;
 
 /// Main application state.
 pub struct App {
     pub config: BeeConfig,
-    pub client: BeeminderClient,
+    pub keymap: Keymap,
+    pub theme: Theme,
+    /// Whether the key binding overlay is shown
+    pub show_help: bool,
+    pub client: Arc<BeeminderClient>,
+    pub tasks: Tasks,
     pub goals: Vec<GoalSummary>,
+    /// Archived goals, shown instead of `goals` while `show_archived` is set
+    pub archived: Vec<GoalSummary>,
+    pub show_archived: bool,
     pub filtered: Vec<usize>,
     pub filter: String,
     pub filter_backup: Option<String>,
@@ -25,14 +46,36 @@ pub struct App {
     pub screen: Screen,
     pub status: Option<StatusMessage>,
     pub last_success_goal: Option<(String, Instant)>,
+    /// Server time of the last successful refresh, for `get_user_diff`
+    pub synced_at: Option<OffsetDateTime>,
+    /// Account timezone deciding which day counts as today
+    pub clock: DayClock,
+    /// When the last refresh was requested, for auto-refresh scheduling
+    pub last_refresh: Instant,
+    /// How to revert the most recent editor save
+    pub last_save: Option<SaveUndo>,
+    /// Editor saves started so far, numbering `SaveUndo::attempt`
+    pub save_attempts: u64,
 }
 
 impl App {
-    pub fn new(config: BeeConfig, client: BeeminderClient) -> Self {
+    pub fn new(
+        config: BeeConfig,
+        keymap: Keymap,
+        theme: Theme,
+        client: BeeminderClient,
+        runtime: Handle,
+    ) -> Self {
         Self {
             config,
-            client,
+            keymap,
+            theme,
+            show_help: false,
+            client: Arc::new(client),
+            tasks: Tasks::new(runtime),
             goals: Vec::new(),
+            archived: Vec::new(),
+            show_archived: false,
             filtered: Vec::new(),
             filter: String::new(),
             filter_backup: None,
@@ -41,32 +84,647 @@ impl App {
             screen: Screen::Main,
             status: None,
             last_success_goal: None,
+            synced_at: None,
+            clock: DayClock::local(),
+            last_refresh: Instant::now(),
+            last_save: None,
+            save_attempts: 0,
         }
     }
 
-    pub fn refresh_goals(&mut self, runtime: &Runtime) -> Result<()> {
-        let mut goals = runtime
-            .block_on(self.client.get_goals())
-            .context("Failed to fetch goals")?;
-        goals.sort_by(|a, b| {
-            let today_cmp = has_entry_today(a).cmp(&has_entry_today(b));
-            if today_cmp != std::cmp::Ordering::Equal {
-                return today_cmp;
-            }
-            a.safebuf.cmp(&b.safebuf)
+    /// Which key bindings apply to the current screen.
+    pub const fn key_context(&self) -> Context {
+        match self.screen {
+            Screen::Main => Context::Main,
+            Screen::Detail(_) => Context::Detail,
+            Screen::CreateGoal(_) | Screen::Settings(_) => Context::Form,
+        }
+    }
+
+    /// Starts fetching goals in the background.
+    pub fn refresh_goals(&mut self) {
+        let client = Arc::clone(&self.client);
+        self.last_refresh = Instant::now();
+        let started = OffsetDateTime::now_utc();
+        self.tasks.spawn(TaskKind::RefreshGoals, async move {
+            let fetched = tokio::try_join!(client.get_goals(), client.get_user());
+            TaskOutput::Goals(fetched.map(|(goals, user)| (goals, user.timezone, started)))
         });
+    }
+
+    /// Starts fetching only goals changed since the last refresh.
+    fn refresh_diff(&mut self, since: OffsetDateTime) {
+        let client = Arc::clone(&self.client);
+        self.last_refresh = Instant::now();
+        let started = OffsetDateTime::now_utc();
+        self.tasks.spawn(TaskKind::RefreshDiff, async move {
+            TaskOutput::GoalsDiff(
+                client
+                    .get_user_diff(since - DIFF_SLACK)
+                    .await
+                    .map(|diff| (diff, started)),
+            )
+        });
+    }
+
+    /// Called every tick: schedules background refreshes.
+    pub fn tick(&mut self) {
+        let interval = self.config.tui.auto_refresh_secs;
+        if interval == 0
+            || self.tasks.is_busy()
+            || self.last_refresh.elapsed() < Duration::from_secs(interval)
+        {
+            return;
+        }
+        match self.synced_at {
+            Some(since) => self.refresh_diff(since),
+            None => self.refresh_goals(),
+        }
+    }
+
+    /// Switches to the account timezone.
+    fn set_timezone(&mut self, timezone: &str) {
+        self.clock = DayClock::for_user(timezone);
+    }
+
+    fn set_goals(&mut self, goals: Vec<GoalSummary>) {
+        let before = self.zones();
         self.goals = goals;
+        self.resort_goals();
+        self.flash_zone_changes(&before);
+    }
+
+    /// Merges changed goals from a diff. Returns false when the diff holds
+    /// goals that are not loaded yet or deletions, which need a full refresh.
+    fn apply_diff(&mut self, diff: UserInfoDiff) -> bool {
+        if !diff.deleted_goals.is_empty() {
+            return false;
+        }
+        let before = self.zones();
+        for full in diff.goals {
+            let Some(goal) = self.goals.iter_mut().find(|goal| goal.slug == full.slug) else {
+                return false;
+            };
+            merge_goal(goal, full);
+        }
+        self.resort_goals();
+        self.flash_zone_changes(&before);
+        true
+    }
+
+    fn zones(&self) -> HashMap<String, i32> {
+        self.goals
+            .iter()
+            .map(|goal| (goal.slug.clone(), goal.safebuf))
+            .collect()
+    }
+
+    fn flash_zone_changes(&mut self, before: &HashMap<String, i32>) {
+        let changes: Vec<String> = self
+            .goals
+            .iter()
+            .filter_map(|goal| {
+                let old = *before.get(&goal.slug)?;
+                let (old, new) = (Zone::of(old), Zone::of(goal.safebuf));
+                (old != new).then(|| format!("{} {} -> {}", goal.slug, old.name(), new.name()))
+            })
+            .collect();
+        if !changes.is_empty() {
+            self.set_status(StatusKind::Alert, changes.join(", "));
+        }
+    }
+
+    fn resort_goals(&mut self) {
+        let selected_slug = self.selected_goal().map(|goal| goal.slug.clone());
+        let sort = self.config.tui.sort;
+        let group = self.config.tui.group;
+        sort_goals(&mut self.goals, sort, group, &self.clock);
+        sort_goals(&mut self.archived, sort, group, &self.clock);
         self.refresh_filtered();
-        Ok(())
+        if let Some(slug) = selected_slug {
+            self.select_goal_by_slug(&slug);
+        }
+    }
+
+    /// Goals of the current view: active or archived.
+    pub fn visible_goals(&self) -> &[GoalSummary] {
+        if self.show_archived {
+            &self.archived
+        } else {
+            &self.goals
+        }
+    }
+
+    /// Reloads the goals of the current view.
+    pub fn refresh_view(&mut self) {
+        if self.show_archived {
+            self.load_archived();
+        } else {
+            self.refresh_goals();
+        }
+    }
+
+    fn load_archived(&mut self) {
+        let client = Arc::clone(&self.client);
+        self.tasks.spawn(TaskKind::LoadArchived, async move {
+            TaskOutput::ArchivedGoals(client.get_archived_goals().await)
+        });
+    }
+
+    /// Switches between active and archived goals.
+    pub fn toggle_archived(&mut self) {
+        self.show_archived = !self.show_archived;
+        self.main_state.select(Some(0));
+        self.refresh_filtered();
+        if self.show_archived {
+            self.load_archived();
+        }
+    }
+
+    /// Unarchives the selected goal of the archived view.
+    pub fn unarchive_selected(&mut self) {
+        if !self.show_archived {
+            self.set_status(
+                StatusKind::Info,
+                "Switch to archived goals to unarchive".to_string(),
+            );
+            return;
+        }
+        let Some(goal) = self.selected_goal() else {
+            self.set_status(StatusKind::Info, "No goal selected".to_string());
+            return;
+        };
+
+        let slug = goal.slug.clone();
+        self.set_archived(slug, false);
+    }
+
+    fn set_archived(&mut self, slug: String, archived: bool) {
+        let kind = if archived {
+            TaskKind::Archive { slug: slug.clone() }
+        } else {
+            TaskKind::Unarchive { slug: slug.clone() }
+        };
+        let client = Arc::clone(&self.client);
+        self.tasks.spawn(kind, async move {
+            let update = UpdateGoal {
+                archived: Some(archived),
+                ..UpdateGoal::new()
+            };
+            let verb = if archived { "Archived" } else { "Unarchived" };
+            let result = client
+                .update_goal(&slug, &update)
+                .await
+                .map(|_| format!("{verb} {slug}"));
+            TaskOutput::GoalChanged { slug, result }
+        });
+    }
+
+    /// Opens the confirmation modal for goal actions on the selected goal
+    /// and loads its contract.
+    pub fn open_goal_action(&mut self) {
+        let Some(goal) = self.selected_goal() else {
+            self.set_status(StatusKind::Info, "No goal selected".to_string());
+            return;
+        };
+
+        let slug = goal.slug.clone();
+        self.main_input = MainInput::GoalAction(Box::new(GoalActionState::new(slug.clone())));
+        let client = Arc::clone(&self.client);
+        self.tasks
+            .spawn(TaskKind::LoadGoal { slug: slug.clone() }, async move {
+                let result = client.get_goal_full(&slug, false).await;
+                TaskOutput::GoalLoaded { slug, result }
+            });
+    }
+
+    /// Runs the confirmed action of the goal action modal.
+    pub fn run_goal_action(&mut self, slug: String, action: GoalAction) {
+        let client = Arc::clone(&self.client);
+        let kind = TaskKind::GoalAction {
+            slug: slug.clone(),
+            action,
+        };
+        self.tasks.spawn(kind, async move {
+            let result = match action {
+                GoalAction::RefreshGraph => client.refresh_graph(&slug).await.map(|queued| {
+                    if queued {
+                        format!("Graph refresh queued for {slug}")
+                    } else {
+                        format!("Graph refresh not queued for {slug}")
+                    }
+                }),
+                GoalAction::Stepdown => client.stepdown(&slug).await.map(|goal| {
+                    let at = goal
+                        .contract
+                        .and_then(|contract| contract.stepdown_at)
+                        .map_or_else(String::new, |at| format!(" for {}", format_timestamp(at)));
+                    format!("Stepdown of {slug} scheduled{at}")
+                }),
+                GoalAction::CancelStepdown => client
+                    .cancel_stepdown(&slug)
+                    .await
+                    .map(|_| format!("Stepdown of {slug} cancelled")),
+                GoalAction::Shortcircuit => client.shortcircuit(&slug).await.map(|goal| {
+                    goal.pledge.map_or_else(
+                        || format!("Shortcircuited {slug}"),
+                        |pledge| format!("Shortcircuited {slug}; pledge is now ${pledge:.0}"),
+                    )
+                }),
+            };
+            TaskOutput::GoalChanged { slug, result }
+        });
+        self.main_input = MainInput::Normal;
+    }
+
+    /// Switches to the next sort mode and saves it to the config.
+    pub fn cycle_sort(&mut self) {
+        let modes = SortMode::VALUES;
+        let pos = modes
+            .iter()
+            .position(|mode| *mode == self.config.tui.sort)
+            .unwrap_or(0);
+        self.config.tui.sort = modes[(pos + 1) % modes.len()];
+        self.resort_goals();
+        self.store_view_config(format!("Sort: {}", self.config.tui.sort.label()));
+    }
+
+    /// Switches to the next grouping and saves it to the config.
+    pub fn cycle_group(&mut self) {
+        let modes = GroupMode::VALUES;
+        let pos = modes
+            .iter()
+            .position(|mode| *mode == self.config.tui.group)
+            .unwrap_or(0);
+        self.config.tui.group = modes[(pos + 1) % modes.len()];
+        self.resort_goals();
+        self.store_view_config(format!("Group: {}", self.config.tui.group.label()));
+    }
+
+    fn store_view_config(&mut self, message: String) {
+        match self.config.store() {
+            Ok(()) => self.set_status(StatusKind::Info, message),
+            Err(err) => self.set_status(
+                StatusKind::Error,
+                format!("{message} (failed to save config: {err})"),
+            ),
+        }
+    }
+
+    /// Applies results of finished background requests.
+    pub fn poll_tasks(&mut self) {
+        while let Some(output) = self.tasks.try_recv() {
+            self.handle_task_output(output);
+        }
+    }
+
+    fn handle_task_output(&mut self, output: TaskOutput) {
+        match output {
+            TaskOutput::Goals(Ok((goals, timezone, synced_at))) => {
+                self.set_timezone(&timezone);
+                self.set_goals(goals);
+                self.synced_at = Some(synced_at);
+                if !self.tasks.is_busy() && self.status.is_none() {
+                    self.set_status(StatusKind::Info, "Goals refreshed".to_string());
+                }
+            }
+            TaskOutput::GoalsDiff(Ok((diff, synced_at))) => {
+                self.set_timezone(&diff.timezone);
+                if self.apply_diff(diff) {
+                    self.synced_at = Some(synced_at);
+                } else {
+                    self.refresh_goals();
+                }
+            }
+            TaskOutput::Goals(Err(err)) | TaskOutput::GoalsDiff(Err(err)) => {
+                self.set_status(
+                    StatusKind::Error,
+                    format!("Failed to fetch goals: {}", err.format_for_display()),
+                );
+            }
+            TaskOutput::DatapointAdded { slug, result } => match result {
+                Ok(_) => {
+                    self.set_status(StatusKind::Success, format!("Added datapoint to {slug}"));
+                    self.last_success_goal = Some((slug.clone(), Instant::now()));
+                    self.select_goal_by_slug(&slug);
+                    self.refresh_goals();
+                }
+                Err(err) => {
+                    self.set_status(
+                        StatusKind::Error,
+                        format!("Failed to add datapoint to {slug}: {err}"),
+                    );
+                }
+            },
+            TaskOutput::TotalAdded { slug, result } => match result {
+                Ok((total, _)) => {
+                    self.set_status(
+                        StatusKind::Success,
+                        format!(
+                            "Added {} to {slug} ({} -> {})",
+                            total.value,
+                            total.previous,
+                            total.previous + total.delta
+                        ),
+                    );
+                    self.last_success_goal = Some((slug.clone(), Instant::now()));
+                    self.select_goal_by_slug(&slug);
+                    self.refresh_goals();
+                }
+                Err(err) => {
+                    self.set_status(
+                        StatusKind::Error,
+                        format!("Failed to add total to {slug}: {err}"),
+                    );
+                }
+            },
+            TaskOutput::DatapointsAdded(results) => {
+                let mut added = Vec::new();
+                let mut failed = Vec::new();
+                for (slug, result) in results {
+                    match result {
+                        Ok(_) => added.push(slug),
+                        Err(err) => failed.push(format!("{slug}: {}", err.format_for_display())),
+                    }
+                }
+                if let Some(last) = added.last() {
+                    self.last_success_goal = Some((last.clone(), Instant::now()));
+                    self.refresh_goals();
+                }
+                if failed.is_empty() {
+                    self.set_status(
+                        StatusKind::Success,
+                        format!("Added datapoints to {}", added.join(", ")),
+                    );
+                } else {
+                    self.set_status(
+                        StatusKind::Error,
+                        format!(
+                            "Added {} of {} datapoints; {}",
+                            added.len(),
+                            added.len() + failed.len(),
+                            failed.join("; ")
+                        ),
+                    );
+                }
+            }
+            TaskOutput::DetailLoaded { slug, result } => {
+                if !matches!(self.screen, Screen::Main) {
+                    return;
+                }
+                match result {
+                    Ok((goal, points)) => {
+                        self.screen = Screen::Detail(DetailState::from_goal(goal, points));
+                    }
+                    Err(err) => self.set_status(
+                        StatusKind::Error,
+                        format!("Failed to load {slug}: {}", err.format_for_display()),
+                    ),
+                }
+            }
+            TaskOutput::DetailSaved {
+                slug,
+                applied,
+                undo,
+                result,
+                conflicts,
+            } => {
+                let undoable = !undo.changeset.is_empty();
+                match self.last_save.as_mut() {
+                    Some(last) if last.attempt == undo.attempt => {
+                        last.changeset.changes.extend(undo.changeset.changes);
+                    }
+                    _ if undoable => self.last_save = Some(undo),
+                    _ => {}
+                }
+                let editing =
+                    matches!(&self.screen, Screen::Detail(detail) if detail.goal_slug == slug);
+                if let Screen::Detail(detail) = &mut self.screen {
+                    if editing {
+                        detail.rebase(&applied);
+                        detail.save_incomplete = result.is_err() || !conflicts.is_empty();
+                    }
+                }
+                let hint = if undoable {
+                    " (U on the goal list reverts the applied part)"
+                } else {
+                    ""
+                };
+                if !conflicts.is_empty() {
+                    let count = conflicts.len();
+                    let err = result.err().map(|err| err.to_string());
+                    let error = err
+                        .as_ref()
+                        .map(|err| format!("; {err}"))
+                        .unwrap_or_default();
+                    if let Screen::Detail(detail) = &mut self.screen {
+                        if editing {
+                            detail.saving = false;
+                            detail.conflicts = conflicts;
+                            detail.save_error = err;
+                            let kind = if error.is_empty() {
+                                StatusKind::Info
+                            } else {
+                                StatusKind::Error
+                            };
+                            self.set_status(
+                                kind,
+                                format!("{count} datapoint(s) were changed elsewhere{error}"),
+                            );
+                            return;
+                        }
+                    }
+                    // The editor was left while saving, so nobody can
+                    // resolve the conflicts and those changes stay unsaved.
+                    self.set_status(
+                        StatusKind::Error,
+                        format!(
+                            "{slug}: {count} datapoint(s) were changed elsewhere and not saved{error}{hint}"
+                        ),
+                    );
+                    self.refresh_goals();
+                    return;
+                }
+                match result {
+                    Ok(()) => {
+                        if editing {
+                            self.screen = Screen::Main;
+                        }
+                        self.set_status(
+                            StatusKind::Success,
+                            format!("Saved changes to {slug} (U: undo save)"),
+                        );
+                        self.refresh_goals();
+                    }
+                    Err(err) => {
+                        if let Screen::Detail(detail) = &mut self.screen {
+                            detail.saving = false;
+                        }
+                        self.set_status(StatusKind::Error, format!("{err}{hint}"));
+                    }
+                }
+            }
+            TaskOutput::ArchivedGoals(result) => match result {
+                Ok(goals) => {
+                    self.archived = goals;
+                    self.resort_goals();
+                }
+                Err(err) => self.set_status(
+                    StatusKind::Error,
+                    format!(
+                        "Failed to fetch archived goals: {}",
+                        err.format_for_display()
+                    ),
+                ),
+            },
+            TaskOutput::GoalLoaded { slug, result } => {
+                let MainInput::GoalAction(state) = &mut self.main_input else {
+                    return;
+                };
+                if state.slug != slug {
+                    return;
+                }
+                match result {
+                    Ok(goal) => state.goal = Some(goal),
+                    Err(err) => self.set_status(
+                        StatusKind::Error,
+                        format!("Failed to load {slug}: {}", err.format_for_display()),
+                    ),
+                }
+            }
+            TaskOutput::GoalChanged { slug, result } => {
+                match result {
+                    Ok(message) => {
+                        self.set_status(StatusKind::Success, message);
+                        self.last_success_goal = Some((slug, Instant::now()));
+                    }
+                    Err(err) => self.set_status(
+                        StatusKind::Error,
+                        format!("{slug}: {}", err.format_for_display()),
+                    ),
+                }
+                self.refresh_view();
+                if self.show_archived {
+                    self.refresh_goals();
+                }
+            }
+            TaskOutput::SaveUndone { slug, result } => {
+                match result {
+                    Ok(()) => {
+                        self.set_status(StatusKind::Success, format!("Undid last save of {slug}"));
+                    }
+                    Err(err) => self.set_status(
+                        StatusKind::Error,
+                        format!(
+                            "Failed to undo save of {slug}: {}",
+                            err.format_for_display()
+                        ),
+                    ),
+                }
+                self.refresh_goals();
+            }
+            TaskOutput::GoalCreated { slug, result } => match result {
+                Ok(_) => {
+                    if matches!(self.screen, Screen::CreateGoal(_)) {
+                        self.screen = Screen::Main;
+                    }
+                    self.set_status(StatusKind::Success, format!("Created goal {slug}"));
+                    self.last_success_goal = Some((slug, Instant::now()));
+                    self.refresh_goals();
+                }
+                Err(err) => {
+                    if let Screen::CreateGoal(wizard) = &mut self.screen {
+                        wizard.saving = false;
+                    }
+                    self.set_status(
+                        StatusKind::Error,
+                        format!("Failed to create {slug}: {}", err.format_for_display()),
+                    );
+                }
+            },
+            TaskOutput::SettingsLoaded { slug, result } => {
+                if !matches!(self.screen, Screen::Main) {
+                    return;
+                }
+                match result {
+                    Ok(goal) => self.screen = Screen::Settings(SettingsState::from_goal(&goal)),
+                    Err(err) => self.set_status(
+                        StatusKind::Error,
+                        format!("Failed to load {slug}: {}", err.format_for_display()),
+                    ),
+                }
+            }
+            TaskOutput::SettingsSaved { slug, result } => match result {
+                Ok(_) => {
+                    if matches!(&self.screen, Screen::Settings(settings) if settings.goal_slug == slug)
+                    {
+                        self.screen = Screen::Main;
+                    }
+                    self.set_status(StatusKind::Success, format!("Updated {slug}"));
+                    self.refresh_goals();
+                }
+                Err(err) => {
+                    if let Screen::Settings(settings) = &mut self.screen {
+                        settings.saving = false;
+                        settings.confirm = false;
+                    }
+                    self.set_status(
+                        StatusKind::Error,
+                        format!("Failed to update {slug}: {}", err.format_for_display()),
+                    );
+                }
+            },
+        }
     }
 
+    /// Aborts in-flight reads; writes can't be cancelled and are left to
+    /// finish. Returns false if nothing was running.
+    pub fn cancel_tasks(&mut self) -> bool {
+        let cancelled = self.tasks.cancel_reads();
+        let writes: Vec<String> = self
+            .tasks
+            .pending_writes()
+            .into_iter()
+            .map(TaskKind::label)
+            .collect();
+        if cancelled.is_empty() && writes.is_empty() {
+            return false;
+        }
+        let mut parts = Vec::new();
+        if !cancelled.is_empty() {
+            parts.push(format!(
+                "Cancelled: {}",
+                cancelled
+                    .iter()
+                    .map(TaskKind::label)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if !writes.is_empty() {
+            parts.push(format!("Can't cancel writes: {}", writes.join(", ")));
+        }
+        self.set_status(StatusKind::Info, parts.join("; "));
+        true
+    }
+
+    /// Applies the filter: `#tag` terms must all be goal tags, the remaining
+    /// text must appear in the slug or title.
     pub fn refresh_filtered(&mut self) {
-        let needle = self.filter.to_ascii_lowercase();
+        let (tags, words): (Vec<&str>, Vec<&str>) = self
+            .filter
+            .split_whitespace()
+            .partition(|term| term.starts_with('#'));
+        let tags: Vec<&str> = tags.into_iter().filter(|tag| tag.len() > 1).collect();
+        let needle = words.join(" ").to_ascii_lowercase();
         self.filtered = self
-            .goals
+            .visible_goals()
             .iter()
             .enumerate()
             .filter(|(_, goal)| {
+                if !tags.iter().all(|tag| goal.has_tag(tag)) {
+                    return false;
+                }
                 if needle.is_empty() {
                     return true;
                 }
@@ -93,18 +751,19 @@ impl App {
 
     pub fn selected_goal(&self) -> Option<&GoalSummary> {
         let idx = self.selected_goal_index()?;
-        self.goals.get(idx)
+        self.visible_goals().get(idx)
     }
 
-    pub fn select_goal_by_slug(&mut self, slug: &str) {
-        if let Some((pos, _)) = self
+    /// Selects the goal if it is shown; returns whether it was found.
+    pub fn select_goal_by_slug(&mut self, slug: &str) -> bool {
+        let found = self
             .filtered
             .iter()
-            .enumerate()
-            .find(|(_, idx)| self.goals.get(**idx).map(|g| g.slug.as_str()) == Some(slug))
-        {
+            .position(|idx| self.visible_goals().get(*idx).map(|g| g.slug.as_str()) == Some(slug));
+        if let Some(pos) = found {
             self.main_state.select(Some(pos));
         }
+        found.is_some()
     }
 
     pub fn set_status(&mut self, kind: StatusKind, text: String) {
@@ -155,77 +814,178 @@ impl App {
         }
     }
 
+    pub fn open_command_palette(&mut self) {
+        self.main_input = MainInput::Command(CommandPalette::new());
+    }
+
+    /// Slugs of active goals, used by the command palette.
+    pub fn goal_slugs(&self) -> Vec<&str> {
+        self.goals.iter().map(|goal| goal.slug.as_str()).collect()
+    }
+
+    /// Runs a command palette chain. Nothing runs if any command is
+    /// invalid; the palette then stays open for correction.
+    pub fn run_commands(&mut self, input: &str) {
+        let entry = |slug: &str, rest: &str| {
+            let goal = self
+                .goals
+                .iter()
+                .find(|goal| goal.slug == slug)
+                .ok_or_else(|| format!("Unknown goal '{slug}'"))?;
+            parse_entry(rest, goal, &self.clock)
+        };
+        let commands = match parse_commands(input, &self.goal_slugs(), &entry) {
+            Ok(commands) => commands,
+            Err(err) => {
+                self.set_status(StatusKind::Error, err);
+                return;
+            }
+        };
+        self.main_input = MainInput::Normal;
+
+        let mut entries = Vec::new();
+        for command in commands {
+            match command {
+                Command::Add { slug, datapoint } => entries.push((slug, datapoint)),
+                Command::Goto { slug } => self.goto_goal(&slug),
+                Command::Refresh => self.refresh_view(),
+                Command::Archive { slug } => self.set_archived(slug, true),
+            }
+        }
+        if entries.is_empty() {
+            return;
+        }
+
+        let slugs = entries.iter().map(|(slug, _)| slug.clone()).collect();
+        let client = Arc::clone(&self.client);
+        self.tasks
+            .spawn(TaskKind::AddDatapoints { slugs }, async move {
+                let mut results = Vec::new();
+                for (slug, dp) in entries {
+                    let result = client.create_datapoint(&slug, &dp).await;
+                    results.push((slug, result));
+                }
+                TaskOutput::DatapointsAdded(results)
+            });
+    }
+
+    /// Selects an active goal, leaving the archived view and clearing a
+    /// filter that hides it.
+    fn goto_goal(&mut self, slug: &str) {
+        if self.show_archived {
+            self.show_archived = false;
+            self.refresh_filtered();
+        }
+        if !self.select_goal_by_slug(slug) {
+            self.filter.clear();
+            self.refresh_filtered();
+            self.select_goal_by_slug(slug);
+        }
+    }
+
     pub fn cancel_inline_add(&mut self) {
         self.main_input = MainInput::Normal;
     }
 
-    pub fn submit_inline_add(&mut self, buffer: &str, runtime: &Runtime) {
+    pub fn submit_inline_add(&mut self, buffer: &str) {
         let Some(goal) = self.selected_goal() else {
             self.set_status(StatusKind::Info, "No goal selected".to_string());
             return;
         };
 
-        let parsed = parse_value_and_comment(buffer);
-        let (value, comment) = match parsed {
-            Ok(parsed) => parsed,
+        // A leading `=` enters the running total instead of the change.
+        let (buffer, total) = match buffer.trim_start().strip_prefix('=') {
+            Some(rest) => (rest, true),
+            None => (buffer, false),
+        };
+        let dp = match parse_entry(buffer, goal, &self.clock) {
+            Ok(dp) => dp,
             Err(err) => {
                 self.set_status(StatusKind::Error, err);
                 return;
             }
         };
 
-        let mut dp = CreateDatapoint::new(value);
-        if let Some(comment) = comment.as_deref() {
-            dp = dp.with_comment(comment);
-        }
-
         let slug = goal.slug.clone();
-        let result = runtime.block_on(self.client.create_datapoint(&slug, &dp));
-        match result {
-            Ok(_) => {
-                let refresh_result = self.refresh_goals(runtime);
-                if let Err(err) = refresh_result {
-                    self.set_status(
-                        StatusKind::Error,
-                        format!("Added datapoint to {slug}, but refresh failed: {err}"),
-                    );
-                } else {
-                    self.set_status(StatusKind::Success, format!("Added datapoint to {slug}"));
-                    self.last_success_goal = Some((slug.clone(), Instant::now()));
-                    self.select_goal_by_slug(&slug);
-                }
-                self.main_input = MainInput::Normal;
-            }
-            Err(err) => {
-                self.set_status(StatusKind::Error, err.to_string());
-            }
+        let client = Arc::clone(&self.client);
+        let kind = TaskKind::AddDatapoint { slug: slug.clone() };
+        if total {
+            self.tasks.spawn(kind, async move {
+                let result = add_total(&client, &slug, dp).await;
+                TaskOutput::TotalAdded { slug, result }
+            });
+        } else {
+            self.tasks.spawn(kind, async move {
+                let result = client.create_datapoint(&slug, &dp).await;
+                TaskOutput::DatapointAdded { slug, result }
+            });
         }
+        self.main_input = MainInput::Normal;
     }
 
-    pub fn open_detail(&mut self, runtime: &Runtime) {
+    pub fn open_detail(&mut self) {
         let Some(goal) = self.selected_goal() else {
             self.set_status(StatusKind::Info, "No goal selected".to_string());
             return;
         };
 
         let limit = self.config.display.datapoints_limit as u64;
-        let datapoints = runtime.block_on(self.client.get_datapoints(
-            &goal.slug,
-            Some("id"),
-            Some(limit),
-            None,
-            None,
-        ));
-
-        match datapoints {
-            Ok(points) => {
-                let detail = DetailState::from_datapoints(goal, points);
-                self.screen = Screen::Detail(detail);
-            }
-            Err(err) => {
-                self.set_status(StatusKind::Error, err.to_string());
-            }
-        }
+        let slug = goal.slug.clone();
+        let client = Arc::clone(&self.client);
+        self.tasks
+            .spawn(TaskKind::LoadDetail { slug: slug.clone() }, async move {
+                let result = tokio::try_join!(
+                    client.get_goal_full(&slug, false),
+                    client.get_datapoints(&slug, Some("id"), Some(limit), None, None),
+                );
+                TaskOutput::DetailLoaded { slug, result }
+            });
+    }
+
+    /// Reverts the most recent editor save: deletes created datapoints,
+    /// recreates deleted ones and restores the original values of updated ones.
+    pub fn undo_last_save(&mut self) {
+        let Some(undo) = self.last_save.take() else {
+            self.set_status(StatusKind::Info, "No save to undo".to_string());
+            return;
+        };
+
+        let slug = undo.slug.clone();
+        let client = Arc::clone(&self.client);
+        self.tasks
+            .spawn(TaskKind::UndoSave { slug: slug.clone() }, async move {
+                let result = undo
+                    .changeset
+                    .apply(&client, &slug)
+                    .await
+                    .failed
+                    .into_iter()
+                    .next()
+                    .map_or(Ok(()), |(_, err)| Err(err));
+                TaskOutput::SaveUndone { slug, result }
+            });
+    }
+
+    /// Opens the goal creation wizard.
+    pub fn open_create_goal(&mut self) {
+        let existing = self.goals.iter().map(|goal| goal.slug.clone()).collect();
+        self.screen = Screen::CreateGoal(CreateGoalState::new(existing));
+    }
+
+    /// Loads the selected goal's settings and opens the settings editor.
+    pub fn open_settings(&mut self) {
+        let Some(goal) = self.selected_goal() else {
+            self.set_status(StatusKind::Info, "No goal selected".to_string());
+            return;
+        };
+
+        let slug = goal.slug.clone();
+        let client = Arc::clone(&self.client);
+        self.tasks
+            .spawn(TaskKind::LoadSettings { slug: slug.clone() }, async move {
+                let result = client.get_goal_full(&slug, false).await;
+                TaskOutput::SettingsLoaded { slug, result }
+            });
     }
 
     pub fn move_main_selection(&mut self, delta: i32) {
@@ -239,29 +999,193 @@ impl App {
     }
 }
 
-/// Check if a goal has an entry today (in local time).
-pub fn has_entry_today(goal: &GoalSummary) -> bool {
+/// Copies the fields shown in the goal table from a diffed goal.
+fn merge_goal(goal: &mut GoalSummary, full: GoalFull) {
+    if let Some(title) = full.title {
+        goal.title = title;
+    }
+    if let Some(limsum) = full.limsum {
+        goal.limsum = limsum;
+    }
+    if let Some(losedate) = full.losedate {
+        goal.losedate = losedate;
+    }
+    if let Some(safebuf) = full.safebuf {
+        goal.safebuf = safebuf;
+    }
+    if let Some(updated_at) = full.updated_at {
+        goal.updated_at = updated_at;
+    }
+    if let Some(lastday) = full.lastday {
+        goal.lastday = lastday;
+    }
+    if let Some(deadline) = full.deadline {
+        goal.deadline = deadline;
+    }
+    if let Some(gunits) = full.gunits {
+        goal.gunits = gunits;
+    }
+    if let Some(integery) = full.integery {
+        goal.integery = integery;
+    }
+    if let Some(queued) = full.queued {
+        goal.queued = queued;
+    }
+    if let Some(tags) = full.tags {
+        goal.tags = tags;
+    }
+    goal.goaldate = full.goaldate.or(goal.goaldate);
+    goal.goalval = full.goalval.or(goal.goalval);
+    goal.rate = full.rate.or(goal.rate);
+    if let Some(pledge) = full.pledge {
+        goal.extra
+            .insert("pledge".to_string(), serde_json::Value::from(pledge));
+    }
+}
+
+fn sort_goals(goals: &mut [GoalSummary], sort: SortMode, group: GroupMode, clock: &DayClock) {
     let now = OffsetDateTime::now_utc();
-    let today_date = UtcOffset::current_local_offset()
-        .map_or_else(|_| now, |offset| now.to_offset(offset))
-        .date();
-    goal.lastday.date() == today_date
+    goals.sort_by(|a, b| {
+        group_rank(a, group)
+            .cmp(&group_rank(b, group))
+            .then_with(|| compare_goals(a, b, sort, clock, now))
+            .then_with(|| a.slug.cmp(&b.slug))
+    });
+}
+
+/// Orders two goals by the given sort mode.
+fn compare_goals(
+    a: &GoalSummary,
+    b: &GoalSummary,
+    sort: SortMode,
+    clock: &DayClock,
+    now: OffsetDateTime,
+) -> Ordering {
+    match sort {
+        SortMode::Safebuf => entered_on(a, clock, now)
+            .cmp(&entered_on(b, clock, now))
+            .then_with(|| a.safebuf.cmp(&b.safebuf)),
+        SortMode::Urgency => a.losedate.cmp(&b.losedate),
+        SortMode::Pledge => {
+            let pledge = |goal| goal_pledge(goal).unwrap_or(0.0);
+            pledge(b).total_cmp(&pledge(a))
+        }
+        SortMode::Slug => a.slug.cmp(&b.slug),
+        SortMode::LastEntry => a.lastday.cmp(&b.lastday),
+    }
+}
+
+/// Position of the goal's group; groups sort by rank, then by label.
+fn group_rank(goal: &GoalSummary, group: GroupMode) -> (i32, String) {
+    match group {
+        GroupMode::None => (0, String::new()),
+        GroupMode::Zone => (Zone::of(goal.safebuf) as i32, String::new()),
+        GroupMode::Tag => match first_tag(goal) {
+            Some(tag) => (0, tag),
+            None => (1, String::new()),
+        },
+    }
+}
+
+/// Label of the group a goal belongs to.
+pub fn group_label(goal: &GoalSummary, group: GroupMode) -> String {
+    match group {
+        GroupMode::None => String::new(),
+        GroupMode::Zone => Zone::of(goal.safebuf).name().to_string(),
+        GroupMode::Tag => {
+            first_tag(goal).map_or_else(|| "(untagged)".to_string(), |tag| format!("#{tag}"))
+        }
+    }
 }
 
-/// Parse input as "value [comment]".
-fn parse_value_and_comment(input: &str) -> Result<(f64, Option<String>), String> {
-    let trimmed = input.trim();
-    if trimmed.is_empty() {
+/// Alphabetically first tag; goals with several tags are grouped under it.
+fn first_tag(goal: &GoalSummary) -> Option<String> {
+    goal.tags.iter().min().cloned()
+}
+
+/// Check if a goal has an entry on its current Beeminder day.
+pub fn has_entry_today(goal: &GoalSummary, clock: &DayClock) -> bool {
+    entered_on(goal, clock, OffsetDateTime::now_utc())
+}
+
+/// Whether the goal's reminders have started, counting `leadtime` days
+/// back from its derailment.
+pub fn reminders_due(goal: &GoalSummary, clock: &DayClock) -> bool {
+    clock
+        .goal_day(goal.deadline, OffsetDateTime::now_utc())
+        .reminders_due(goal.losedate, goal.leadtime)
+}
+
+fn entered_on(goal: &GoalSummary, clock: &DayClock, now: OffsetDateTime) -> bool {
+    clock.goal_day(goal.deadline, now).contains(goal.lastday)
+}
+
+/// Fetches the goal and submits the datapoint that brings it to the total
+/// held in `dp.value`.
+async fn add_total(
+    client: &BeeminderClient,
+    slug: &str,
+    mut dp: CreateDatapoint,
+) -> Result<(TotalEntry, Datapoint), String> {
+    let goal = client
+        .get_goal_full(slug, false)
+        .await
+        .map_err(|err| err.format_for_display())?;
+    let total = entry::total_entry(&goal, dp.value).map_err(|err| err.to_string())?;
+    dp.value = total.value;
+    let created = client
+        .create_datapoint(slug, &dp)
+        .await
+        .map_err(|err| err.format_for_display())?;
+    Ok((total, created))
+}
+
+/// Parse input as "[@date [HH:MM]] value [comment]". The value and date use
+/// the shared entry grammar, and the comment may be wrapped in double
+/// quotes. A word with a colon right after the date is its time when a value
+/// follows it, so `@yesterday 08:30 5` is 5 at 08:30 yesterday.
+pub fn parse_entry(
+    input: &str,
+    goal: &GoalSummary,
+    clock: &DayClock,
+) -> Result<CreateDatapoint, String> {
+    let mut rest = input.trim();
+    let mut date = None;
+    if let Some(stripped) = rest.strip_prefix('@') {
+        let (word, mut tail) = stripped
+            .split_once(char::is_whitespace)
+            .map_or((stripped, ""), |(word, tail)| (word, tail.trim_start()));
+        let mut when = word.to_string();
+        if !word.contains(':') {
+            if let Some((time, after)) = tail.split_once(char::is_whitespace) {
+                if time.contains(':') {
+                    when = format!("{word} {time}");
+                    tail = after;
+                }
+            }
+        }
+        let today = clock.goal_day(goal.deadline, OffsetDateTime::now_utc());
+        date = Some(entry::parse_date(&when, &today).map_err(|err| err.to_string())?);
+        rest = tail.trim_start();
+    }
+    if rest.is_empty() {
         return Err("Enter a value".to_string());
     }
-    let mut parts = trimmed.splitn(2, |c: char| c.is_whitespace());
-    let value_str = parts.next().unwrap_or("");
-    let value = value_str
-        .parse::<f64>()
-        .map_err(|_| "Invalid value".to_string())?;
-    let comment = parts
-        .next()
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty());
-    Ok((value, comment))
+    let (value_str, comment) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let value =
+        entry::parse_value(value_str, &EntryGoal::from(goal)).map_err(|err| err.to_string())?;
+    let comment = comment.trim();
+    let comment = comment
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(comment);
+
+    let mut dp = CreateDatapoint::new(value);
+    if !comment.is_empty() {
+        dp = dp.with_comment(comment);
+    }
+    if let Some(date) = date {
+        dp = date.apply(dp);
+    }
+    Ok(dp)
 }
@@ -0,0 +1,230 @@
+//! Command palette: parsing, chaining and fuzzy completion.
+
+use crate::state::EditInput;
+use beeminder::types::CreateDatapoint;
+
+/// Command names offered for completion.
+pub const COMMANDS: [&str; 4] = ["add", "goto", "refresh", "archive"];
+
+/// Number of completion candidates shown below the palette.
+pub const MAX_CANDIDATES: usize = 8;
+
+/// Parses the `[@date [HH:MM]] value [comment]` part of an add for a goal.
+pub type EntryParser<'a> = &'a dyn Fn(&str, &str) -> Result<CreateDatapoint, String>;
+
+/// One parsed palette command.
+#[derive(Debug, Clone)]
+pub enum Command {
+    Add {
+        slug: String,
+        datapoint: CreateDatapoint,
+    },
+    Goto {
+        slug: String,
+    },
+    Refresh,
+    Archive {
+        slug: String,
+    },
+}
+
+/// State of the `:` prompt.
+#[derive(Debug)]
+pub struct CommandPalette {
+    pub input: EditInput,
+    completion: Option<Completion>,
+}
+
+/// Tab cycling position; dropped once the buffer is edited by hand.
+#[derive(Debug)]
+struct Completion {
+    base: String,
+    candidates: Vec<String>,
+    index: usize,
+}
+
+impl CommandPalette {
+    pub fn new() -> Self {
+        Self {
+            input: EditInput::new(String::new()),
+            completion: None,
+        }
+    }
+
+    /// Completes the word being typed, cycling through candidates on
+    /// repeated presses.
+    pub fn complete(&mut self, slugs: &[&str]) {
+        if let Some(completion) = &mut self.completion {
+            let current = format!(
+                "{}{}",
+                completion.base, completion.candidates[completion.index]
+            );
+            if current == self.input.buffer {
+                completion.index = (completion.index + 1) % completion.candidates.len();
+                let next = format!(
+                    "{}{}",
+                    completion.base, completion.candidates[completion.index]
+                );
+                self.input = EditInput::new(next);
+                return;
+            }
+        }
+
+        let base = split_current_word(&self.input.buffer).0.to_string();
+        let candidates = completions(&self.input.buffer, slugs);
+        let Some(first) = candidates.first() else {
+            self.completion = None;
+            return;
+        };
+        self.input = EditInput::new(format!("{base}{first}"));
+        self.completion = Some(Completion {
+            base,
+            candidates,
+            index: 0,
+        });
+    }
+}
+
+/// Parses a `;`-separated chain of commands. A segment starting with a goal
+/// slug is shorthand for `add`. Fails on the first invalid segment so that
+/// nothing runs.
+pub fn parse_commands(
+    input: &str,
+    slugs: &[&str],
+    entry: EntryParser,
+) -> Result<Vec<Command>, String> {
+    let commands = split_chain(input)
+        .into_iter()
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            parse_command(segment, slugs, entry).map_err(|err| format!("{segment}: {err}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if commands.is_empty() {
+        return Err("Enter a command".to_string());
+    }
+    Ok(commands)
+}
+
+fn parse_command(segment: &str, slugs: &[&str], entry: EntryParser) -> Result<Command, String> {
+    let (head, rest) = split_word(segment);
+    match head {
+        "add" => {
+            let (slug, rest) = split_word(rest);
+            parse_add(known_slug(slug, slugs)?, rest, entry)
+        }
+        "goto" => Ok(Command::Goto {
+            slug: known_slug(rest, slugs)?,
+        }),
+        "archive" => Ok(Command::Archive {
+            slug: known_slug(rest, slugs)?,
+        }),
+        "refresh" if rest.is_empty() => Ok(Command::Refresh),
+        "refresh" => Err("refresh takes no arguments".to_string()),
+        slug if slugs.contains(&slug) => parse_add(slug.to_string(), rest, entry),
+        other => Err(format!("Unknown command or goal '{other}'")),
+    }
+}
+
+fn parse_add(slug: String, rest: &str, entry: EntryParser) -> Result<Command, String> {
+    let datapoint = entry(&slug, rest)?;
+    Ok(Command::Add { slug, datapoint })
+}
+
+fn known_slug(slug: &str, slugs: &[&str]) -> Result<String, String> {
+    let slug = slug.trim();
+    if slug.is_empty() {
+        Err("Missing goal".to_string())
+    } else if slugs.contains(&slug) {
+        Ok(slug.to_string())
+    } else {
+        Err(format!("Unknown goal '{slug}'"))
+    }
+}
+
+/// Splits on `;` outside double quotes.
+fn split_chain(input: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (idx, c) in input.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                segments.push(&input[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    segments.push(&input[start..]);
+    segments
+}
+
+fn split_word(input: &str) -> (&str, &str) {
+    let input = input.trim_start();
+    match input.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim_start()),
+        None => (input, ""),
+    }
+}
+
+/// Splits the buffer into everything before the word being typed and that
+/// word.
+fn split_current_word(buffer: &str) -> (&str, &str) {
+    let start = buffer
+        .rfind(|c: char| c.is_whitespace() || c == ';')
+        .map_or(0, |idx| idx + 1);
+    buffer.split_at(start)
+}
+
+/// Candidates for the word being typed, best match first.
+pub fn completions(buffer: &str, slugs: &[&str]) -> Vec<String> {
+    let segment = split_chain(buffer).pop().unwrap_or_default();
+    let (_, word) = split_current_word(buffer);
+    let previous: Vec<&str> = segment[..segment.len() - word.len()]
+        .split_whitespace()
+        .collect();
+
+    let pool: Vec<&str> = match previous.as_slice() {
+        [] => COMMANDS.iter().chain(slugs).copied().collect(),
+        ["add" | "goto" | "archive"] => slugs.to_vec(),
+        _ => return Vec::new(),
+    };
+
+    let mut scored: Vec<(usize, &str)> = pool
+        .into_iter()
+        .filter_map(|candidate| fuzzy_score(word, candidate).map(|score| (score, candidate)))
+        .collect();
+    scored.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then_with(|| a.1.len().cmp(&b.1.len()))
+            .then_with(|| a.1.cmp(b.1))
+    });
+    scored.dedup_by(|a, b| a.1 == b.1);
+    scored
+        .into_iter()
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Scores `candidate` if it contains the characters of `query` in order;
+/// lower is better, prefixes score 0.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let query = query.to_lowercase();
+    let candidate = candidate.to_lowercase();
+    if candidate.starts_with(&query) {
+        return Some(0);
+    }
+    let mut chars = candidate.char_indices();
+    let mut first = None;
+    let mut last = 0;
+    for q in query.chars() {
+        let (idx, _) = chars.by_ref().find(|(_, c)| *c == q)?;
+        first.get_or_insert(idx);
+        last = idx;
+    }
+    let span = last - first.unwrap_or(0) + 1;
+    Some(1 + span - query.chars().count().min(span))
+}
@@ -0,0 +1,625 @@
+//! Form screens: the goal creation wizard and the goal settings editor.
+
+use crate::state::{clamp_index, EditInput};
+use beeminder::types::{CreateGoal, GoalFull, GoalType, UpdateGoal};
+use ratatui::widgets::TableState;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime, UtcOffset};
+
+const RUNITS: &[&str] = &["d", "w", "m", "y", "h"];
+const GOAL_TYPES: &[&str] = &[
+    "hustler", "biker", "fatloser", "gainer", "inboxer", "drinker", "custom",
+];
+const BOOLS: &[&str] = &["no", "yes"];
+
+/// How a field's value is entered and validated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Text,
+    /// Optional number
+    Number,
+    /// Optional YYYY-MM-DD date
+    Date,
+    /// Cycled with Enter/Space instead of typed
+    Choice(&'static [&'static str]),
+}
+
+/// A labelled form field holding its value as text.
+#[derive(Debug, Clone)]
+pub struct FormField {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub help: &'static str,
+    pub kind: FieldKind,
+    pub value: String,
+}
+
+impl FormField {
+    const fn new(
+        key: &'static str,
+        label: &'static str,
+        kind: FieldKind,
+        help: &'static str,
+    ) -> Self {
+        Self {
+            key,
+            label,
+            help,
+            kind,
+            value: String::new(),
+        }
+    }
+
+    fn with_value(mut self, value: impl Into<String>) -> Self {
+        self.value = value.into();
+        self
+    }
+}
+
+/// A list of fields with a selection and an optional text input.
+#[derive(Debug)]
+pub struct Form {
+    pub fields: Vec<FormField>,
+    pub table_state: TableState,
+    pub input: Option<EditInput>,
+}
+
+impl Form {
+    fn new(fields: Vec<FormField>) -> Self {
+        let mut table_state = TableState::default();
+        if !fields.is_empty() {
+            table_state.select(Some(0));
+        }
+        Self {
+            fields,
+            table_state,
+            input: None,
+        }
+    }
+
+    pub fn selected_field(&self) -> Option<&FormField> {
+        self.fields.get(self.table_state.selected()?)
+    }
+
+    pub fn move_field(&mut self, delta: i32) {
+        if self.fields.is_empty() {
+            return;
+        }
+        let selected = self.table_state.selected().unwrap_or(0);
+        let max = self.fields.len().saturating_sub(1);
+        self.table_state
+            .select(Some(clamp_index(selected, delta, max)));
+    }
+
+    /// Opens the text input for the selected field, or cycles a choice.
+    pub fn activate(&mut self) {
+        let Some(idx) = self.table_state.selected() else {
+            return;
+        };
+        let Some(field) = self.fields.get_mut(idx) else {
+            return;
+        };
+        if let FieldKind::Choice(choices) = field.kind {
+            let pos = choices.iter().position(|choice| *choice == field.value);
+            let next = pos.map_or(0, |pos| (pos + 1) % choices.len());
+            field.value = choices[next].to_string();
+        } else {
+            self.input = Some(EditInput::new(field.value.clone()));
+        }
+    }
+
+    /// Validates and stores the text input into the selected field.
+    pub fn apply_input(&mut self, buffer: &str) -> Result<(), String> {
+        let Some(field) = self
+            .table_state
+            .selected()
+            .and_then(|idx| self.fields.get_mut(idx))
+        else {
+            return Ok(());
+        };
+        let value = buffer.trim();
+        match field.kind {
+            FieldKind::Number if !value.is_empty() => {
+                value
+                    .parse::<f64>()
+                    .map_err(|_| format!("{}: invalid number", field.label))?;
+            }
+            FieldKind::Date if !value.is_empty() => {
+                parse_day(value).map_err(|err| format!("{}: {err}", field.label))?;
+            }
+            _ => {}
+        }
+        field.value = value.to_string();
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|field| field.key == key)
+            .map(|field| field.value.as_str())
+    }
+}
+
+/// One page of the creation wizard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WizardStep {
+    Basics,
+    Commitment,
+    Details,
+    Review,
+}
+
+impl WizardStep {
+    pub const VALUES: [Self; 4] = [Self::Basics, Self::Commitment, Self::Details, Self::Review];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Basics => "Basics",
+            Self::Commitment => "Commitment",
+            Self::Details => "Details",
+            Self::Review => "Review",
+        }
+    }
+}
+
+/// State of the goal creation wizard.
+#[derive(Debug)]
+pub struct CreateGoalState {
+    pub step: WizardStep,
+    pub basics: Form,
+    pub commitment: Form,
+    pub details: Form,
+    /// Slugs of existing goals, to reject duplicates early
+    pub existing: Vec<String>,
+    pub saving: bool,
+}
+
+impl CreateGoalState {
+    pub fn new(existing: Vec<String>) -> Self {
+        Self {
+            step: WizardStep::Basics,
+            basics: Form::new(vec![
+                FormField::new(
+                    "slug",
+                    "Slug",
+                    FieldKind::Text,
+                    "Goal URL name: letters, digits, - and _",
+                ),
+                FormField::new("title", "Title", FieldKind::Text, "Short description"),
+                FormField::new(
+                    "goal_type",
+                    "Goal type",
+                    FieldKind::Choice(GOAL_TYPES),
+                    "hustler: do more, biker: odometer, fatloser/gainer: weight, inboxer: do less of a total, drinker: do less",
+                )
+                .with_value("hustler"),
+                FormField::new(
+                    "gunits",
+                    "Units",
+                    FieldKind::Text,
+                    "What you count, e.g. pages or hours",
+                ),
+            ]),
+            commitment: Form::new(vec![
+                FormField::new(
+                    "rate",
+                    "Rate",
+                    FieldKind::Number,
+                    "Slope of the bright red line; set two of rate, goal value and goal date",
+                ),
+                FormField::new(
+                    "runits",
+                    "Rate units",
+                    FieldKind::Choice(RUNITS),
+                    "d/w/m/y/h: per day, week, month, year or hour",
+                )
+                .with_value("w"),
+                FormField::new(
+                    "goalval",
+                    "Goal value",
+                    FieldKind::Number,
+                    "Value the bright red line reaches at the end",
+                ),
+                FormField::new(
+                    "goaldate",
+                    "Goal date",
+                    FieldKind::Date,
+                    "YYYY-MM-DD when the bright red line ends",
+                ),
+                FormField::new(
+                    "initval",
+                    "Start value",
+                    FieldKind::Number,
+                    "Initial value (default 0)",
+                ),
+            ]),
+            details: Form::new(vec![
+                FormField::new(
+                    "fineprint",
+                    "Fine print",
+                    FieldKind::Text,
+                    "What exactly you are committing to",
+                ),
+                FormField::new("yaxis", "Y-axis", FieldKind::Text, "Graph y-axis label"),
+                FormField::new(
+                    "secret",
+                    "Secret",
+                    FieldKind::Choice(BOOLS),
+                    "Require login to view the goal",
+                )
+                .with_value("no"),
+                FormField::new(
+                    "datapublic",
+                    "Public data",
+                    FieldKind::Choice(BOOLS),
+                    "Allow anyone to see the datapoints",
+                )
+                .with_value("no"),
+            ]),
+            existing,
+            saving: false,
+        }
+    }
+
+    /// The form of the current step; the review step has none.
+    pub const fn form(&self) -> Option<&Form> {
+        match self.step {
+            WizardStep::Basics => Some(&self.basics),
+            WizardStep::Commitment => Some(&self.commitment),
+            WizardStep::Details => Some(&self.details),
+            WizardStep::Review => None,
+        }
+    }
+
+    pub fn form_mut(&mut self) -> Option<&mut Form> {
+        match self.step {
+            WizardStep::Basics => Some(&mut self.basics),
+            WizardStep::Commitment => Some(&mut self.commitment),
+            WizardStep::Details => Some(&mut self.details),
+            WizardStep::Review => None,
+        }
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.form().is_some_and(|form| form.input.is_some())
+    }
+
+    /// Moves to the next step if the current one is valid.
+    pub fn next_step(&mut self) -> Result<(), String> {
+        self.validate_step(self.step)?;
+        let pos = WizardStep::VALUES
+            .iter()
+            .position(|step| *step == self.step)
+            .unwrap_or(0);
+        if let Some(next) = WizardStep::VALUES.get(pos + 1) {
+            self.step = *next;
+        }
+        Ok(())
+    }
+
+    pub fn previous_step(&mut self) {
+        let pos = WizardStep::VALUES
+            .iter()
+            .position(|step| *step == self.step)
+            .unwrap_or(0);
+        self.step = WizardStep::VALUES[pos.saturating_sub(1)];
+    }
+
+    fn validate_step(&self, step: WizardStep) -> Result<(), String> {
+        match step {
+            WizardStep::Basics => {
+                let slug = self.basics.get("slug").unwrap_or_default();
+                if slug.is_empty() {
+                    return Err("Slug is required".to_string());
+                }
+                if !slug
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                {
+                    return Err("Slug may only contain letters, digits, - and _".to_string());
+                }
+                if self.existing.iter().any(|existing| existing == slug) {
+                    return Err(format!("Goal '{slug}' already exists"));
+                }
+                if self.basics.get("title").unwrap_or_default().is_empty() {
+                    return Err("Title is required".to_string());
+                }
+                if self.basics.get("gunits").unwrap_or_default().is_empty() {
+                    return Err("Units are required".to_string());
+                }
+                Ok(())
+            }
+            WizardStep::Commitment => self.road().map(|_| ()),
+            WizardStep::Details | WizardStep::Review => Ok(()),
+        }
+    }
+
+    fn road(&self) -> Result<Road, String> {
+        let form = &self.commitment;
+        let number = |key| {
+            form.get(key)
+                .filter(|value| !value.is_empty())
+                .map(str::parse::<f64>)
+                .transpose()
+                .map_err(|_| format!("Invalid {key}"))
+        };
+        let goaldate = form
+            .get("goaldate")
+            .filter(|value| !value.is_empty())
+            .map(parse_day)
+            .transpose()?;
+        let road = Road {
+            rate: number("rate")?,
+            runits: form.get("runits").unwrap_or("d").to_string(),
+            goalval: number("goalval")?,
+            goaldate,
+            initval: number("initval")?.unwrap_or(0.0),
+        };
+        let set = u8::from(road.rate.is_some())
+            + u8::from(road.goalval.is_some())
+            + u8::from(road.goaldate.is_some());
+        if set != 2 {
+            return Err("Set exactly two of: rate, goal value, goal date".to_string());
+        }
+        if road.goaldate.is_some_and(|date| date <= local_today()) {
+            return Err("Goal date must be in the future".to_string());
+        }
+        Ok(road)
+    }
+
+    /// Describes the bright red line the commitment fields produce.
+    pub fn rate_preview(&self) -> Result<String, String> {
+        let gunits = self.basics.get("gunits").unwrap_or_default();
+        self.road()?.preview(gunits, local_today())
+    }
+
+    /// Validates every step and builds the API request.
+    pub fn build(&self) -> Result<CreateGoal, String> {
+        for step in WizardStep::VALUES {
+            self.validate_step(step)?;
+        }
+        let text = |form: &Form, key| {
+            form.get(key)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let goal_type = self
+            .basics
+            .get("goal_type")
+            .unwrap_or_default()
+            .parse::<GoalType>()
+            .map_err(|err| err.to_string())?;
+        let road = self.road()?;
+        let mut goal = CreateGoal::new(
+            self.basics.get("slug").unwrap_or_default(),
+            self.basics.get("title").unwrap_or_default(),
+            goal_type,
+        );
+        goal.gunits = text(&self.basics, "gunits");
+        goal.rate = road.rate;
+        goal.runits = Some(road.runits);
+        goal.goalval = road.goalval;
+        goal.goaldate = road.goaldate.map(day_timestamp);
+        goal.initval = text(&self.commitment, "initval").and(Some(road.initval));
+        goal.fineprint = text(&self.details, "fineprint");
+        goal.yaxis = text(&self.details, "yaxis");
+        goal.secret = Some(self.details.get("secret") == Some("yes"));
+        goal.datapublic = Some(self.details.get("datapublic") == Some("yes"));
+        Ok(goal)
+    }
+
+    /// Lines summarizing all entered values for the review step.
+    pub fn summary(&self) -> Vec<(&'static str, String)> {
+        [&self.basics, &self.commitment, &self.details]
+            .into_iter()
+            .flat_map(|form| form.fields.iter())
+            .map(|field| {
+                let value = if field.value.is_empty() {
+                    "-".to_string()
+                } else {
+                    field.value.clone()
+                };
+                (field.label, value)
+            })
+            .collect()
+    }
+}
+
+/// Two of rate, goal value and goal date plus the start value.
+struct Road {
+    rate: Option<f64>,
+    runits: String,
+    goalval: Option<f64>,
+    goaldate: Option<Date>,
+    initval: f64,
+}
+
+impl Road {
+    fn preview(&self, gunits: &str, today: Date) -> Result<String, String> {
+        let unit_days = runit_days(&self.runits)?;
+        let unit = runit_name(&self.runits);
+        let days_until = |date: Date| (date - today).whole_days() as f64;
+        let (rate, goalval, goaldate) = match (self.rate, self.goalval, self.goaldate) {
+            (Some(rate), Some(goalval), None) => {
+                let delta = goalval - self.initval;
+                if rate == 0.0 || delta.signum() != rate.signum() {
+                    return Ok(format!(
+                        "{rate} {gunits} per {unit} never reaches {goalval} from {}",
+                        self.initval
+                    ));
+                }
+                #[allow(clippy::cast_possible_truncation)]
+                let days = (delta / rate * unit_days).ceil() as i64;
+                (rate, goalval, today + time::Duration::days(days))
+            }
+            (Some(rate), None, Some(goaldate)) => {
+                let goalval = self.initval + rate * days_until(goaldate) / unit_days;
+                (rate, goalval, goaldate)
+            }
+            (None, Some(goalval), Some(goaldate)) => {
+                let rate = (goalval - self.initval) / days_until(goaldate) * unit_days;
+                (rate, goalval, goaldate)
+            }
+            _ => return Err("Set exactly two of: rate, goal value, goal date".to_string()),
+        };
+        Ok(format!(
+            "{rate:.2} {gunits} per {unit} ({:.2}/day), reaching {goalval:.2} on {goaldate}",
+            rate / unit_days
+        ))
+    }
+}
+
+fn runit_days(runits: &str) -> Result<f64, String> {
+    match runits {
+        "d" => Ok(1.0),
+        "w" => Ok(7.0),
+        "m" => Ok(30.4375),
+        "y" => Ok(365.25),
+        "h" => Ok(1.0 / 24.0),
+        other => Err(format!("Invalid rate units '{other}'")),
+    }
+}
+
+const fn runit_name(runits: &str) -> &'static str {
+    match runits.as_bytes() {
+        b"w" => "week",
+        b"m" => "month",
+        b"y" => "year",
+        b"h" => "hour",
+        _ => "day",
+    }
+}
+
+/// State of the goal settings editor.
+#[derive(Debug)]
+pub struct SettingsState {
+    pub goal_slug: String,
+    pub form: Form,
+    /// Values as loaded, in field order
+    pub original: Vec<String>,
+    pub confirm: bool,
+    pub saving: bool,
+}
+
+impl SettingsState {
+    pub fn from_goal(goal: &GoalFull) -> Self {
+        let yes_no = |value: Option<bool>| if value == Some(true) { "yes" } else { "no" };
+        let form = Form::new(vec![
+            FormField::new("title", "Title", FieldKind::Text, "Short description")
+                .with_value(goal.title.clone().unwrap_or_default()),
+            FormField::new(
+                "rate",
+                "Rate",
+                FieldKind::Number,
+                "New slope of the bright red line, after the akrasia horizon",
+            )
+            .with_value(goal.rate.map(|rate| rate.to_string()).unwrap_or_default()),
+            FormField::new(
+                "runits",
+                "Rate units",
+                FieldKind::Choice(RUNITS),
+                "d/w/m/y/h: per day, week, month, year or hour",
+            )
+            .with_value(goal.runits.clone().unwrap_or_else(|| "d".to_string())),
+            FormField::new(
+                "fineprint",
+                "Fine print",
+                FieldKind::Text,
+                "What exactly you are committing to",
+            )
+            .with_value(goal.fineprint.clone().unwrap_or_default()),
+            FormField::new(
+                "secret",
+                "Secret",
+                FieldKind::Choice(BOOLS),
+                "Require login to view the goal",
+            )
+            .with_value(yes_no(goal.secret)),
+            FormField::new(
+                "datapublic",
+                "Public data",
+                FieldKind::Choice(BOOLS),
+                "Allow anyone to see the datapoints",
+            )
+            .with_value(yes_no(goal.datapublic)),
+            FormField::new(
+                "archived",
+                "Archived",
+                FieldKind::Choice(BOOLS),
+                "Archive the goal; only possible outside the akrasia horizon",
+            )
+            .with_value("no"),
+        ]);
+        let original = form
+            .fields
+            .iter()
+            .map(|field| field.value.clone())
+            .collect();
+        Self {
+            goal_slug: goal.slug.clone(),
+            form,
+            original,
+            confirm: false,
+            saving: false,
+        }
+    }
+
+    /// Changed fields as (label, old, new).
+    pub fn changes(&self) -> Vec<(&'static str, &str, &str)> {
+        self.form
+            .fields
+            .iter()
+            .zip(&self.original)
+            .filter(|(field, original)| field.value != **original)
+            .map(|(field, original)| (field.label, original.as_str(), field.value.as_str()))
+            .collect()
+    }
+
+    /// Builds an update holding only the changed fields.
+    pub fn build(&self) -> Result<UpdateGoal, String> {
+        let mut update = UpdateGoal::new();
+        for (field, original) in self.form.fields.iter().zip(&self.original) {
+            if field.value == *original {
+                continue;
+            }
+            let value = field.value.clone();
+            let flag = value == "yes";
+            match field.key {
+                "title" => update.title = Some(value),
+                "rate" => {
+                    let rate = value
+                        .parse::<f64>()
+                        .map_err(|_| "Rate cannot be cleared".to_string())?;
+                    update.rate = Some(rate);
+                }
+                "runits" => update.runits = Some(value),
+                "fineprint" => update.fineprint = Some(value),
+                "secret" => update.secret = Some(flag),
+                "datapublic" => update.datapublic = Some(flag),
+                "archived" => update.archived = Some(flag),
+                _ => {}
+            }
+        }
+        Ok(update)
+    }
+}
+
+fn parse_day(value: &str) -> Result<Date, String> {
+    Date::parse(value, format_description!("[year]-[month]-[day]"))
+        .map_err(|_| format!("invalid date '{value}'; expected YYYY-MM-DD"))
+}
+
+fn local_offset() -> UtcOffset {
+    UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC)
+}
+
+fn local_today() -> Date {
+    OffsetDateTime::now_utc().to_offset(local_offset()).date()
+}
+
+/// Local noon of `day`, so the date survives any timezone shift.
+fn day_timestamp(day: Date) -> OffsetDateTime {
+    day.with_time(time::macros::time!(12:00))
+        .assume_offset(local_offset())
+}
@@ -1,53 +1,125 @@
 //! Keyboard event handlers.
 
 use crate::app::App;
-use crate::state::{DetailState, EditInput, EditorCol, MainInput, Screen, StatusKind};
+use crate::form::{CreateGoalState, Form, SettingsState, WizardStep};
+use crate::keys::{Action, Context};
+use crate::state::{DetailState, EditInput, EditorCol, MainInput, SaveUndo, Screen, StatusKind};
+use crate::tasks::{TaskKind, TaskOutput};
 use beeconfig::{format_timestamp, parse_timestamp};
-use beeminder::types::{CreateDatapoint, UpdateDatapoint};
+use beeminder::changeset::{Changeset, Resolution};
+use beeminder::entry::{self, EntryGoal};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use tokio::runtime::Runtime;
+use std::sync::Arc;
 
 /// Handle a key event, returns true if the app should exit.
-pub fn handle_key(app: &mut App, key: KeyEvent, runtime: &Runtime) -> bool {
-    if matches!(app.screen, Screen::Main) {
-        handle_main_key(app, key, runtime)
-    } else {
-        let mut detail = match std::mem::replace(&mut app.screen, Screen::Main) {
-            Screen::Detail(detail) => detail,
-            Screen::Main => return false,
-        };
-        let outcome = handle_detail_key(app, &mut detail, key, runtime);
-        match outcome {
-            DetailOutcome::Stay => app.screen = Screen::Detail(detail),
-            DetailOutcome::Exit => app.screen = Screen::Main,
+pub fn handle_key(app: &mut App, key: KeyEvent) -> bool {
+    if app.show_help {
+        app.show_help = false;
+        return false;
+    }
+
+    let editing = match &app.screen {
+        Screen::Main => !matches!(app.main_input, MainInput::Normal),
+        Screen::Detail(detail) => detail.input.is_some(),
+        Screen::CreateGoal(wizard) => wizard.is_editing(),
+        Screen::Settings(settings) => settings.form.input.is_some(),
+    };
+    if key.code == KeyCode::Esc && !editing && app.cancel_tasks() {
+        return false;
+    }
+    if !editing && app.keymap.action(app.key_context(), &key) == Some(Action::Help) {
+        app.show_help = true;
+        return false;
+    }
+
+    match std::mem::replace(&mut app.screen, Screen::Main) {
+        Screen::Main => return handle_main_key(app, key),
+        Screen::Detail(mut detail) => {
+            if matches!(
+                handle_detail_key(app, &mut detail, key),
+                ScreenOutcome::Stay
+            ) {
+                app.screen = Screen::Detail(detail);
+            }
+        }
+        Screen::CreateGoal(mut wizard) => {
+            if matches!(
+                handle_create_goal_key(app, &mut wizard, key),
+                ScreenOutcome::Stay
+            ) {
+                app.screen = Screen::CreateGoal(wizard);
+            }
+        }
+        Screen::Settings(mut settings) => {
+            if matches!(
+                handle_settings_key(app, &mut settings, key),
+                ScreenOutcome::Stay
+            ) {
+                app.screen = Screen::Settings(settings);
+            }
         }
-        false
     }
+    false
 }
 
-fn handle_main_key(app: &mut App, key: KeyEvent, runtime: &Runtime) -> bool {
+fn handle_main_key(app: &mut App, key: KeyEvent) -> bool {
     match &mut app.main_input {
-        MainInput::Normal => match key.code {
-            KeyCode::Char('q') => return true,
-            KeyCode::Char('r') => {
-                if let Err(err) = app.refresh_goals(runtime) {
-                    app.set_status(StatusKind::Error, err.to_string());
+        MainInput::Normal => match app.keymap.action(Context::Main, &key) {
+            Some(Action::Quit) => return true,
+            Some(Action::Refresh) => {
+                app.status = None;
+                app.refresh_view();
+            }
+            Some(Action::Down) => app.move_main_selection(1),
+            Some(Action::Up) => app.move_main_selection(-1),
+            Some(Action::AddDatapoint) => app.start_inline_add(),
+            Some(Action::EditDatapoints) => app.open_detail(),
+            Some(Action::Filter) => app.enter_filter_mode(),
+            Some(Action::Sort) => app.cycle_sort(),
+            Some(Action::Group) => app.cycle_group(),
+            Some(Action::NewGoal) => app.open_create_goal(),
+            Some(Action::GoalSettings) => app.open_settings(),
+            Some(Action::UndoSave) => app.undo_last_save(),
+            Some(Action::ToggleArchived) => app.toggle_archived(),
+            Some(Action::Unarchive) => app.unarchive_selected(),
+            Some(Action::GoalActions) => app.open_goal_action(),
+            Some(Action::Command) => app.open_command_palette(),
+            _ => {}
+        },
+        MainInput::Command(palette) => match key.code {
+            KeyCode::Esc => app.main_input = MainInput::Normal,
+            KeyCode::Enter => {
+                let input = palette.input.buffer.clone();
+                app.run_commands(&input);
+            }
+            KeyCode::Tab => {
+                let slugs: Vec<&str> = app.goals.iter().map(|goal| goal.slug.as_str()).collect();
+                palette.complete(&slugs);
+            }
+            _ => edit_input(&mut palette.input, key),
+        },
+        MainInput::GoalAction(state) => match key.code {
+            KeyCode::Esc => app.main_input = MainInput::Normal,
+            KeyCode::Up => state.move_action(-1),
+            KeyCode::Down => state.move_action(1),
+            KeyCode::Enter => {
+                if state.is_confirmed() {
+                    let (slug, action) = (state.slug.clone(), state.action);
+                    app.run_goal_action(slug, action);
                 } else {
-                    app.set_status(StatusKind::Info, "Goals refreshed".to_string());
+                    app.set_status(
+                        StatusKind::Error,
+                        "Type the goal slug to confirm".to_string(),
+                    );
                 }
             }
-            KeyCode::Char('j') | KeyCode::Down => app.move_main_selection(1),
-            KeyCode::Char('k') | KeyCode::Up => app.move_main_selection(-1),
-            KeyCode::Enter => app.start_inline_add(),
-            KeyCode::Char('e') => app.open_detail(runtime),
-            KeyCode::Char('/') => app.enter_filter_mode(),
-            _ => {}
+            _ => edit_input(&mut state.typed, key),
         },
         MainInput::InlineAdd { buffer } => match key.code {
             KeyCode::Esc => app.cancel_inline_add(),
             KeyCode::Enter => {
                 let input = buffer.clone();
-                app.submit_inline_add(&input, runtime);
+                app.submit_inline_add(&input);
             }
             KeyCode::Backspace => {
                 buffer.pop();
@@ -79,17 +151,12 @@ fn handle_main_key(app: &mut App, key: KeyEvent, runtime: &Runtime) -> bool {
     false
 }
 
-enum DetailOutcome {
+enum ScreenOutcome {
     Stay,
     Exit,
 }
 
-fn handle_detail_key(
-    app: &mut App,
-    detail: &mut DetailState,
-    key: KeyEvent,
-    runtime: &Runtime,
-) -> DetailOutcome {
+fn handle_detail_key(app: &mut App, detail: &mut DetailState, key: KeyEvent) -> ScreenOutcome {
     if let Some(mut input) = detail.input.take() {
         match key.code {
             KeyCode::Esc => {
@@ -104,48 +171,30 @@ fn handle_detail_key(
                     detail.input = None;
                 }
             }
-            KeyCode::Backspace => {
-                input.backspace();
-                detail.input = Some(input);
-            }
-            KeyCode::Delete => {
-                input.delete();
-                detail.input = Some(input);
-            }
-            KeyCode::Left => {
-                input.move_left();
-                detail.input = Some(input);
-            }
-            KeyCode::Right => {
-                input.move_right();
-                detail.input = Some(input);
-            }
-            KeyCode::Home => {
-                input.move_home();
-                detail.input = Some(input);
-            }
-            KeyCode::End => {
-                input.move_end();
-                detail.input = Some(input);
-            }
-            KeyCode::Char(c) => {
-                if !key.modifiers.contains(KeyModifiers::CONTROL) {
-                    input.insert(c);
-                }
-                detail.input = Some(input);
-            }
             _ => {
+                edit_input(&mut input, key);
                 detail.input = Some(input);
             }
         }
-        return DetailOutcome::Stay;
+        return ScreenOutcome::Stay;
     }
 
-    match key.code {
-        KeyCode::Esc => {
+    if !detail.conflicts.is_empty() {
+        return match key.code {
+            KeyCode::Char('m') => resolve_detail_conflict(app, detail, Resolution::Merge),
+            KeyCode::Char('o') => resolve_detail_conflict(app, detail, Resolution::Mine),
+            KeyCode::Char('t') | KeyCode::Esc => {
+                resolve_detail_conflict(app, detail, Resolution::Theirs)
+            }
+            _ => ScreenOutcome::Stay,
+        };
+    }
+
+    match app.keymap.action(Context::Detail, &key) {
+        Some(Action::Back) => {
             if detail.dirty {
                 if detail.confirm_discard {
-                    return DetailOutcome::Exit;
+                    return ScreenOutcome::Exit;
                 }
                 app.set_status(
                     StatusKind::Info,
@@ -153,23 +202,180 @@ fn handle_detail_key(
                 );
                 detail.confirm_discard = true;
             } else {
-                return DetailOutcome::Exit;
+                return ScreenOutcome::Exit;
             }
         }
-        KeyCode::Char('j') | KeyCode::Down => detail.move_row(1),
-        KeyCode::Char('k') | KeyCode::Up => detail.move_row(-1),
-        KeyCode::Char('h') | KeyCode::Left => detail.move_col(-1),
-        KeyCode::Char('l') | KeyCode::Right => detail.move_col(1),
-        KeyCode::Enter => start_detail_edit(detail),
-        KeyCode::Char('n') => detail.add_new_row(),
-        KeyCode::Char('d') => detail.toggle_delete(),
-        KeyCode::Char('s') if save_detail_changes(app, detail, runtime) => {
-            return DetailOutcome::Exit;
+        Some(Action::Down) => detail.move_row(1),
+        Some(Action::Up) => detail.move_row(-1),
+        Some(Action::Left) => detail.move_col(-1),
+        Some(Action::Right) => detail.move_col(1),
+        _ if detail.saving => {
+            app.set_status(StatusKind::Info, "Save in progress".to_string());
+        }
+        Some(Action::EditCell) => start_detail_edit(detail),
+        Some(Action::NewRow) => detail.add_new_row(),
+        Some(Action::DeleteRow) => detail.toggle_delete(),
+        Some(Action::Save) => save_detail_changes(app, detail),
+        Some(Action::Undo) if !detail.undo() => {
+            app.set_status(StatusKind::Info, "Nothing to undo".to_string());
+        }
+        Some(Action::Redo) if !detail.redo() => {
+            app.set_status(StatusKind::Info, "Nothing to redo".to_string());
         }
         _ => {}
     }
 
-    DetailOutcome::Stay
+    ScreenOutcome::Stay
+}
+
+/// Applies a line-editing key to a text input.
+fn edit_input(input: &mut EditInput, key: KeyEvent) {
+    match key.code {
+        KeyCode::Backspace => input.backspace(),
+        KeyCode::Delete => input.delete(),
+        KeyCode::Left => input.move_left(),
+        KeyCode::Right => input.move_right(),
+        KeyCode::Home => input.move_home(),
+        KeyCode::End => input.move_end(),
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => input.insert(c),
+        _ => {}
+    }
+}
+
+/// Handles field navigation and editing; returns false for unused keys.
+fn handle_form_key(app: &mut App, form: &mut Form, key: KeyEvent) -> bool {
+    if let Some(mut input) = form.input.take() {
+        match key.code {
+            KeyCode::Esc => {}
+            KeyCode::Enter => {
+                let buffer = input.buffer.clone();
+                if let Err(err) = form.apply_input(&buffer) {
+                    app.set_status(StatusKind::Error, err);
+                    form.input = Some(input);
+                }
+            }
+            _ => {
+                edit_input(&mut input, key);
+                form.input = Some(input);
+            }
+        }
+        return true;
+    }
+
+    match app.keymap.action(Context::Form, &key) {
+        Some(Action::Down) => form.move_field(1),
+        Some(Action::Up) => form.move_field(-1),
+        Some(Action::EditField) => form.activate(),
+        _ => return false,
+    }
+    true
+}
+
+fn handle_create_goal_key(
+    app: &mut App,
+    wizard: &mut CreateGoalState,
+    key: KeyEvent,
+) -> ScreenOutcome {
+    if wizard.saving {
+        app.set_status(StatusKind::Info, "Goal creation in progress".to_string());
+        return ScreenOutcome::Stay;
+    }
+    if let Some(form) = wizard.form_mut() {
+        if handle_form_key(app, form, key) {
+            return ScreenOutcome::Stay;
+        }
+    }
+
+    match app.keymap.action(Context::Form, &key) {
+        Some(Action::Back) if wizard.step == WizardStep::Basics => return ScreenOutcome::Exit,
+        Some(Action::Back | Action::PreviousStep) => wizard.previous_step(),
+        Some(Action::NextStep) => {
+            if let Err(err) = wizard.next_step() {
+                app.set_status(StatusKind::Error, err);
+            }
+        }
+        Some(Action::Save | Action::EditField) if wizard.step == WizardStep::Review => {
+            submit_create_goal(app, wizard);
+        }
+        _ => {}
+    }
+    ScreenOutcome::Stay
+}
+
+fn submit_create_goal(app: &mut App, wizard: &mut CreateGoalState) {
+    let goal = match wizard.build() {
+        Ok(goal) => goal,
+        Err(err) => {
+            app.set_status(StatusKind::Error, err);
+            return;
+        }
+    };
+
+    let slug = goal.slug.clone();
+    let client = Arc::clone(&app.client);
+    wizard.saving = true;
+    app.tasks
+        .spawn(TaskKind::CreateGoal { slug: slug.clone() }, async move {
+            let result = client.create_goal(&goal).await;
+            TaskOutput::GoalCreated { slug, result }
+        });
+}
+
+fn handle_settings_key(
+    app: &mut App,
+    settings: &mut SettingsState,
+    key: KeyEvent,
+) -> ScreenOutcome {
+    if settings.saving {
+        app.set_status(StatusKind::Info, "Save in progress".to_string());
+        return ScreenOutcome::Stay;
+    }
+    if settings.confirm {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Enter => save_settings(app, settings),
+            KeyCode::Char('n') | KeyCode::Esc => settings.confirm = false,
+            _ => {}
+        }
+        return ScreenOutcome::Stay;
+    }
+    if handle_form_key(app, &mut settings.form, key) {
+        return ScreenOutcome::Stay;
+    }
+
+    match app.keymap.action(Context::Form, &key) {
+        Some(Action::Back) => return ScreenOutcome::Exit,
+        Some(Action::Save) => {
+            if settings.changes().is_empty() {
+                app.set_status(StatusKind::Info, "No changes to save".to_string());
+            } else if let Err(err) = settings.build() {
+                app.set_status(StatusKind::Error, err);
+            } else {
+                settings.confirm = true;
+            }
+        }
+        _ => {}
+    }
+    ScreenOutcome::Stay
+}
+
+fn save_settings(app: &mut App, settings: &mut SettingsState) {
+    let update = match settings.build() {
+        Ok(update) => update,
+        Err(err) => {
+            settings.confirm = false;
+            app.set_status(StatusKind::Error, err);
+            return;
+        }
+    };
+
+    let slug = settings.goal_slug.clone();
+    let client = Arc::clone(&app.client);
+    settings.saving = true;
+    app.tasks
+        .spawn(TaskKind::SaveSettings { slug: slug.clone() }, async move {
+            let result = client.update_goal(&slug, &update).await;
+            TaskOutput::SettingsSaved { slug, result }
+        });
 }
 
 fn start_detail_edit(detail: &mut DetailState) {
@@ -186,6 +392,8 @@ fn start_detail_edit(detail: &mut DetailState) {
 
 fn apply_detail_edit(detail: &mut DetailState, input: &str) -> Result<(), String> {
     let selected_col = detail.selected_col;
+    let entry_goal = EntryGoal::from(detail.goal.as_ref());
+    let snapshot = detail.snapshot();
     let trimmed = input.trim();
     let mut modified = false;
     let result = {
@@ -198,13 +406,11 @@ fn apply_detail_edit(detail: &mut DetailState, input: &str) -> Result<(), String
                     row.timestamp = ts;
                 })
                 .map_err(|e| e.to_string()),
-            EditorCol::Value => match trimmed.parse::<f64>() {
-                Ok(value) => {
+            EditorCol::Value => entry::parse_value(trimmed, &entry_goal)
+                .map(|value| {
                     row.value = value;
-                    Ok(())
-                }
-                Err(_) => Err("Invalid value".to_string()),
-            },
+                })
+                .map_err(|e| e.to_string()),
             EditorCol::Comment => {
                 row.comment = trimmed.to_string();
                 Ok(())
@@ -216,6 +422,9 @@ fn apply_detail_edit(detail: &mut DetailState, input: &str) -> Result<(), String
         result
     };
 
+    if result.is_ok() && detail.rows != snapshot.rows {
+        detail.push_undo(snapshot);
+    }
     if modified {
         detail.mark_dirty();
     }
@@ -223,87 +432,104 @@ fn apply_detail_edit(detail: &mut DetailState, input: &str) -> Result<(), String
     result
 }
 
-fn save_detail_changes(app: &mut App, detail: &DetailState, runtime: &Runtime) -> bool {
-    let mut creates = Vec::new();
-    let mut updates = Vec::new();
-    let mut deletes = Vec::new();
+fn save_detail_changes(app: &mut App, detail: &mut DetailState) {
+    let changeset = detail.pending_changes();
+    if changeset.is_empty() {
+        app.set_status(StatusKind::Info, "No changes to save".to_string());
+        return;
+    }
+
+    app.set_status(StatusKind::Info, format!("Saving: {}", changeset.summary()));
+    let resumed = detail.save_incomplete;
+    spawn_detail_save(app, detail, changeset, resumed);
+}
 
-    for row in &detail.rows {
-        if row.id.is_none() {
-            if !row.is_deleted {
-                let mut dp = CreateDatapoint::new(row.value).with_timestamp(row.timestamp);
-                if !row.comment.trim().is_empty() {
-                    dp = dp.with_comment(&row.comment);
+/// Applies `changeset`, refusing to overwrite datapoints changed elsewhere;
+/// those come back as conflicts to resolve. A `resumed` save continues the
+/// previous attempt, so undo reverts both parts together.
+fn spawn_detail_save(app: &mut App, detail: &mut DetailState, changeset: Changeset, resumed: bool) {
+    let slug = detail.goal_slug.clone();
+    let client = Arc::clone(&app.client);
+    if !resumed {
+        app.save_attempts += 1;
+        detail.save_attempt = app.save_attempts;
+    }
+    let attempt = detail.save_attempt;
+    detail.saving = true;
+    app.tasks
+        .spawn(TaskKind::SaveDetail { slug: slug.clone() }, async move {
+            let mut undo = SaveUndo {
+                slug: slug.clone(),
+                attempt,
+                changeset: Changeset::default(),
+            };
+            let (applied, result, conflicts) = match changeset.apply_checked(&client, &slug).await {
+                Ok(applied) => {
+                    undo.changeset = applied.inverse();
+                    let mut result = Ok(());
+                    let mut conflicts = Vec::new();
+                    for (change, err) in applied.failed {
+                        match err {
+                            beeminder::Error::Conflict(conflict) => {
+                                conflicts.push((change, *conflict));
+                            }
+                            err if result.is_ok() => result = Err(err),
+                            _ => {}
+                        }
+                    }
+                    (applied.applied, result, conflicts)
                 }
-                creates.push(dp);
+                Err(err) => (Vec::new(), Err(err), Vec::new()),
+            };
+            TaskOutput::DetailSaved {
+                slug,
+                applied,
+                undo,
+                result,
+                conflicts,
             }
-            continue;
-        }
-
-        let id = match &row.id {
-            Some(id) => id.clone(),
-            None => continue,
-        };
-
-        if row.is_deleted {
-            deletes.push(id);
-            continue;
-        }
+        });
+}
 
-        if row.is_modified() {
-            let mut update = UpdateDatapoint::new(id)
-                .with_timestamp(row.timestamp)
-                .with_value(row.value);
-            if row.comment.trim().is_empty() {
-                update.comment = Some(String::new());
-            } else {
-                update = update.with_comment(&row.comment);
-            }
-            updates.push(update);
-        }
+/// Settles the first pending conflict; once none are left, saves what is
+/// still pending, including changes that failed alongside the conflicts.
+fn resolve_detail_conflict(
+    app: &mut App,
+    detail: &mut DetailState,
+    resolution: Resolution,
+) -> ScreenOutcome {
+    if detail.conflicts.is_empty() {
+        return ScreenOutcome::Stay;
     }
-
-    if creates.is_empty() && updates.is_empty() && deletes.is_empty() {
-        app.set_status(StatusKind::Info, "No changes to save".to_string());
-        return false;
+    let (change, conflict) = detail.conflicts.remove(0);
+    let resolved = change.resolve(conflict.current.as_ref(), resolution);
+    detail.settle(&conflict, resolved.as_ref());
+    if !detail.conflicts.is_empty() {
+        return ScreenOutcome::Stay;
     }
 
+    let error = detail.save_error.take();
+    let changeset = detail.pending_changes();
+    if changeset.is_empty() {
+        if let Some(err) = error {
+            app.set_status(StatusKind::Error, err);
+            return ScreenOutcome::Stay;
+        }
+        detail.save_incomplete = false;
+        app.set_status(
+            StatusKind::Success,
+            format!("Saved changes to {}", detail.goal_slug),
+        );
+        app.refresh_goals();
+        return ScreenOutcome::Exit;
+    }
+    let retry = error
+        .map(|err| format!(" (retrying after: {err})"))
+        .unwrap_or_default();
     app.set_status(
         StatusKind::Info,
-        format!(
-            "Saving: {} new, {} updated, {} deleted",
-            creates.len(),
-            updates.len(),
-            deletes.len()
-        ),
+        format!("Saving resolved changes: {}{retry}", changeset.summary()),
     );
-
-    let slug = detail.goal_slug.clone();
-    let result = runtime.block_on(async {
-        for dp in creates {
-            app.client.create_datapoint(&slug, &dp).await?;
-        }
-        for update in updates {
-            app.client.update_datapoint(&slug, &update).await?;
-        }
-        for id in deletes {
-            app.client.delete_datapoint(&slug, &id).await?;
-        }
-        Ok::<(), beeminder::Error>(())
-    });
-
-    match result {
-        Ok(()) => {
-            if let Err(err) = app.refresh_goals(runtime) {
-                app.set_status(StatusKind::Error, err.to_string());
-            } else {
-                app.set_status(StatusKind::Success, "Saved changes".to_string());
-            }
-            true
-        }
-        Err(err) => {
-            app.set_status(StatusKind::Error, err.to_string());
-            false
-        }
-    }
+    spawn_detail_save(app, detail, changeset, true);
+    ScreenOutcome::Stay
 }
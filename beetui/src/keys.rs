@@ -0,0 +1,458 @@
+//! Configurable key bindings.
+
+use anyhow::{anyhow, Result};
+use beeconfig::KeyBinding;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+/// Where a key is pressed; actions only conflict within one context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    Main,
+    Detail,
+    Form,
+}
+
+impl Context {
+    pub const VALUES: [Self; 3] = [Self::Main, Self::Detail, Self::Form];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Main => "Goal list",
+            Self::Detail => "Datapoint editor",
+            Self::Form => "Forms",
+        }
+    }
+}
+
+/// Something a key can be bound to, named as in `[tui.keys]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Up,
+    Down,
+    Help,
+    Quit,
+    Refresh,
+    AddDatapoint,
+    EditDatapoints,
+    Filter,
+    Sort,
+    Group,
+    NewGoal,
+    GoalSettings,
+    UndoSave,
+    ToggleArchived,
+    Unarchive,
+    GoalActions,
+    Command,
+    Left,
+    Right,
+    EditCell,
+    NewRow,
+    DeleteRow,
+    Save,
+    Undo,
+    Redo,
+    Back,
+    EditField,
+    NextStep,
+    PreviousStep,
+}
+
+impl Action {
+    pub const VALUES: [Self; 29] = [
+        Self::Up,
+        Self::Down,
+        Self::Help,
+        Self::Quit,
+        Self::Refresh,
+        Self::AddDatapoint,
+        Self::EditDatapoints,
+        Self::Filter,
+        Self::Sort,
+        Self::Group,
+        Self::NewGoal,
+        Self::GoalSettings,
+        Self::UndoSave,
+        Self::ToggleArchived,
+        Self::Unarchive,
+        Self::GoalActions,
+        Self::Command,
+        Self::Left,
+        Self::Right,
+        Self::EditCell,
+        Self::NewRow,
+        Self::DeleteRow,
+        Self::Save,
+        Self::Undo,
+        Self::Redo,
+        Self::Back,
+        Self::EditField,
+        Self::NextStep,
+        Self::PreviousStep,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::Help => "help",
+            Self::Quit => "quit",
+            Self::Refresh => "refresh",
+            Self::AddDatapoint => "add_datapoint",
+            Self::EditDatapoints => "edit_datapoints",
+            Self::Filter => "filter",
+            Self::Sort => "sort",
+            Self::Group => "group",
+            Self::NewGoal => "new_goal",
+            Self::GoalSettings => "goal_settings",
+            Self::UndoSave => "undo_save",
+            Self::ToggleArchived => "toggle_archived",
+            Self::Unarchive => "unarchive",
+            Self::GoalActions => "goal_actions",
+            Self::Command => "command",
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::EditCell => "edit_cell",
+            Self::NewRow => "new_row",
+            Self::DeleteRow => "delete_row",
+            Self::Save => "save",
+            Self::Undo => "undo",
+            Self::Redo => "redo",
+            Self::Back => "back",
+            Self::EditField => "edit_field",
+            Self::NextStep => "next_step",
+            Self::PreviousStep => "previous_step",
+        }
+    }
+
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::Up => "Move up",
+            Self::Down => "Move down",
+            Self::Help => "Show this help",
+            Self::Quit => "Quit",
+            Self::Refresh => "Refresh goals",
+            Self::AddDatapoint => "Add a datapoint to the selected goal",
+            Self::EditDatapoints => "Edit the selected goal's datapoints",
+            Self::Filter => "Filter goals by slug or title",
+            Self::Sort => "Cycle the sort order",
+            Self::Group => "Cycle the grouping",
+            Self::NewGoal => "Create a goal",
+            Self::GoalSettings => "Edit the selected goal's settings",
+            Self::UndoSave => "Undo the last datapoint editor save",
+            Self::ToggleArchived => "Switch between active and archived goals",
+            Self::Unarchive => "Unarchive the selected archived goal",
+            Self::GoalActions => "Refresh graph, stepdown or shortcircuit the selected goal",
+            Self::Command => "Open the command palette",
+            Self::Left => "Previous column",
+            Self::Right => "Next column",
+            Self::EditCell => "Edit the selected cell",
+            Self::NewRow => "Add a datapoint row",
+            Self::DeleteRow => "Toggle deletion of the selected row",
+            Self::Save => "Save changes",
+            Self::Undo => "Undo the last change",
+            Self::Redo => "Redo the last undone change",
+            Self::Back => "Back to the goal list or previous step",
+            Self::EditField => "Edit the selected field",
+            Self::NextStep => "Next step of the goal wizard",
+            Self::PreviousStep => "Previous step of the goal wizard",
+        }
+    }
+
+    const fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Self::Up => &["k", "Up"],
+            Self::Down => &["j", "Down"],
+            Self::Help => &["?"],
+            Self::Quit => &["q"],
+            Self::Refresh => &["r"],
+            Self::AddDatapoint | Self::EditCell => &["Enter"],
+            Self::EditDatapoints => &["e"],
+            Self::Filter => &["/"],
+            Self::Sort => &["o"],
+            Self::Group => &["g"],
+            Self::NewGoal | Self::NewRow => &["n"],
+            Self::GoalSettings | Self::Save => &["s"],
+            Self::UndoSave => &["U"],
+            Self::ToggleArchived => &["A"],
+            Self::Unarchive => &["a"],
+            Self::GoalActions => &["x"],
+            Self::Command => &[":"],
+            Self::Left => &["h", "Left"],
+            Self::Right => &["l", "Right"],
+            Self::DeleteRow => &["d"],
+            Self::Undo => &["u"],
+            Self::Redo => &["Ctrl-r"],
+            Self::Back => &["Esc"],
+            Self::EditField => &["Enter", "Space"],
+            Self::NextStep => &["Tab"],
+            Self::PreviousStep => &["Shift-Tab"],
+        }
+    }
+
+    fn in_context(self, context: Context) -> bool {
+        match self {
+            Self::Up | Self::Down | Self::Help => true,
+            Self::Quit
+            | Self::Refresh
+            | Self::AddDatapoint
+            | Self::EditDatapoints
+            | Self::Filter
+            | Self::Sort
+            | Self::Group
+            | Self::NewGoal
+            | Self::GoalSettings
+            | Self::UndoSave
+            | Self::ToggleArchived
+            | Self::Unarchive
+            | Self::GoalActions
+            | Self::Command => context == Context::Main,
+            Self::Left
+            | Self::Right
+            | Self::EditCell
+            | Self::NewRow
+            | Self::DeleteRow
+            | Self::Undo
+            | Self::Redo => context == Context::Detail,
+            Self::Save | Self::Back => matches!(context, Context::Detail | Context::Form),
+            Self::EditField | Self::NextStep | Self::PreviousStep => context == Context::Form,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::VALUES
+            .into_iter()
+            .find(|action| action.name() == name)
+    }
+}
+
+/// A key plus the Ctrl/Alt modifiers that must be held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn matches(self, event: &KeyEvent) -> bool {
+        let modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        let code = match event.code {
+            // Ctrl-Shift-r arrives as 'R'; bindings store Ctrl keys lowercase.
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            code => code,
+        };
+        code == self.code && modifiers == self.modifiers
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    /// Parses keys like `j`, `U`, `?`, `Enter`, `Space`, `F5`, `Ctrl-r` or `Alt-x`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = value;
+        loop {
+            let lower = rest.to_ascii_lowercase();
+            if rest.len() > 1 && (lower.starts_with("ctrl-") || lower.starts_with("c-")) {
+                modifiers |= KeyModifiers::CONTROL;
+            } else if rest.len() > 1 && (lower.starts_with("alt-") || lower.starts_with("m-")) {
+                modifiers |= KeyModifiers::ALT;
+            } else {
+                break;
+            }
+            rest = &rest[rest.find('-').map_or(0, |pos| pos + 1)..];
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" | "shift-tab" => KeyCode::BackTab,
+                "space" => KeyCode::Char(' '),
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                other => match other.strip_prefix('f').map(str::parse::<u8>) {
+                    Some(Ok(n @ 1..=12)) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key '{value}'")),
+                },
+            },
+        };
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            f.write_str("Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            f.write_str("Alt-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::Enter => f.write_str("Enter"),
+            KeyCode::Esc => f.write_str("Esc"),
+            KeyCode::Tab => f.write_str("Tab"),
+            KeyCode::BackTab => f.write_str("Shift-Tab"),
+            KeyCode::Backspace => f.write_str("Backspace"),
+            KeyCode::Delete => f.write_str("Delete"),
+            KeyCode::Insert => f.write_str("Insert"),
+            KeyCode::Up => f.write_str("Up"),
+            KeyCode::Down => f.write_str("Down"),
+            KeyCode::Left => f.write_str("Left"),
+            KeyCode::Right => f.write_str("Right"),
+            KeyCode::Home => f.write_str("Home"),
+            KeyCode::End => f.write_str("End"),
+            KeyCode::PageUp => f.write_str("PageUp"),
+            KeyCode::PageDown => f.write_str("PageDown"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            other => write!(f, "{other:?}"),
+        }
+    }
+}
+
+/// Active bindings: defaults overridden by `[tui.keys]`.
+#[derive(Debug)]
+pub struct Keymap {
+    bindings: HashMap<Action, Vec<Key>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::VALUES
+            .into_iter()
+            .map(|action| {
+                let keys = action
+                    .default_keys()
+                    .iter()
+                    .filter_map(|key| key.parse().ok())
+                    .collect();
+                (action, keys)
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// Applies the configured overrides, rejecting unknown actions, unknown
+    /// keys and keys bound to two actions of the same context.
+    pub fn from_config(config: &BTreeMap<String, KeyBinding>) -> Result<Self> {
+        let mut keymap = Self::default();
+        let mut problems = Vec::new();
+        for (name, binding) in config {
+            let Some(action) = Action::from_name(name) else {
+                problems.push(format!("unknown action '{name}'"));
+                continue;
+            };
+            let mut keys = Vec::new();
+            for key in binding.keys() {
+                match key.parse::<Key>() {
+                    Ok(key) => keys.push(key),
+                    Err(err) => problems.push(format!("{name}: {err}")),
+                }
+            }
+            if keys.is_empty() {
+                problems.push(format!("{name}: no keys given"));
+            }
+            keymap.bindings.insert(action, keys);
+        }
+
+        for context in Context::VALUES {
+            let actions: Vec<Action> = Action::VALUES
+                .into_iter()
+                .filter(|action| action.in_context(context))
+                .collect();
+            for (pos, first) in actions.iter().enumerate() {
+                for second in &actions[pos + 1..] {
+                    for key in keymap.keys(*first) {
+                        if keymap.keys(*second).contains(key) {
+                            problems.push(format!(
+                                "'{key}' is bound to both {} and {} ({})",
+                                first.name(),
+                                second.name(),
+                                context.label()
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(keymap)
+        } else {
+            Err(anyhow!("Invalid [tui.keys]: {}", problems.join("; ")))
+        }
+    }
+
+    fn keys(&self, action: Action) -> &[Key] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// The action a key event triggers in `context`, if any.
+    pub fn action(&self, context: Context, event: &KeyEvent) -> Option<Action> {
+        Action::VALUES.into_iter().find(|action| {
+            action.in_context(context) && self.keys(*action).iter().any(|key| key.matches(event))
+        })
+    }
+
+    /// Keys bound to `action`, e.g. "j/Down".
+    pub fn label(&self, action: Action) -> String {
+        self.keys(action)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Footer text like "Enter: add  e: edit" using the first key of each action.
+    pub fn hints(&self, hints: &[(Action, &str)]) -> String {
+        hints
+            .iter()
+            .filter_map(|(action, text)| {
+                let key = self.keys(*action).first()?;
+                Some(format!("{key}: {text}"))
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+
+    /// Help overlay rows per context: (keys, description).
+    pub fn help(&self) -> Vec<(Context, Vec<(String, &'static str)>)> {
+        Context::VALUES
+            .into_iter()
+            .map(|context| {
+                let rows = Action::VALUES
+                    .into_iter()
+                    .filter(|action| action.in_context(context))
+                    .map(|action| (self.label(action), action.description()))
+                    .collect();
+                (context, rows)
+            })
+            .collect()
+    }
+}
@@ -1,8 +1,13 @@
 #![allow(clippy::multiple_crate_versions)]
 
 mod app;
+mod command;
+mod form;
 mod handlers;
+mod keys;
 mod state;
+mod tasks;
+mod theme;
 mod ui;
 
 use anyhow::{Context, Result};
@@ -16,10 +21,12 @@ use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
 use handlers::handle_key;
+use keys::Keymap;
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
 use state::{StatusKind, TICK_RATE};
 use std::io::{self, Stdout};
+use theme::Theme;
 use tokio::runtime::Runtime;
 use ui::render_app;
 
@@ -35,20 +42,21 @@ fn main() -> Result<()> {
         BeeminderClient::new(api_key)
     };
 
+    let keymap = Keymap::from_config(&config.tui.keys)?;
+    let theme = Theme::from_config(&config.tui.theme)?;
+
     let runtime = Runtime::new().context("Failed to start tokio runtime")?;
-    let mut app = App::new(config, client);
+    let mut app = App::new(config, keymap, theme, client, runtime.handle().clone());
 
     let (mut terminal, _guard) = init_terminal()?;
 
     if app.config.tui.refresh_on_start {
-        if let Err(err) = app.refresh_goals(&runtime) {
-            app.set_status(StatusKind::Error, err.to_string());
-        }
+        app.refresh_goals();
     } else {
         app.set_status(StatusKind::Info, "Press r to load goals".to_string());
     }
 
-    run_app(&mut terminal, &mut app, &runtime)
+    run_app(&mut terminal, &mut app)
 }
 
 fn init_terminal() -> Result<(Terminal<CrosstermBackend<Stdout>>, TerminalGuard)> {
@@ -70,18 +78,16 @@ impl Drop for TerminalGuard {
     }
 }
 
-fn run_app(
-    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
-    app: &mut App,
-    runtime: &Runtime,
-) -> Result<()> {
+fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App) -> Result<()> {
     loop {
+        app.poll_tasks();
+        app.tick();
         app.clear_expired_status();
         terminal.draw(|f| render_app(f, app))?;
 
         if event::poll(TICK_RATE)? {
             if let Event::Key(key) = event::read()? {
-                if handle_key(app, key, runtime) {
+                if handle_key(app, key) {
                     return Ok(());
                 }
             }
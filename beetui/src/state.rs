@@ -1,6 +1,9 @@
 //! State types for the TUI application.
 
-use beeminder::types::{Datapoint, GoalSummary};
+use crate::command::CommandPalette;
+use crate::form::{CreateGoalState, SettingsState};
+use beeminder::changeset::{Applied, Change, Changeset, Conflict, EditedDatapoint};
+use beeminder::types::{Datapoint, GoalFull};
 use ratatui::widgets::TableState;
 use std::time::{Duration, Instant};
 use time::OffsetDateTime;
@@ -8,20 +11,106 @@ use unicode_width::UnicodeWidthStr;
 
 pub const STATUS_TTL: Duration = Duration::from_secs(4);
 pub const TICK_RATE: Duration = Duration::from_millis(200);
+/// How often the status line blinks for alerts.
+pub const FLASH_PERIOD: Duration = Duration::from_millis(500);
+/// Overlap between consecutive `get_user_diff` windows to absorb clock skew.
+pub const DIFF_SLACK: Duration = Duration::from_secs(60);
+/// Maximum number of undo steps kept by the datapoint editor.
+pub const UNDO_LIMIT: usize = 100;
 
 /// The current screen being displayed.
 #[derive(Debug)]
 pub enum Screen {
     Main,
     Detail(DetailState),
+    CreateGoal(CreateGoalState),
+    Settings(SettingsState),
 }
 
 /// Input mode for the main screen.
 #[derive(Debug)]
 pub enum MainInput {
     Normal,
-    InlineAdd { buffer: String },
-    Filter { buffer: String },
+    InlineAdd {
+        buffer: String,
+    },
+    Filter {
+        buffer: String,
+    },
+    /// `:` command palette
+    Command(CommandPalette),
+    /// Confirmation modal for a goal action
+    GoalAction(Box<GoalActionState>),
+}
+
+/// Goal-level operations run from the action modal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoalAction {
+    RefreshGraph,
+    Stepdown,
+    CancelStepdown,
+    Shortcircuit,
+}
+
+impl GoalAction {
+    pub const VALUES: [Self; 4] = [
+        Self::RefreshGraph,
+        Self::Stepdown,
+        Self::CancelStepdown,
+        Self::Shortcircuit,
+    ];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::RefreshGraph => "Refresh graph",
+            Self::Stepdown => "Step down pledge",
+            Self::CancelStepdown => "Cancel stepdown",
+            Self::Shortcircuit => "Shortcircuit",
+        }
+    }
+
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::RefreshGraph => "Refetch autodata and redraw the graph",
+            Self::Stepdown => "Schedule the pledge to drop one level after the akrasia horizon",
+            Self::CancelStepdown => "Cancel a scheduled pledge stepdown",
+            Self::Shortcircuit => "Charge the current pledge now and raise it to the next level",
+        }
+    }
+}
+
+/// State of the goal action modal; the action runs once the slug is typed.
+#[derive(Debug)]
+pub struct GoalActionState {
+    pub slug: String,
+    pub action: GoalAction,
+    pub typed: EditInput,
+    /// Full goal with contract and pledge, once loaded
+    pub goal: Option<GoalFull>,
+}
+
+impl GoalActionState {
+    pub fn new(slug: String) -> Self {
+        Self {
+            slug,
+            action: GoalAction::RefreshGraph,
+            typed: EditInput::new(String::new()),
+            goal: None,
+        }
+    }
+
+    pub fn move_action(&mut self, delta: i32) {
+        let current = GoalAction::VALUES
+            .iter()
+            .position(|action| *action == self.action)
+            .unwrap_or(0);
+        let max = GoalAction::VALUES.len() - 1;
+        self.action = GoalAction::VALUES[clamp_index(current, delta, max)];
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.typed.buffer.trim() == self.slug
+    }
 }
 
 /// Status message severity.
@@ -30,6 +119,8 @@ pub enum StatusKind {
     Info,
     Success,
     Error,
+    /// Flashing notice, e.g. a goal changed color zone
+    Alert,
 }
 
 /// A status message with expiration tracking.
@@ -45,18 +136,43 @@ pub struct StatusMessage {
 pub struct DetailState {
     pub goal_slug: String,
     pub goal_title: String,
+    /// Full goal shown in the metadata pane
+    pub goal: Box<GoalFull>,
+    /// Datapoints as loaded, diffed against the rows on save
+    pub original: Vec<Datapoint>,
     pub rows: Vec<EditorRow>,
     pub table_state: TableState,
     pub selected_col: EditorCol,
     pub input: Option<EditInput>,
     pub dirty: bool,
     pub confirm_discard: bool,
+    pub saving: bool,
+    /// Changes the save found changed elsewhere, waiting for a resolution
+    pub conflicts: Vec<(Change, Conflict)>,
+    /// Error of a change that failed alongside the conflicts, retried
+    /// once they are settled
+    pub save_error: Option<String>,
+    /// Attempt of the latest save, continued by the resolved changes
+    pub save_attempt: u64,
+    /// Whether the latest save left changes unsaved, so the next one
+    /// continues its attempt
+    pub save_incomplete: bool,
+    pub undo: Vec<EditorSnapshot>,
+    pub redo: Vec<EditorSnapshot>,
+}
+
+/// Editor rows and selection at one point of the undo history.
+#[derive(Debug, Clone)]
+pub struct EditorSnapshot {
+    pub rows: Vec<EditorRow>,
+    pub selected: Option<usize>,
 }
 
 impl DetailState {
-    pub fn from_datapoints(goal: &GoalSummary, datapoints: Vec<Datapoint>) -> Self {
+    pub fn from_goal(goal: GoalFull, datapoints: Vec<Datapoint>) -> Self {
         let rows = datapoints
-            .into_iter()
+            .iter()
+            .cloned()
             .map(EditorRow::from_datapoint)
             .collect::<Vec<_>>();
         let mut table_state = TableState::default();
@@ -65,14 +181,189 @@ impl DetailState {
         }
         Self {
             goal_slug: goal.slug.clone(),
-            goal_title: goal.title.clone(),
+            goal_title: goal.title.clone().unwrap_or_default(),
+            goal: Box::new(goal),
+            original: datapoints,
             rows,
             table_state,
             selected_col: EditorCol::Timestamp,
             input: None,
             dirty: false,
             confirm_discard: false,
+            saving: false,
+            conflicts: Vec::new(),
+            save_error: None,
+            save_attempt: 0,
+            save_incomplete: false,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    pub fn snapshot(&self) -> EditorSnapshot {
+        EditorSnapshot {
+            rows: self.rows.clone(),
+            selected: self.table_state.selected(),
+        }
+    }
+
+    /// Records `snapshot` as the state to return to on undo.
+    pub fn push_undo(&mut self, snapshot: EditorSnapshot) {
+        self.undo.push(snapshot);
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
         }
+        self.redo.clear();
+    }
+
+    /// Reverts the last change. Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.undo.pop() else {
+            return false;
+        };
+        self.redo.push(self.snapshot());
+        self.restore(snapshot);
+        true
+    }
+
+    /// Reapplies the last undone change. Returns false if there is none.
+    pub fn redo(&mut self) -> bool {
+        let Some(snapshot) = self.redo.pop() else {
+            return false;
+        };
+        self.undo.push(self.snapshot());
+        self.restore(snapshot);
+        true
+    }
+
+    fn restore(&mut self, snapshot: EditorSnapshot) {
+        self.rows = snapshot.rows;
+        self.table_state.select(snapshot.selected);
+        self.dirty = self.has_changes();
+        self.confirm_discard = false;
+    }
+
+    /// Takes the changes a save applied as the new loaded state, so the
+    /// next save only sends what is still pending. Created rows get their
+    /// server IDs; the editor history is dropped since it predates them.
+    pub fn rebase(&mut self, applied: &[Applied]) {
+        if applied.is_empty() {
+            return;
+        }
+        for applied in applied {
+            match applied {
+                Applied::Created(dp) => {
+                    let snapshot = RowSnapshot::from(dp);
+                    if let Some(row) = self.rows.iter_mut().find(|row| {
+                        row.id.is_none() && !row.is_deleted && row.snapshot() == snapshot
+                    }) {
+                        row.id = Some(dp.id.clone());
+                        row.original = Some(snapshot);
+                    }
+                    self.original.push(dp.clone());
+                }
+                Applied::Updated { after, .. } => {
+                    if let Some(dp) = self.original.iter_mut().find(|dp| dp.id == after.id) {
+                        *dp = after.clone();
+                    }
+                    if let Some(row) = self
+                        .rows
+                        .iter_mut()
+                        .find(|row| row.id == Some(after.id.clone()))
+                    {
+                        row.original = Some(RowSnapshot::from(after));
+                    }
+                }
+                Applied::Deleted(deleted) => {
+                    self.original.retain(|dp| dp.id != deleted.id);
+                    self.rows
+                        .retain(|row| row.id.as_deref() != Some(deleted.id.as_str()));
+                }
+            }
+        }
+        let selected = self
+            .table_state
+            .selected()
+            .map(|idx| idx.min(self.rows.len().saturating_sub(1)));
+        self.table_state
+            .select(selected.filter(|_| !self.rows.is_empty()));
+        self.undo.clear();
+        self.redo.clear();
+        self.dirty = self.has_changes();
+    }
+
+    /// Takes the other version of a point changed elsewhere as loaded and
+    /// sets its row to what `resolved`, the settled change, would write.
+    pub fn settle(&mut self, conflict: &Conflict, resolved: Option<&Change>) {
+        let id = conflict.before.id.as_str();
+        match &conflict.current {
+            Some(current) => {
+                if let Some(dp) = self.original.iter_mut().find(|dp| dp.id == id) {
+                    *dp = current.clone();
+                }
+            }
+            None => self.original.retain(|dp| dp.id != id),
+        }
+        let Some(pos) = self
+            .rows
+            .iter()
+            .position(|row| row.id.as_deref() == Some(id))
+        else {
+            return;
+        };
+        match (&conflict.current, resolved) {
+            (None, None) => {
+                self.rows.remove(pos);
+            }
+            (None, Some(_)) => {
+                // Recreated with this side's values
+                let row = &mut self.rows[pos];
+                row.id = None;
+                row.original = None;
+            }
+            (Some(current), resolved) => {
+                let row = &mut self.rows[pos];
+                row.original = Some(RowSnapshot::from(current));
+                if !matches!(resolved, Some(Change::Delete(_))) {
+                    let values = resolved
+                        .and_then(Change::proposed)
+                        .unwrap_or_else(|| EditedDatapoint::from(current));
+                    row.timestamp = values.timestamp;
+                    row.value = values.value;
+                    row.comment = values.comment;
+                    row.is_deleted = false;
+                }
+            }
+        }
+        let selected = self
+            .table_state
+            .selected()
+            .map(|idx| idx.min(self.rows.len().saturating_sub(1)));
+        self.table_state
+            .select(selected.filter(|_| !self.rows.is_empty()));
+        self.dirty = self.has_changes();
+    }
+
+    /// The changes turning the loaded datapoints into the current rows.
+    pub fn pending_changes(&self) -> Changeset {
+        let edited: Vec<EditedDatapoint> = self
+            .rows
+            .iter()
+            .filter(|row| !row.is_deleted)
+            .map(EditorRow::edited)
+            .collect();
+        Changeset::diff(&self.original, &edited)
+    }
+
+    /// Whether any row differs from the loaded datapoints.
+    pub fn has_changes(&self) -> bool {
+        self.rows.iter().any(|row| {
+            if row.id.is_none() {
+                !row.is_deleted
+            } else {
+                row.is_deleted || row.is_modified()
+            }
+        })
     }
 
     pub const fn selected_row_index(&self) -> Option<usize> {
@@ -110,13 +401,20 @@ impl DetailState {
     }
 
     pub fn toggle_delete(&mut self) {
+        if self.selected_row_index().is_none() {
+            return;
+        }
+        let snapshot = self.snapshot();
         if let Some(row) = self.selected_row_mut() {
             row.is_deleted = !row.is_deleted;
+            self.push_undo(snapshot);
             self.mark_dirty();
         }
     }
 
     pub fn add_new_row(&mut self) {
+        let snapshot = self.snapshot();
+        self.push_undo(snapshot);
         let now = OffsetDateTime::now_utc();
         let row = EditorRow::new(now);
         self.rows.insert(0, row);
@@ -226,7 +524,7 @@ impl EditInput {
 }
 
 /// A row in the datapoint editor.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct EditorRow {
     pub id: Option<String>,
     pub timestamp: OffsetDateTime,
@@ -237,26 +535,32 @@ pub struct EditorRow {
 }
 
 /// Snapshot of original datapoint values for change detection.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RowSnapshot {
     pub timestamp: OffsetDateTime,
     pub value: f64,
     pub comment: String,
 }
 
+impl From<&Datapoint> for RowSnapshot {
+    fn from(dp: &Datapoint) -> Self {
+        Self {
+            timestamp: dp.timestamp,
+            value: dp.value,
+            comment: dp.comment.clone().unwrap_or_default(),
+        }
+    }
+}
+
 impl EditorRow {
     pub fn from_datapoint(dp: Datapoint) -> Self {
-        let comment = dp.comment.unwrap_or_default();
+        let original = RowSnapshot::from(&dp);
         Self {
             id: Some(dp.id),
             timestamp: dp.timestamp,
             value: dp.value,
-            comment: comment.clone(),
-            original: Some(RowSnapshot {
-                timestamp: dp.timestamp,
-                value: dp.value,
-                comment,
-            }),
+            comment: dp.comment.unwrap_or_default(),
+            original: Some(original),
             is_deleted: false,
         }
     }
@@ -280,6 +584,25 @@ impl EditorRow {
         })
     }
 
+    /// The row's current values.
+    fn snapshot(&self) -> RowSnapshot {
+        RowSnapshot {
+            timestamp: self.timestamp,
+            value: self.value,
+            comment: self.comment.clone(),
+        }
+    }
+
+    /// The row as the changeset engine sees it.
+    pub fn edited(&self) -> EditedDatapoint {
+        EditedDatapoint {
+            id: self.id.clone(),
+            timestamp: self.timestamp,
+            value: self.value,
+            comment: self.comment.clone(),
+        }
+    }
+
     pub fn marker(&self) -> &'static str {
         if self.id.is_none() {
             "+"
@@ -291,6 +614,16 @@ impl EditorRow {
     }
 }
 
+/// Inverse of a saved editor changeset, for "undo last save".
+#[derive(Debug)]
+pub struct SaveUndo {
+    pub slug: String,
+    /// Save this belongs to; resolving conflicts continues the same one
+    pub attempt: u64,
+    /// Reverts whatever part of the save went through
+    pub changeset: Changeset,
+}
+
 /// Clamp an index after applying a delta.
 pub fn clamp_index(current: usize, delta: i32, max: usize) -> usize {
     let current = isize::try_from(current).unwrap_or(0);
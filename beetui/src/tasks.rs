@@ -0,0 +1,277 @@
+//! Background API requests reporting back to the UI thread.
+
+use crate::state::{GoalAction, SaveUndo};
+use beeminder::changeset::{Applied, Change, Conflict};
+use beeminder::entry::TotalEntry;
+use beeminder::types::{Datapoint, GoalFull, GoalSummary, UserInfoDiff};
+use beeminder::Error;
+use std::future::Future;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Instant;
+use time::OffsetDateTime;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_FRAME_MS: u128 = 100;
+
+/// What a background request is doing, used for the status line and for
+/// superseding repeated reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskKind {
+    RefreshGoals,
+    RefreshDiff,
+    AddDatapoint {
+        slug: String,
+    },
+    LoadDetail {
+        slug: String,
+    },
+    SaveDetail {
+        slug: String,
+    },
+    CreateGoal {
+        slug: String,
+    },
+    LoadSettings {
+        slug: String,
+    },
+    SaveSettings {
+        slug: String,
+    },
+    UndoSave {
+        slug: String,
+    },
+    LoadArchived,
+    LoadGoal {
+        slug: String,
+    },
+    Unarchive {
+        slug: String,
+    },
+    Archive {
+        slug: String,
+    },
+    /// Datapoints entered from the command palette
+    AddDatapoints {
+        slugs: Vec<String>,
+    },
+    GoalAction {
+        slug: String,
+        action: GoalAction,
+    },
+}
+
+impl TaskKind {
+    /// Whether the request only reads, so a newer identical one can
+    /// replace it. Writes always run.
+    const fn is_read(&self) -> bool {
+        matches!(
+            self,
+            Self::RefreshGoals
+                | Self::RefreshDiff
+                | Self::LoadDetail { .. }
+                | Self::LoadSettings { .. }
+                | Self::LoadArchived
+                | Self::LoadGoal { .. }
+        )
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Self::RefreshGoals | Self::RefreshDiff => "Refreshing goals".to_string(),
+            Self::AddDatapoint { slug } => format!("Adding datapoint to {slug}"),
+            Self::LoadDetail { slug } => format!("Loading {slug}"),
+            Self::SaveDetail { slug } | Self::SaveSettings { slug } => format!("Saving {slug}"),
+            Self::CreateGoal { slug } => format!("Creating {slug}"),
+            Self::LoadSettings { slug } => format!("Loading settings of {slug}"),
+            Self::UndoSave { slug } => format!("Undoing last save of {slug}"),
+            Self::LoadArchived => "Loading archived goals".to_string(),
+            Self::LoadGoal { slug } => format!("Loading {slug}"),
+            Self::Unarchive { slug } => format!("Unarchiving {slug}"),
+            Self::Archive { slug } => format!("Archiving {slug}"),
+            Self::AddDatapoints { slugs } => format!("Adding datapoints to {}", slugs.join(", ")),
+            Self::GoalAction { slug, action } => format!("{}: {slug}", action.label()),
+        }
+    }
+}
+
+/// Result of a finished background request.
+#[derive(Debug)]
+pub enum TaskOutput {
+    /// Goals and the account timezone plus the time the request was started
+    Goals(Result<(Vec<GoalSummary>, String, OffsetDateTime), Error>),
+    /// Changed goals plus the time the request was started
+    GoalsDiff(Result<(UserInfoDiff, OffsetDateTime), Error>),
+    DatapointAdded {
+        slug: String,
+        result: Result<Datapoint, Error>,
+    },
+    /// Entry from a running total, with the computed change
+    TotalAdded {
+        slug: String,
+        result: Result<(TotalEntry, Datapoint), String>,
+    },
+    /// Results of a command palette chain, in entry order
+    DatapointsAdded(Vec<(String, Result<Datapoint, Error>)>),
+    /// Full goal and its datapoints for the detail screen
+    DetailLoaded {
+        slug: String,
+        result: Result<(GoalFull, Vec<Datapoint>), Error>,
+    },
+    /// Saved editor changes; `applied` and `undo` cover whatever went
+    /// through, even when the save failed part way. `conflicts` holds the
+    /// changes to datapoints changed elsewhere, left unapplied.
+    DetailSaved {
+        slug: String,
+        applied: Vec<Applied>,
+        undo: SaveUndo,
+        result: Result<(), Error>,
+        conflicts: Vec<(Change, Conflict)>,
+    },
+    SaveUndone {
+        slug: String,
+        result: Result<(), Error>,
+    },
+    ArchivedGoals(Result<Vec<GoalSummary>, Error>),
+    /// Full goal for the action modal
+    GoalLoaded {
+        slug: String,
+        result: Result<GoalFull, Error>,
+    },
+    /// Unarchive or goal action finished; `Ok` holds a status message
+    GoalChanged {
+        slug: String,
+        result: Result<String, Error>,
+    },
+    GoalCreated {
+        slug: String,
+        result: Result<GoalFull, Error>,
+    },
+    SettingsLoaded {
+        slug: String,
+        result: Result<GoalFull, Error>,
+    },
+    SettingsSaved {
+        slug: String,
+        result: Result<GoalFull, Error>,
+    },
+}
+
+struct TaskMessage {
+    id: u64,
+    output: TaskOutput,
+}
+
+struct PendingTask {
+    id: u64,
+    kind: TaskKind,
+    handle: JoinHandle<()>,
+}
+
+/// Spawns requests on the tokio runtime and collects their results.
+pub struct Tasks {
+    runtime: Handle,
+    sender: Sender<TaskMessage>,
+    receiver: Receiver<TaskMessage>,
+    pending: Vec<PendingTask>,
+    next_id: u64,
+    started: Instant,
+}
+
+impl Tasks {
+    pub fn new(runtime: Handle) -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            runtime,
+            sender,
+            receiver,
+            pending: Vec::new(),
+            next_id: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Runs `future` in the background. An identical read still in flight
+    /// is aborted, since it may predate a write the new one should see.
+    pub fn spawn<F>(&mut self, kind: TaskKind, future: F)
+    where
+        F: Future<Output = TaskOutput> + Send + 'static,
+    {
+        if kind.is_read() {
+            if let Some(pos) = self.pending.iter().position(|task| task.kind == kind) {
+                self.pending.remove(pos).handle.abort();
+            }
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let sender = self.sender.clone();
+        let handle = self.runtime.spawn(async move {
+            let output = future.await;
+            let _ = sender.send(TaskMessage { id, output });
+        });
+        self.pending.push(PendingTask { id, kind, handle });
+    }
+
+    pub const fn is_busy(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Returns the next finished task, if any. Results of cancelled tasks
+    /// are dropped.
+    pub fn try_recv(&mut self) -> Option<TaskOutput> {
+        while let Ok(message) = self.receiver.try_recv() {
+            if let Some(pos) = self.pending.iter().position(|task| task.id == message.id) {
+                self.pending.remove(pos);
+                return Some(message.output);
+            }
+        }
+        None
+    }
+
+    /// Aborts the in-flight reads and returns their kinds. Writes keep
+    /// running, since aborting one would lose what it already changed.
+    pub fn cancel_reads(&mut self) -> Vec<TaskKind> {
+        let (reads, writes) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|task| task.kind.is_read());
+        self.pending = writes;
+        reads
+            .into_iter()
+            .map(|task| {
+                task.handle.abort();
+                task.kind
+            })
+            .collect()
+    }
+
+    /// Kinds of the writes still in flight.
+    pub fn pending_writes(&self) -> Vec<&TaskKind> {
+        self.pending
+            .iter()
+            .map(|task| &task.kind)
+            .filter(|kind| !kind.is_read())
+            .collect()
+    }
+
+    /// Spinner and description of in-flight requests for the status line.
+    pub fn progress_text(&self) -> Option<String> {
+        let first = self.pending.first()?;
+        let ticks = self.started.elapsed().as_millis() / SPINNER_FRAME_MS;
+        let frame = usize::try_from(ticks).unwrap_or(0) % SPINNER.len();
+        let more = match self.pending.len() {
+            1 => String::new(),
+            n => format!(" (+{} more)", n - 1),
+        };
+        let hint = if self.pending.iter().any(|task| task.kind.is_read()) {
+            "  Esc: cancel"
+        } else {
+            ""
+        };
+        Some(format!(
+            "{} {}...{more}{hint}",
+            SPINNER[frame],
+            first.kind.label()
+        ))
+    }
+}
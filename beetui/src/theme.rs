@@ -0,0 +1,134 @@
+//! Configurable colors.
+
+use anyhow::{anyhow, Result};
+use beeconfig::{ThemeConfig, ThemePreset};
+use ratatui::style::Color;
+use std::str::FromStr;
+
+/// Color zone of a goal by safety buffer, most urgent first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Zone {
+    Red,
+    Yellow,
+    Blue,
+    Green,
+    White,
+}
+
+impl Zone {
+    /// Zone a safety buffer falls in; derailed goals (below zero) are red.
+    pub const fn of(safebuf: i32) -> Self {
+        match safebuf {
+            ..=0 => Self::Red,
+            1 => Self::Yellow,
+            2 => Self::Blue,
+            3..=6 => Self::Green,
+            _ => Self::White,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Red => "red",
+            Self::Yellow => "yellow",
+            Self::Blue => "blue",
+            Self::Green => "green",
+            Self::White => "white",
+        }
+    }
+}
+
+/// Colors used by the UI, resolved from `[tui.theme]`.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub red: Color,
+    pub yellow: Color,
+    pub blue: Color,
+    pub green: Color,
+    pub white: Color,
+    pub info: Color,
+    pub success: Color,
+    pub error: Color,
+    pub alert: Color,
+    pub accent: Color,
+    pub highlight: Color,
+}
+
+impl Theme {
+    const fn preset(preset: ThemePreset) -> Self {
+        match preset {
+            ThemePreset::Dark => Self {
+                red: Color::Red,
+                yellow: Color::Yellow,
+                blue: Color::Blue,
+                green: Color::Green,
+                white: Color::White,
+                info: Color::Blue,
+                success: Color::Green,
+                error: Color::Red,
+                alert: Color::Magenta,
+                accent: Color::Yellow,
+                highlight: Color::Green,
+            },
+            // Dark shades that stay readable on a white background.
+            ThemePreset::Light => Self {
+                red: Color::Rgb(0xc0, 0x1c, 0x28),
+                yellow: Color::Rgb(0xa6, 0x6a, 0x00),
+                blue: Color::Rgb(0x1a, 0x5f, 0xb4),
+                green: Color::Rgb(0x26, 0xa2, 0x69),
+                white: Color::Black,
+                info: Color::Rgb(0x1a, 0x5f, 0xb4),
+                success: Color::Rgb(0x26, 0xa2, 0x69),
+                error: Color::Rgb(0xc0, 0x1c, 0x28),
+                alert: Color::Rgb(0x81, 0x3d, 0x9c),
+                accent: Color::Rgb(0xa6, 0x6a, 0x00),
+                highlight: Color::Rgb(0x8f, 0xf0, 0xa4),
+            },
+        }
+    }
+
+    /// Applies the configured overrides on top of the preset, rejecting
+    /// colors ratatui cannot parse.
+    pub fn from_config(config: &ThemeConfig) -> Result<Self> {
+        let mut theme = Self::preset(config.preset);
+        let mut problems = Vec::new();
+        let overrides = [
+            ("red", &config.red, &mut theme.red),
+            ("yellow", &config.yellow, &mut theme.yellow),
+            ("blue", &config.blue, &mut theme.blue),
+            ("green", &config.green, &mut theme.green),
+            ("white", &config.white, &mut theme.white),
+            ("info", &config.info, &mut theme.info),
+            ("success", &config.success, &mut theme.success),
+            ("error", &config.error, &mut theme.error),
+            ("alert", &config.alert, &mut theme.alert),
+            ("accent", &config.accent, &mut theme.accent),
+            ("highlight", &config.highlight, &mut theme.highlight),
+        ];
+        for (name, value, color) in overrides {
+            let Some(value) = value else {
+                continue;
+            };
+            match Color::from_str(value) {
+                Ok(parsed) => *color = parsed,
+                Err(_) => problems.push(format!("{name}: unknown color '{value}'")),
+            }
+        }
+        if problems.is_empty() {
+            Ok(theme)
+        } else {
+            Err(anyhow!("Invalid [tui.theme]: {}", problems.join("; ")))
+        }
+    }
+
+    /// Color of the zone a goal's safety buffer falls in.
+    pub const fn goal_color(&self, safebuf: i32) -> Color {
+        match Zone::of(safebuf) {
+            Zone::Red => self.red,
+            Zone::Yellow => self.yellow,
+            Zone::Blue => self.blue,
+            Zone::Green => self.green,
+            Zone::White => self.white,
+        }
+    }
+}
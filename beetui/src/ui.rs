@@ -1,29 +1,49 @@
 //! UI rendering functions.
 
-use crate::app::{has_entry_today, App};
+use crate::app::{group_label, has_entry_today, reminders_due, App};
+use crate::command::{completions, MAX_CANDIDATES};
+use crate::form::{CreateGoalState, FieldKind, Form, SettingsState, WizardStep};
+use crate::keys::{Action, Context, Keymap};
 use crate::state::{
-    DetailState, EditorCol, EditorRow, MainInput, Screen, StatusKind, StatusMessage,
+    DetailState, EditInput, EditorCol, EditorRow, GoalAction, GoalActionState, MainInput, Screen,
+    StatusKind, FLASH_PERIOD,
 };
-use beeconfig::format_timestamp;
+use crate::theme::Theme;
+use beeconfig::{format_timestamp, GoalColumn, GroupMode};
+use beeminder::changeset::EditedDatapoint;
+use beeminder::types::{GoalFull, GoalSummary};
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Position, Rect};
 use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::Marker;
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState};
+use ratatui::widgets::{
+    Axis, Block, Borders, Cell, Chart, Clear, Dataset, GraphType, Paragraph, Row, Table,
+    TableState, Wrap,
+};
 use std::time::Duration;
+use time::OffsetDateTime;
 use unicode_width::UnicodeWidthStr;
 
 /// Render the application based on current screen.
 pub fn render_app(f: &mut ratatui::Frame, app: &mut App) {
-    if matches!(app.screen, Screen::Main) {
-        render_main(f, app);
-    } else {
-        let status = app.status.clone();
-        let mut detail = match std::mem::replace(&mut app.screen, Screen::Main) {
-            Screen::Detail(detail) => detail,
-            Screen::Main => return,
-        };
-        render_detail(f, status.as_ref(), &mut detail);
-        app.screen = Screen::Detail(detail);
+    let context = app.key_context();
+    match std::mem::replace(&mut app.screen, Screen::Main) {
+        Screen::Main => render_main(f, app),
+        Screen::Detail(mut detail) => {
+            render_detail(f, app, &mut detail);
+            app.screen = Screen::Detail(detail);
+        }
+        Screen::CreateGoal(mut wizard) => {
+            render_create_goal(f, app, &mut wizard);
+            app.screen = Screen::CreateGoal(wizard);
+        }
+        Screen::Settings(mut settings) => {
+            render_settings(f, app, &mut settings);
+            app.screen = Screen::Settings(settings);
+        }
+    }
+    if app.show_help {
+        render_help(f, &app.keymap, context);
     }
 }
 
@@ -34,16 +54,25 @@ fn render_main(f: &mut ratatui::Frame, app: &mut App) {
         .constraints([Constraint::Min(3), Constraint::Length(2)])
         .split(size);
 
+    let mut title = if app.show_archived {
+        format!("beetui (archived) - sort: {}", app.config.tui.sort.label())
+    } else {
+        format!("beetui - sort: {}", app.config.tui.sort.label())
+    };
+    if app.config.tui.group != GroupMode::None {
+        title.push_str(&format!(", group: {}", app.config.tui.group.label()));
+    }
     let block = Block::default()
-        .title_top("beetui")
-        .title_top(Line::from("[r]efresh  [q]uit").right_aligned())
+        .title_top(title)
+        .title_top(Line::from(app.keymap.hints(&[(Action::Help, "help")])).right_aligned())
         .borders(Borders::ALL);
 
     let inner = block.inner(layout[0]);
     f.render_widget(block, layout[0]);
 
-    let rows = build_goal_rows(app);
-    let widths = build_goal_widths(app);
+    let now = OffsetDateTime::now_utc();
+    let rows = build_goal_rows(app, now);
+    let widths = build_goal_widths(app, now);
 
     let table = Table::new(rows, widths)
         .column_spacing(1)
@@ -57,13 +86,7 @@ fn render_main(f: &mut ratatui::Frame, app: &mut App) {
         f.render_stateful_widget(table, inner, &mut app.main_state);
     }
 
-    render_footer_main(
-        f,
-        app.status.as_ref(),
-        &app.main_input,
-        &app.filter,
-        layout[1],
-    );
+    render_footer_main(f, app, layout[1]);
 
     if let MainInput::InlineAdd { buffer } = &app.main_input {
         let prompt = format!("Add datapoint: {buffer}");
@@ -74,9 +97,91 @@ fn render_main(f: &mut ratatui::Frame, app: &mut App) {
         let prompt = format!("Filter: {buffer}");
         set_footer_cursor(f, layout[1], UnicodeWidthStr::width(prompt.as_str()));
     }
+
+    if let MainInput::Command(palette) = &app.main_input {
+        set_footer_cursor(f, layout[1], 1 + palette.input.cursor_display_width());
+    }
+
+    if let MainInput::GoalAction(state) = &app.main_input {
+        render_goal_action(f, &app.theme, state, size);
+    }
 }
 
-fn build_goal_rows(app: &App) -> Vec<Row<'static>> {
+fn render_goal_action(f: &mut ratatui::Frame, theme: &Theme, state: &GoalActionState, area: Rect) {
+    let popup = centered_rect(70, 60, area);
+    f.render_widget(Clear, popup);
+
+    let block = Block::default()
+        .title_top(format!("Goal actions: {}", state.slug))
+        .borders(Borders::ALL);
+    let inner = block.inner(popup);
+    f.render_widget(block, popup);
+
+    let mut lines = Vec::new();
+    match &state.goal {
+        Some(goal) => {
+            let pledge = goal
+                .pledge
+                .map_or_else(|| "-".to_string(), |pledge| format!("${pledge:.0}"));
+            lines.push(Line::from(format!("Pledge: {pledge}")));
+            let contract = goal.contract.as_ref();
+            let amount = contract
+                .and_then(|contract| contract.amount)
+                .map_or_else(|| "-".to_string(), |amount| format!("${amount:.0}"));
+            let stepdown = contract
+                .and_then(|contract| contract.stepdown_at)
+                .map_or_else(|| "none".to_string(), format_timestamp);
+            lines.push(Line::from(format!(
+                "Contract: {amount} at risk, stepdown scheduled: {stepdown}"
+            )));
+        }
+        None => lines.push(Line::from("Loading contract...")),
+    }
+    lines.push(Line::from(""));
+
+    for action in GoalAction::VALUES {
+        if action == state.action {
+            lines.push(Line::from(Span::styled(
+                format!("> {}", action.label()),
+                Style::default()
+                    .fg(theme.accent)
+                    .add_modifier(Modifier::BOLD),
+            )));
+        } else {
+            lines.push(Line::from(format!("  {}", action.label())));
+        }
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(state.action.description()));
+    lines.push(Line::from(""));
+    lines.push(Line::from(format!(
+        "Type '{}' and press Enter to confirm (Up/Down: choose, Esc: cancel)",
+        state.slug
+    )));
+    let input_row = lines.len();
+    let confirm_style = if state.is_confirmed() {
+        Style::default().fg(theme.success)
+    } else {
+        Style::default()
+    };
+    lines.push(Line::from(Span::styled(
+        format!("> {}", state.typed.buffer),
+        confirm_style,
+    )));
+
+    f.render_widget(Paragraph::new(lines), inner);
+
+    let row = u16::try_from(input_row).unwrap_or(u16::MAX);
+    if inner.width == 0 || row >= inner.height {
+        return;
+    }
+    let offset = u16::try_from(state.typed.cursor_display_width() + 2).unwrap_or(u16::MAX);
+    let max_x = inner.x + inner.width - 1;
+    let cursor_x = inner.x.saturating_add(offset).min(max_x);
+    f.set_cursor_position(Position::new(cursor_x, inner.y + row));
+}
+
+fn build_goal_rows(app: &App, now: OffsetDateTime) -> Vec<Row<'static>> {
     let mut rows = Vec::new();
     let highlight_goal = app
         .last_success_goal
@@ -84,11 +189,20 @@ fn build_goal_rows(app: &App) -> Vec<Row<'static>> {
         .filter(|(_, at)| at.elapsed() < Duration::from_secs(2))
         .map(|(slug, _)| slug.as_str());
 
+    let group = app.config.tui.group;
+    let columns = app.config.display.goal_columns();
+    let mut previous_group = None;
     for (row_idx, goal_idx) in app.filtered.iter().enumerate() {
-        let Some(goal) = app.goals.get(*goal_idx) else {
+        let Some(goal) = app.visible_goals().get(*goal_idx) else {
             continue;
         };
-        let check = if has_entry_today(goal) { "x" } else { " " };
+        let check = if has_entry_today(goal, &app.clock) {
+            "x"
+        } else if reminders_due(goal, &app.clock) {
+            "!"
+        } else {
+            " "
+        };
         let mut slug = goal.slug.clone();
         let mut limsum = goal.limsum.clone();
 
@@ -99,21 +213,28 @@ fn build_goal_rows(app: &App) -> Vec<Row<'static>> {
             }
         }
 
-        let mut cells = Vec::new();
-        cells.push(Cell::from(check));
+        let mut cells = vec![Cell::from(check)];
+        if group != GroupMode::None {
+            // Only the first goal of each group shows its label.
+            let label = group_label(goal, group);
+            let cell = if previous_group.as_ref() == Some(&label) {
+                Cell::from("")
+            } else {
+                Cell::from(label.clone()).style(Style::default().add_modifier(Modifier::BOLD))
+            };
+            cells.push(cell);
+            previous_group = Some(label);
+        }
         cells.push(Cell::from(slug));
-        cells.push(Cell::from(limsum));
-
-        if app.config.display.show_pledge {
-            let pledge =
-                goal_pledge(goal).map_or_else(|| "-".to_string(), |value| format!("${value:.0}"));
-            cells.push(Cell::from(pledge));
+        for column in &columns {
+            cells.push(Cell::from(goal_column_text(goal, *column, now)));
         }
+        cells.push(Cell::from(limsum));
 
-        let mut style = Style::default().fg(goal_color(goal.safebuf));
+        let mut style = Style::default().fg(app.theme.goal_color(goal.safebuf));
         if let Some(slug) = highlight_goal {
             if goal.slug == slug {
-                style = style.bg(Color::Green).fg(Color::Black);
+                style = style.bg(app.theme.highlight).fg(Color::Black);
             }
         }
 
@@ -123,19 +244,66 @@ fn build_goal_rows(app: &App) -> Vec<Row<'static>> {
     rows
 }
 
-fn build_goal_widths(app: &App) -> Vec<Constraint> {
-    let mut widths = vec![
-        Constraint::Length(2),
-        Constraint::Length(20),
-        Constraint::Min(10),
-    ];
-    if app.config.display.show_pledge {
-        widths.push(Constraint::Length(7));
+fn goal_column_text(goal: &GoalSummary, column: GoalColumn, now: OffsetDateTime) -> String {
+    match column {
+        GoalColumn::Countdown => format_countdown(goal.losedate - now),
+        GoalColumn::Pledge => {
+            goal_pledge(goal).map_or_else(|| "-".to_string(), |value| format!("${value:.0}"))
+        }
+        GoalColumn::LastValue => goal
+            .extra
+            .get("last_datapoint")
+            .and_then(|dp| dp.get("value"))
+            .and_then(serde_json::Value::as_f64)
+            .map_or_else(|| "-".to_string(), |value| value.to_string()),
+        GoalColumn::Rate => goal.rate.map_or_else(
+            || "-".to_string(),
+            |rate| {
+                let runits = goal
+                    .extra
+                    .get("runits")
+                    .and_then(serde_json::Value::as_str)
+                    .unwrap_or("d");
+                format!("{rate}/{runits}")
+            },
+        ),
+        GoalColumn::DaysSinceEntry => match (now - goal.lastday).whole_days() {
+            ..=0 => "today".to_string(),
+            days => format!("{days}d ago"),
+        },
+    }
+}
+
+fn goal_column_width(app: &App, column: GoalColumn, now: OffsetDateTime) -> u16 {
+    match column {
+        // Countdowns grow with the days left, so fit the longest one shown.
+        GoalColumn::Countdown => app
+            .filtered
+            .iter()
+            .filter_map(|idx| app.visible_goals().get(*idx))
+            .map(|goal| format_countdown(goal.losedate - now).len())
+            .max()
+            .map_or(8, |len| u16::try_from(len).unwrap_or(u16::MAX)),
+        GoalColumn::Pledge => 7,
+        GoalColumn::LastValue | GoalColumn::Rate => 9,
+        GoalColumn::DaysSinceEntry => 8,
+    }
+}
+
+fn build_goal_widths(app: &App, now: OffsetDateTime) -> Vec<Constraint> {
+    let mut widths = vec![Constraint::Length(2)];
+    if app.config.tui.group != GroupMode::None {
+        widths.push(Constraint::Length(12));
     }
+    widths.push(Constraint::Length(20));
+    for column in app.config.display.goal_columns() {
+        widths.push(Constraint::Length(goal_column_width(app, column, now)));
+    }
+    widths.push(Constraint::Min(10));
     widths
 }
 
-fn render_detail(f: &mut ratatui::Frame, status: Option<&StatusMessage>, detail: &mut DetailState) {
+fn render_detail(f: &mut ratatui::Frame, app: &App, detail: &mut DetailState) {
     let size = f.area();
     let layout = Layout::default()
         .direction(Direction::Vertical)
@@ -147,19 +315,29 @@ fn render_detail(f: &mut ratatui::Frame, status: Option<&StatusMessage>, detail:
     } else {
         detail.goal_title.clone()
     };
-    let title = format!("{display} - Edit Datapoints");
+    let title = if detail.saving {
+        format!("{display} - Edit Datapoints (saving)")
+    } else {
+        format!("{display} - Edit Datapoints")
+    };
     let block = Block::default()
         .title_top(title)
-        .title_top(Line::from("[?]help").right_aligned())
+        .title_top(Line::from(app.keymap.hints(&[(Action::Help, "help")])).right_aligned())
         .borders(Borders::ALL);
 
-    let inner = block.inner(layout[0]);
-    f.render_widget(block, layout[0]);
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(layout[0]);
+    render_goal_info(f, app, detail, panes[0]);
+
+    let inner = block.inner(panes[1]);
+    f.render_widget(block, panes[1]);
 
     let header_cells = EditorCol::VALUES.iter().map(|col| {
         let style = if *col == detail.selected_col {
             Style::default()
-                .fg(Color::Yellow)
+                .fg(app.theme.accent)
                 .add_modifier(Modifier::BOLD)
         } else {
             Style::default()
@@ -172,7 +350,7 @@ fn render_detail(f: &mut ratatui::Frame, status: Option<&StatusMessage>, detail:
         .rows
         .iter()
         .enumerate()
-        .map(|(idx, row)| build_editor_row(row, detail, idx))
+        .map(|(idx, row)| build_editor_row(row, detail, idx, &app.theme))
         .collect::<Vec<_>>();
 
     let widths = vec![
@@ -194,11 +372,171 @@ fn render_detail(f: &mut ratatui::Frame, status: Option<&StatusMessage>, detail:
         f.render_stateful_widget(table, inner, &mut detail.table_state);
     }
 
-    render_footer_detail(f, status, detail, layout[1]);
+    render_footer_detail(f, app, detail, layout[1]);
     render_detail_input_modal(f, detail, size);
+    render_conflict_modal(f, detail, size);
+}
+
+/// Goal metadata above a chart of the editor's datapoints.
+fn render_goal_info(f: &mut ratatui::Frame, app: &App, detail: &DetailState, area: Rect) {
+    let goal = &detail.goal;
+    let block = Block::default().title_top("Goal").borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let lines = goal_info_lines(goal, &app.theme);
+    let info_height = u16::try_from(lines.len())
+        .unwrap_or(u16::MAX)
+        .min(inner.height / 2);
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(info_height),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .split(inner);
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: true }), sections[0]);
+
+    let cumulative = goal.kyoom.unwrap_or(false);
+    let points = chart_points(&detail.rows, cumulative);
+    if points.len() < 2 {
+        let empty = Paragraph::new("Not enough datapoints to chart").alignment(Alignment::Center);
+        f.render_widget(empty, sections[2]);
+        return;
+    }
+
+    let (first, last) = (points[0].0, points[points.len() - 1].0);
+    let (low, high) = points
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), (_, y)| {
+            (low.min(*y), high.max(*y))
+        });
+    let (low, high) = if low == high {
+        (low - 1.0, high + 1.0)
+    } else {
+        (low, high)
+    };
+    let date_label = |seconds: f64| {
+        #[allow(clippy::cast_possible_truncation)]
+        OffsetDateTime::from_unix_timestamp(seconds as i64)
+            .map(|ts| format_timestamp(ts).chars().take(10).collect::<String>())
+            .unwrap_or_default()
+    };
+    let color = app.theme.goal_color(goal.safebuf.unwrap_or(i32::MAX));
+    let dataset = Dataset::default()
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(color))
+        .data(&points);
+    let chart = Chart::new(vec![dataset])
+        .x_axis(
+            Axis::default()
+                .bounds([first, last])
+                .labels([date_label(first), date_label(last)]),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([low, high])
+                .labels([format!("{low}"), format!("{high}")]),
+        );
+    f.render_widget(chart, sections[2]);
 }
 
-fn build_editor_row<'a>(row: &'a EditorRow, detail: &DetailState, idx: usize) -> Row<'a> {
+fn goal_info_lines(goal: &GoalFull, theme: &Theme) -> Vec<Line<'static>> {
+    let number = |value: Option<f64>| value.map_or_else(|| "-".to_string(), |v| v.to_string());
+    let mut fields = Vec::new();
+    if let Some(limsum) = &goal.limsum {
+        fields.push(("Needed", limsum.clone()));
+    }
+    let rate = goal.rate.map_or_else(
+        || "-".to_string(),
+        |rate| {
+            let runits = goal.runits.as_deref().unwrap_or("d");
+            match goal.gunits.as_deref().filter(|units| !units.is_empty()) {
+                Some(gunits) => format!("{rate} {gunits}/{runits}"),
+                None => format!("{rate}/{runits}"),
+            }
+        },
+    );
+    fields.push(("Rate", rate));
+    fields.push(("Current", number(goal.curval)));
+    fields.push(("Delta", number(goal.delta)));
+    fields.push((
+        "Pledge",
+        goal.pledge
+            .map_or_else(|| "-".to_string(), |pledge| format!("${pledge:.0}")),
+    ));
+    if let Some(stepdown) = goal
+        .contract
+        .as_ref()
+        .and_then(|contract| contract.stepdown_at)
+    {
+        fields.push(("Stepdown", format_timestamp(stepdown)));
+    }
+    fields.push((
+        "Autodata",
+        goal.autodata
+            .clone()
+            .filter(|source| !source.is_empty())
+            .unwrap_or_else(|| "manual".to_string()),
+    ));
+    if let Some(tags) = goal.tags.as_ref().filter(|tags| !tags.is_empty()) {
+        fields.push(("Tags", tags.join(", ")));
+    }
+
+    let mut lines: Vec<Line> = fields
+        .into_iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{label:<10}"),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(value),
+            ])
+        })
+        .collect();
+    if let Some(fineprint) = goal.fineprint.as_ref().filter(|text| !text.is_empty()) {
+        lines.push(Line::from(Span::styled(
+            fineprint.clone(),
+            Style::default().fg(theme.info),
+        )));
+    }
+    lines
+}
+
+/// Chart points as (unix seconds, value), summed for cumulative goals.
+/// Uses the editor rows so unsaved edits show up immediately.
+fn chart_points(rows: &[EditorRow], cumulative: bool) -> Vec<(f64, f64)> {
+    let mut points: Vec<(i64, f64)> = rows
+        .iter()
+        .filter(|row| !row.is_deleted)
+        .map(|row| (row.timestamp.unix_timestamp(), row.value))
+        .collect();
+    points.sort_by_key(|(timestamp, _)| *timestamp);
+    let mut total = 0.0;
+    #[allow(clippy::cast_precision_loss)]
+    points
+        .into_iter()
+        .map(|(timestamp, value)| {
+            let y = if cumulative {
+                total += value;
+                total
+            } else {
+                value
+            };
+            (timestamp as f64, y)
+        })
+        .collect()
+}
+
+fn build_editor_row<'a>(
+    row: &'a EditorRow,
+    detail: &DetailState,
+    idx: usize,
+    theme: &Theme,
+) -> Row<'a> {
     let timestamp = format!("{}{}", row.marker(), format_timestamp(row.timestamp));
     let value = if row.id.is_none() && row.value == 0.0 {
         String::new()
@@ -216,11 +554,11 @@ fn build_editor_row<'a>(row: &'a EditorRow, detail: &DetailState, idx: usize) ->
 
     let mut style = Style::default();
     if row.is_deleted {
-        style = style.fg(Color::Red).add_modifier(Modifier::DIM);
+        style = style.fg(theme.error).add_modifier(Modifier::DIM);
     } else if row.id.is_none() {
-        style = style.fg(Color::Cyan);
+        style = style.fg(theme.info);
     } else if row.is_modified() {
-        style = style.fg(Color::Yellow);
+        style = style.fg(theme.accent);
     }
 
     let mut cells = vec![
@@ -245,15 +583,24 @@ fn build_editor_row<'a>(row: &'a EditorRow, detail: &DetailState, idx: usize) ->
     Row::new(cells).style(style)
 }
 
-fn render_status_line(f: &mut ratatui::Frame, status: Option<&StatusMessage>, area: Rect) {
-    let widget = status.map(|status| {
-        let style = match status.kind {
-            StatusKind::Info => Style::default().fg(Color::Blue),
-            StatusKind::Success => Style::default().fg(Color::Green),
-            StatusKind::Error => Style::default().fg(Color::Red),
-        };
-        Paragraph::new(status.text.clone()).style(style)
-    });
+fn render_status_line(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    let status = app.status.as_ref();
+    let theme = &app.theme;
+    if let Some(progress) = app.tasks.progress_text() {
+        let mut spans = vec![Span::styled(progress, Style::default().fg(theme.accent))];
+        if let Some(status) = status {
+            spans.push(Span::raw("  "));
+            spans.push(Span::styled(
+                status.text.clone(),
+                status_style(status.kind, theme),
+            ));
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)), area);
+        return;
+    }
+
+    let widget = status
+        .map(|status| Paragraph::new(status.text.clone()).style(status_style(status.kind, theme)));
 
     if let Some(widget) = widget {
         f.render_widget(widget, area);
@@ -262,23 +609,71 @@ fn render_status_line(f: &mut ratatui::Frame, status: Option<&StatusMessage>, ar
     }
 }
 
-fn render_footer_detail(
-    f: &mut ratatui::Frame,
-    status: Option<&StatusMessage>,
-    detail: &DetailState,
-    area: Rect,
-) {
+fn status_style(kind: StatusKind, theme: &Theme) -> Style {
+    match kind {
+        StatusKind::Info => Style::default().fg(theme.info),
+        StatusKind::Success => Style::default().fg(theme.success),
+        StatusKind::Error => Style::default().fg(theme.error),
+        StatusKind::Alert => {
+            let style = Style::default()
+                .fg(theme.alert)
+                .add_modifier(Modifier::BOLD);
+            if flash_on() {
+                style.add_modifier(Modifier::REVERSED)
+            } else {
+                style
+            }
+        }
+    }
+}
+
+/// Alternates every `FLASH_PERIOD`, based on wall-clock time.
+fn flash_on() -> bool {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis());
+    (millis / FLASH_PERIOD.as_millis()).is_multiple_of(2)
+}
+
+/// Formats the time left until derailment, e.g. "2d 03:12:45" or "-00:05:00".
+fn format_countdown(remaining: time::Duration) -> String {
+    let sign = if remaining.is_negative() { "-" } else { "" };
+    let total = remaining.whole_seconds().unsigned_abs();
+    let days = total / 86_400;
+    let hours = (total % 86_400) / 3600;
+    let minutes = (total % 3600) / 60;
+    let seconds = total % 60;
+    if days > 0 {
+        format!("{sign}{days}d {hours:02}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{sign}{hours:02}:{minutes:02}:{seconds:02}")
+    }
+}
+
+fn render_footer_detail(f: &mut ratatui::Frame, app: &App, detail: &DetailState, area: Rect) {
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Length(1), Constraint::Length(1)])
         .split(area);
 
-    render_status_line(f, status, layout[0]);
+    render_status_line(f, app, layout[0]);
 
     let line = if detail.input.is_some() {
         Line::from("Enter: confirm  Esc: cancel")
+    } else if !detail.conflicts.is_empty() {
+        Line::from("m: merge  o: overwrite with yours  t/Esc: keep theirs")
     } else {
-        Line::from("j/k or up/down: move  h/l or left/right: column  Enter: edit  n: new  d: delete  s: save  Esc: back")
+        Line::from(app.keymap.hints(&[
+            (Action::Down, "move"),
+            (Action::Right, "column"),
+            (Action::EditCell, "edit"),
+            (Action::NewRow, "new"),
+            (Action::DeleteRow, "delete"),
+            (Action::Undo, "undo"),
+            (Action::Redo, "redo"),
+            (Action::Save, "save"),
+            (Action::Back, "back"),
+        ]))
     };
 
     let footer = Paragraph::new(line);
@@ -286,15 +681,73 @@ fn render_footer_detail(
 }
 
 fn render_detail_input_modal(f: &mut ratatui::Frame, detail: &DetailState, area: Rect) {
-    let Some(input) = &detail.input else {
+    if let Some(input) = &detail.input {
+        let title = format!("Edit {}", detail.selected_col.label());
+        render_input_modal(f, &title, input, area);
+    }
+}
+
+/// The loaded, server and edited versions of the first datapoint the save
+/// found changed elsewhere.
+fn render_conflict_modal(f: &mut ratatui::Frame, detail: &DetailState, area: Rect) {
+    let Some((change, conflict)) = detail.conflicts.first() else {
         return;
     };
+    let describe = |dp: Option<EditedDatapoint>| {
+        dp.map_or_else(
+            || "(deleted)".to_string(),
+            |dp| {
+                format!(
+                    "{}  {}  {}",
+                    format_timestamp(dp.timestamp),
+                    dp.value,
+                    dp.comment
+                )
+            },
+        )
+    };
+    let lines = vec![
+        Line::from(format!(
+            "Datapoint {} was changed elsewhere since it was loaded.",
+            conflict.before.id
+        )),
+        Line::from(""),
+        Line::from(format!(
+            "loaded  {}",
+            describe(Some(EditedDatapoint::from(&conflict.before)))
+        )),
+        Line::from(format!(
+            "server  {}",
+            describe(conflict.current.as_ref().map(EditedDatapoint::from))
+        )),
+        Line::from(format!("yours   {}", describe(change.proposed()))),
+        Line::from(""),
+        Line::from("m: merge your changes  o: overwrite with yours  t: keep theirs"),
+    ];
 
+    let popup = centered_rect(70, 30, area);
+    f.render_widget(Clear, popup);
+    let title = if detail.conflicts.len() > 1 {
+        format!("Conflict (1 of {})", detail.conflicts.len())
+    } else {
+        "Conflict".to_string()
+    };
+    let block = Block::default().title_top(title).borders(Borders::ALL);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        popup,
+    );
+}
+
+fn render_input_modal(f: &mut ratatui::Frame, title: &str, input: &EditInput, area: Rect) {
     let popup = centered_rect(60, 20, area);
     f.render_widget(Clear, popup);
 
-    let title = format!("Edit {}", detail.selected_col.label());
-    let block = Block::default().title_top(title).borders(Borders::ALL);
+    let block = Block::default()
+        .title_top(title.to_string())
+        .borders(Borders::ALL);
     let inner = block.inner(popup);
     f.render_widget(block, popup);
     f.render_widget(Paragraph::new(input.buffer.as_str()), inner);
@@ -310,6 +763,251 @@ fn render_detail_input_modal(f: &mut ratatui::Frame, detail: &DetailState, area:
     f.set_cursor_position(Position::new(cursor_x, cursor_y));
 }
 
+fn render_create_goal(f: &mut ratatui::Frame, app: &App, wizard: &mut CreateGoalState) {
+    let size = f.area();
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(2)])
+        .split(size);
+
+    let position = WizardStep::VALUES
+        .iter()
+        .position(|step| *step == wizard.step)
+        .unwrap_or(0);
+    let mut title = format!(
+        "New goal - step {}/{}: {}",
+        position + 1,
+        WizardStep::VALUES.len(),
+        wizard.step.label()
+    );
+    if wizard.saving {
+        title.push_str(" (creating)");
+    }
+    let block = Block::default().title_top(title).borders(Borders::ALL);
+    let inner = block.inner(layout[0]);
+    f.render_widget(block, layout[0]);
+
+    let preview = match wizard.step {
+        WizardStep::Commitment | WizardStep::Review => Some(wizard.rate_preview()),
+        WizardStep::Basics | WizardStep::Details => None,
+    };
+    let preview_line = preview.map(|preview| match preview {
+        Ok(text) => Line::from(Span::styled(
+            format!("Preview: {text}"),
+            Style::default().fg(app.theme.accent),
+        )),
+        Err(err) => Line::from(Span::styled(
+            format!("Preview: {err}"),
+            Style::default().fg(Color::DarkGray),
+        )),
+    });
+
+    if wizard.step == WizardStep::Review {
+        let mut lines: Vec<Line> = wizard
+            .summary()
+            .into_iter()
+            .map(|(label, value)| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("{label:<12}"),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(value),
+                ])
+            })
+            .collect();
+        lines.push(Line::from(""));
+        lines.extend(preview_line);
+        f.render_widget(Paragraph::new(lines), inner);
+    } else if let Some(form) = wizard.form_mut() {
+        let sections = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(2)])
+            .split(inner);
+        render_form(f, form, sections[0]);
+        let mut lines = Vec::new();
+        lines.extend(preview_line);
+        if let Some(field) = form.selected_field() {
+            lines.push(Line::from(Span::styled(
+                field.help,
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+        f.render_widget(Paragraph::new(lines), sections[1]);
+    }
+
+    let keys = if wizard.is_editing() {
+        "Enter: confirm  Esc: cancel".to_string()
+    } else if wizard.step == WizardStep::Review {
+        app.keymap
+            .hints(&[(Action::Save, "create goal"), (Action::Back, "back")])
+    } else {
+        let back = if wizard.step == WizardStep::Basics {
+            "cancel"
+        } else {
+            "back"
+        };
+        app.keymap.hints(&[
+            (Action::Down, "move"),
+            (Action::EditField, "edit"),
+            (Action::NextStep, "next step"),
+            (Action::Back, back),
+        ])
+    };
+    render_form_footer(f, app, &keys, layout[1]);
+
+    if let Some(form) = wizard.form() {
+        render_form_input_modal(f, form, size);
+    }
+}
+
+fn render_settings(f: &mut ratatui::Frame, app: &App, settings: &mut SettingsState) {
+    let size = f.area();
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(2)])
+        .split(size);
+
+    let title = if settings.saving {
+        format!("{} - Settings (saving)", settings.goal_slug)
+    } else {
+        format!("{} - Settings", settings.goal_slug)
+    };
+    let block = Block::default().title_top(title).borders(Borders::ALL);
+    let inner = block.inner(layout[0]);
+    f.render_widget(block, layout[0]);
+
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+    render_form(f, &mut settings.form, sections[0]);
+    if let Some(field) = settings.form.selected_field() {
+        let help = Paragraph::new(field.help).style(Style::default().fg(Color::DarkGray));
+        f.render_widget(help, sections[1]);
+    }
+
+    let keys = if settings.form.input.is_some() {
+        "Enter: confirm  Esc: cancel".to_string()
+    } else if settings.confirm {
+        "y/Enter: save  n/Esc: back".to_string()
+    } else {
+        app.keymap.hints(&[
+            (Action::Down, "move"),
+            (Action::EditField, "edit"),
+            (Action::Save, "save"),
+            (Action::Back, "back"),
+        ])
+    };
+    render_form_footer(f, app, &keys, layout[1]);
+
+    render_form_input_modal(f, &settings.form, size);
+    if settings.confirm {
+        render_settings_confirm(f, settings, &app.theme, size);
+    }
+}
+
+/// Modal listing the pending changes before an update is sent.
+fn render_settings_confirm(
+    f: &mut ratatui::Frame,
+    settings: &SettingsState,
+    theme: &Theme,
+    area: Rect,
+) {
+    let popup = centered_rect(70, 50, area);
+    f.render_widget(Clear, popup);
+
+    let block = Block::default()
+        .title_top(format!("Update {}?", settings.goal_slug))
+        .title_bottom(Line::from("[y]es  [n]o").right_aligned())
+        .borders(Borders::ALL);
+    let inner = block.inner(popup);
+    f.render_widget(block, popup);
+
+    let lines: Vec<Line> = settings
+        .changes()
+        .into_iter()
+        .map(|(label, old, new)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{label:<12}"),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(old.to_string(), Style::default().fg(theme.error)),
+                Span::raw(" -> "),
+                Span::styled(new.to_string(), Style::default().fg(theme.success)),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_form(f: &mut ratatui::Frame, form: &mut Form, area: Rect) {
+    let rows = form
+        .fields
+        .iter()
+        .map(|field| {
+            let value = match field.kind {
+                FieldKind::Choice(_) => format!("< {} >", field.value),
+                FieldKind::Text | FieldKind::Number | FieldKind::Date => field.value.clone(),
+            };
+            Row::new(vec![Cell::from(field.label), Cell::from(value)])
+        })
+        .collect::<Vec<_>>();
+    let widths = vec![Constraint::Length(12), Constraint::Min(10)];
+    let table = Table::new(rows, widths)
+        .column_spacing(1)
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    ensure_table_state_visible(&mut form.table_state, area.height as usize);
+    f.render_stateful_widget(table, area, &mut form.table_state);
+}
+
+fn render_form_input_modal(f: &mut ratatui::Frame, form: &Form, area: Rect) {
+    if let (Some(input), Some(field)) = (&form.input, form.selected_field()) {
+        render_input_modal(f, &format!("Edit {}", field.label), input, area);
+    }
+}
+
+fn render_form_footer(f: &mut ratatui::Frame, app: &App, keys: &str, area: Rect) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Length(1)])
+        .split(area);
+
+    render_status_line(f, app, layout[0]);
+    f.render_widget(Paragraph::new(keys.to_string()), layout[1]);
+}
+
+/// Overlay listing the active key bindings, current context first.
+fn render_help(f: &mut ratatui::Frame, keymap: &Keymap, context: Context) {
+    let popup = centered_rect(70, 80, f.area());
+    f.render_widget(Clear, popup);
+
+    let block = Block::default()
+        .title_top("Keys")
+        .title_bottom(Line::from("any key: close").right_aligned())
+        .borders(Borders::ALL);
+    let inner = block.inner(popup);
+    f.render_widget(block, popup);
+
+    let mut sections = keymap.help();
+    sections.sort_by_key(|(section, _)| *section != context);
+    let mut lines = Vec::new();
+    for (section, rows) in sections {
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+        lines.push(Line::from(Span::styled(
+            section.label(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        for (keys, description) in rows {
+            lines.push(Line::from(format!("  {keys:<14} {description}")));
+        }
+    }
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
     let vertical = Layout::default()
         .direction(Direction::Vertical)
@@ -332,38 +1030,78 @@ fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
     horizontal[1]
 }
 
-fn render_footer_main(
-    f: &mut ratatui::Frame,
-    status: Option<&StatusMessage>,
-    main_input: &MainInput,
-    filter: &str,
-    area: Rect,
-) {
+fn render_footer_main(f: &mut ratatui::Frame, app: &App, area: Rect) {
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Length(1), Constraint::Length(1)])
         .split(area);
 
-    render_status_line(f, status, layout[0]);
+    match &app.main_input {
+        MainInput::Command(palette) if app.status.is_none() => {
+            let candidates = completions(&palette.input.buffer, &app.goal_slugs());
+            let text = candidates
+                .into_iter()
+                .take(MAX_CANDIDATES)
+                .collect::<Vec<_>>()
+                .join("  ");
+            let hint = Line::from(Span::styled(
+                format!("Tab: {text}"),
+                Style::default().fg(Color::DarkGray),
+            ));
+            f.render_widget(Paragraph::new(hint), layout[0]);
+        }
+        _ => render_status_line(f, app, layout[0]),
+    }
 
-    let line = match main_input {
+    let line = match &app.main_input {
         MainInput::InlineAdd { buffer } => Line::from(vec![
             Span::raw(format!("Add datapoint: {buffer}")),
-            Span::raw("  Enter: submit  Esc: cancel"),
+            Span::raw(
+                "  [@date [HH:MM]] value [comment], =value for a running total  Enter: submit  Esc: cancel",
+            ),
         ]),
         MainInput::Filter { buffer } => Line::from(vec![
             Span::raw(format!("Filter: {buffer}")),
-            Span::raw("  Enter: apply  Esc: cancel"),
+            Span::raw("  #tag: by tag  Enter: apply  Esc: cancel"),
         ]),
+        MainInput::Command(palette) => Line::from(format!(":{}", palette.input.buffer)),
+        MainInput::GoalAction(state) => Line::from(format!(
+            "{} on {}: type the slug to confirm  Esc: cancel",
+            state.action.label(),
+            state.slug
+        )),
         MainInput::Normal => {
-            if filter.is_empty() {
-                Line::from(
-                    "j/k or up/down: navigate  Enter: add  e: edit  /: filter  r: refresh  q: quit",
-                )
+            let hints = if app.show_archived {
+                app.keymap.hints(&[
+                    (Action::Down, "navigate"),
+                    (Action::Unarchive, "unarchive"),
+                    (Action::ToggleArchived, "active goals"),
+                    (Action::Filter, "filter"),
+                    (Action::Refresh, "refresh"),
+                    (Action::Quit, "quit"),
+                ])
+            } else {
+                app.keymap.hints(&[
+                    (Action::Down, "navigate"),
+                    (Action::AddDatapoint, "add"),
+                    (Action::EditDatapoints, "edit"),
+                    (Action::NewGoal, "new goal"),
+                    (Action::GoalSettings, "settings"),
+                    (Action::GoalActions, "actions"),
+                    (Action::ToggleArchived, "archived"),
+                    (Action::Filter, "filter"),
+                    (Action::Sort, "sort"),
+                    (Action::Group, "group"),
+                    (Action::Refresh, "refresh"),
+                    (Action::Quit, "quit"),
+                ])
+            };
+            if app.filter.is_empty() {
+                Line::from(hints)
             } else {
                 Line::from(vec![
-                    Span::raw(format!("Filter: {filter}  ")),
-                    Span::raw("j/k: navigate  Enter: add  e: edit  /: filter"),
+                    Span::raw(format!("Filter: {}  ", app.filter)),
+                    Span::raw(hints),
                 ])
             }
         }
@@ -399,16 +1137,6 @@ fn ensure_table_state_visible(state: &mut TableState, height: usize) {
     }
 }
 
-const fn goal_color(safebuf: i32) -> Color {
-    match safebuf {
-        0 => Color::Red,
-        1 => Color::Yellow,
-        2 => Color::Blue,
-        3..=6 => Color::Green,
-        _ => Color::White,
-    }
-}
-
-fn goal_pledge(goal: &beeminder::types::GoalSummary) -> Option<f64> {
+pub fn goal_pledge(goal: &GoalSummary) -> Option<f64> {
     goal.extra.get("pledge").and_then(serde_json::Value::as_f64)
 }